@@ -0,0 +1,104 @@
+-- The schema of the SQLite migrations 001 to 007, for PostgreSQL.
+--
+-- Timestamps are UTC without a time zone and whole seconds, so they read
+-- back in the same `YYYY-MM-DD HH:MM:SS` form as SQLite's.
+CREATE FUNCTION utc_now() RETURNS TIMESTAMP(0) AS $$
+    SELECT (now() AT TIME ZONE 'UTC')::TIMESTAMP(0)
+$$ LANGUAGE SQL STABLE;
+
+CREATE TABLE users (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    username TEXT UNIQUE NOT NULL,
+    email TEXT UNIQUE NOT NULL,
+    password_hash TEXT NOT NULL,
+    -- Administrators can see instance-wide status such as background jobs
+    is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP(0) NOT NULL DEFAULT utc_now()
+);
+
+CREATE TABLE sessions (
+    -- SHA-256 of the session cookie token
+    id TEXT PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    created_at TIMESTAMP(0) NOT NULL DEFAULT utc_now(),
+    -- Sliding expiry, pushed forward on activity
+    expires_at TIMESTAMP(0) NOT NULL,
+    -- Hard upper bound regardless of activity
+    absolute_expires_at TIMESTAMP(0) NOT NULL
+);
+
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
+
+CREATE TABLE todos (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    title TEXT NOT NULL,
+    completed BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMP(0) NOT NULL DEFAULT utc_now(),
+    updated_at TIMESTAMP(0) NOT NULL DEFAULT utc_now(),
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_todos_user_id ON todos (user_id);
+
+-- Personal access tokens for scripts and integrations
+CREATE TABLE api_tokens (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    -- SHA-256 of the token; the token itself is only shown once
+    token_hash TEXT UNIQUE NOT NULL,
+    scope TEXT NOT NULL CHECK (scope IN ('read', 'write')),
+    created_at TIMESTAMP(0) NOT NULL DEFAULT utc_now(),
+    expires_at TIMESTAMP(0),
+    last_used_at TIMESTAMP(0)
+);
+
+CREATE INDEX idx_api_tokens_user_id ON api_tokens (user_id);
+
+-- Outgoing webhooks and their delivery queue
+CREATE TABLE webhooks (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    -- Key for the HMAC signature on every delivery
+    secret TEXT NOT NULL,
+    -- Comma-separated event names, e.g. 'todo.created,todo.deleted'
+    events TEXT NOT NULL,
+    created_at TIMESTAMP(0) NOT NULL DEFAULT utc_now()
+);
+
+CREATE INDEX idx_webhooks_user_id ON webhooks (user_id);
+
+CREATE TABLE webhook_deliveries (
+    id BIGINT GENERATED BY DEFAULT AS IDENTITY PRIMARY KEY,
+    webhook_id BIGINT NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'delivered', 'failed')),
+    attempts BIGINT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP(0) NOT NULL DEFAULT utc_now(),
+    last_status_code BIGINT,
+    last_error TEXT,
+    created_at TIMESTAMP(0) NOT NULL DEFAULT utc_now(),
+    delivered_at TIMESTAMP(0)
+);
+
+CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries (status, next_attempt_at);
+CREATE INDEX idx_webhook_deliveries_webhook_id ON webhook_deliveries (webhook_id);
+
+-- Secret per-user iCalendar feed URLs
+CREATE TABLE calendar_feeds (
+    user_id BIGINT PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    -- SHA-256 of the token in the feed URL; the URL itself is only shown once
+    token_hash TEXT UNIQUE NOT NULL,
+    created_at TIMESTAMP(0) NOT NULL DEFAULT utc_now()
+);
+
+-- Resource names and UIDs chosen by CalDAV clients for the todos they create
+CREATE TABLE caldav_resources (
+    todo_id BIGINT PRIMARY KEY REFERENCES todos (id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    uid TEXT NOT NULL,
+    UNIQUE (user_id, name)
+);
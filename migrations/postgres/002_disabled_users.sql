@@ -0,0 +1,4 @@
+-- SQLite migration 008: operators can disable an account instead of deleting
+-- it; disabled accounts can't sign in and their sessions and tokens stop
+-- working.
+ALTER TABLE users ADD COLUMN disabled_at TIMESTAMP(0);
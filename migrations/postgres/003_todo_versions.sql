@@ -0,0 +1,4 @@
+-- SQLite migration 009: bumped on every change to a todo. ETags and
+-- conditional updates use it rather than updated_at, which only has
+-- one-second resolution.
+ALTER TABLE todos ADD COLUMN version BIGINT NOT NULL DEFAULT 1;
@@ -0,0 +1,5 @@
+-- SQLite migration 010: each /metrics scrape counts the todos created today
+-- and the sessions that haven't expired; without these indexes both scan
+-- the whole table.
+CREATE INDEX idx_todos_created_at ON todos (created_at);
+CREATE INDEX idx_sessions_expires_at ON sessions (expires_at);
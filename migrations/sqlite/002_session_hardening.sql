@@ -0,0 +1,16 @@
+-- Session ids are now stored as SHA-256 hashes of the cookie token, so the
+-- existing plaintext rows can't be carried over; everyone signs in again.
+DROP TABLE sessions;
+
+CREATE TABLE sessions (
+    id TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    -- Sliding expiry, pushed forward on activity
+    expires_at DATETIME NOT NULL,
+    -- Hard upper bound regardless of activity
+    absolute_expires_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_sessions_user_id ON sessions (user_id);
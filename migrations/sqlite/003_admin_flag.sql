@@ -0,0 +1,3 @@
+-- Administrators can see instance-wide status such as background jobs.
+-- Nobody is one until an operator runs `todo-leptos user promote`
+ALTER TABLE users ADD COLUMN is_admin BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,8 @@
+-- Secret per-user iCalendar feed URLs
+CREATE TABLE calendar_feeds (
+    user_id INTEGER PRIMARY KEY,
+    -- SHA-256 of the token in the feed URL; the URL itself is only shown once
+    token_hash TEXT UNIQUE NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,12 @@
+-- Resource names and UIDs chosen by CalDAV clients for the todos they create,
+-- so later requests for the same resource find the same todo. Todos created
+-- elsewhere are addressed as `{id}.ics` and need no row here.
+CREATE TABLE caldav_resources (
+    todo_id INTEGER PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    uid TEXT NOT NULL,
+    UNIQUE (user_id, name),
+    FOREIGN KEY (todo_id) REFERENCES todos (id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
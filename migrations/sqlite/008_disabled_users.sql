@@ -0,0 +1,3 @@
+-- Operators can disable an account instead of deleting it; disabled accounts
+-- can't sign in and their sessions and tokens stop working.
+ALTER TABLE users ADD COLUMN disabled_at DATETIME;
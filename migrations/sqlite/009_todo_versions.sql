@@ -0,0 +1,3 @@
+-- Bumped on every change to a todo. ETags and conditional updates use it
+-- rather than updated_at, which only has one-second resolution.
+ALTER TABLE todos ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
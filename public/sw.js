@@ -0,0 +1,63 @@
+// Service worker: keeps the app shell available offline.
+//
+// Pages and static assets are fetched network-first and fall back to the
+// last cached copy; `/api` calls are never cached, the hydrated client keeps
+// its own copy of the todo list in IndexedDB. Cached pages are rendered for
+// whoever was signed in, so `offline::clear()` deletes them on logout.
+
+const CACHE = "todo-leptos-v1";
+const SHELL = [
+    "/",
+    "/pkg/todo-leptos.js",
+    "/pkg/todo-leptos_bg.wasm",
+    "/pkg/todo-leptos.css",
+    "/manifest.webmanifest",
+    "/icon.svg",
+];
+
+self.addEventListener("install", (event) => {
+    event.waitUntil(
+        caches
+            .open(CACHE)
+            .then((cache) => Promise.allSettled(SHELL.map((url) => cache.add(url))))
+            .then(() => self.skipWaiting())
+    );
+});
+
+self.addEventListener("activate", (event) => {
+    event.waitUntil(
+        caches
+            .keys()
+            .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+            .then(() => self.clients.claim())
+    );
+});
+
+self.addEventListener("fetch", (event) => {
+    const request = event.request;
+    const url = new URL(request.url);
+    if (request.method !== "GET" || url.origin !== self.location.origin || url.pathname.startsWith("/api/")) {
+        return;
+    }
+
+    event.respondWith(
+        fetch(request)
+            .then((response) => {
+                if (response.ok) {
+                    const copy = response.clone();
+                    caches.open(CACHE).then((cache) => cache.put(request, copy));
+                }
+                return response;
+            })
+            .catch(async () => {
+                const cached = await caches.match(request);
+                if (cached) {
+                    return cached;
+                }
+                if (request.mode === "navigate") {
+                    return (await caches.match("/")) ?? Response.error();
+                }
+                return Response.error();
+            })
+    );
+});
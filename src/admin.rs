@@ -0,0 +1,41 @@
+use crate::csrf::CsrfClient;
+use crate::error::AppError;
+use crate::models::{Backup, JobStatus};
+use leptos::prelude::*;
+
+#[cfg(feature = "ssr")]
+use crate::auth::require_admin;
+#[cfg(feature = "ssr")]
+use crate::backup::{self, BackupError};
+#[cfg(feature = "ssr")]
+use crate::config::Config;
+#[cfg(feature = "ssr")]
+use crate::database::Database;
+#[cfg(feature = "ssr")]
+use crate::jobs::JobRunner;
+#[cfg(feature = "ssr")]
+use crate::models::TokenScope;
+
+#[server(GetJobStatuses, "/api", client = CsrfClient)]
+pub async fn get_job_statuses() -> Result<Vec<JobStatus>, AppError> {
+    require_admin(TokenScope::Read).await?;
+
+    Ok(use_context::<JobRunner>()
+        .map(|runner| runner.statuses())
+        .unwrap_or_default())
+}
+
+/// Writes a backup now, outside the `backup_database` schedule.
+#[server(CreateBackup, "/api", client = CsrfClient)]
+pub async fn create_backup() -> Result<Backup, AppError> {
+    require_admin(TokenScope::Write).await?;
+    let db = expect_context::<Database>();
+    let config = expect_context::<Config>();
+
+    backup::create(&db, &config.backup).await.map_err(|e| match e {
+        BackupError::NotConfigured | BackupError::Unsupported | BackupError::Exists { .. } => {
+            AppError::Conflict(e.to_string())
+        }
+        e => AppError::internal(e),
+    })
+}
@@ -0,0 +1,66 @@
+use crate::csrf::CsrfClient;
+use crate::error::AppError;
+use crate::models::{ApiToken, CreateApiToken, NewApiToken};
+use leptos::prelude::*;
+
+#[cfg(feature = "ssr")]
+use crate::auth::require_session_user;
+#[cfg(feature = "ssr")]
+use crate::database::Database;
+
+#[server(ListApiTokens, "/api", client = CsrfClient)]
+pub async fn list_api_tokens() -> Result<Vec<ApiToken>, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    Ok(db.list_api_tokens(user.id).await?)
+}
+
+#[server(GenerateApiToken, "/api", client = CsrfClient)]
+pub async fn generate_api_token(request: CreateApiToken) -> Result<NewApiToken, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    issue_api_token(&db, user.id, request).await
+}
+
+/// Validates and creates a token for `user_id`. Shared by the
+/// `GenerateApiToken` server function and the REST API.
+#[cfg(feature = "ssr")]
+pub(crate) async fn issue_api_token(
+    db: &Database,
+    user_id: i64,
+    request: CreateApiToken,
+) -> Result<NewApiToken, AppError> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(AppError::validation("name", "Token name cannot be empty."));
+    }
+    if request.expires_in_days.is_some_and(|days| days <= 0) {
+        return Err(AppError::validation(
+            "expires_in_days",
+            "Expiry must be at least one day.",
+        ));
+    }
+
+    let expires_in = request.expires_in_days.map(chrono::Duration::days);
+    let (token, api_token) =
+        db.create_api_token(user_id, name, request.scope, expires_in).await?;
+
+    Ok(NewApiToken { token, api_token })
+}
+
+#[server(RevokeApiToken, "/api", client = CsrfClient)]
+pub async fn revoke_api_token(id: i64) -> Result<(), AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    if db.delete_api_token(user.id, id).await? {
+        Ok(())
+    } else {
+        Err(AppError::NotFound)
+    }
+}
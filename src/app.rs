@@ -1,13 +1,24 @@
 use crate::auth::*;
 use crate::components::auth::{LoginForm, SignupForm};
+use crate::components::calendar::CalendarSettings;
+use crate::components::export::ExportMenu;
+use crate::components::import::ImportForm;
 use crate::components::nav::Navigation;
-use crate::models::{CreateTodo, Todo, User};
+use crate::components::settings::ApiTokenSettings;
+use crate::components::webhooks::WebhookSettings;
+use crate::csrf;
+use crate::error::AppError;
+use crate::events;
+use crate::models::{Todo, User};
+use crate::offline;
 use crate::server_functions::*;
+use crate::store::TodoStore;
 use leptos::prelude::*;
 use leptos::task::spawn_local;
 use leptos_meta::{provide_meta_context, MetaTags, Stylesheet, Title};
 use leptos_router::{
     components::{Redirect, Route, Router, Routes},
+    hooks::use_navigate,
     StaticSegment,
 };
 
@@ -18,6 +29,12 @@ pub struct UserContext {
     pub loading: RwSignal<bool>,
 }
 
+impl Default for UserContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl UserContext {
     pub fn new() -> Self {
         Self {
@@ -29,17 +46,24 @@ impl UserContext {
     pub async fn check_user(&self) {
         self.loading.set(true);
         match get_current_user().await {
-            Ok(user) => self.user.set(user),
+            Ok(user) => {
+                offline::save(offline::USER_KEY, &user);
+                self.user.set(user)
+            }
+            // Offline: carry on as whoever was signed in last time
+            Err(AppError::Request(_)) => self.user.set(offline::load(offline::USER_KEY).await.flatten()),
             Err(_) => self.user.set(None),
         }
         self.loading.set(false);
     }
 
     pub fn logout(&self) {
+        offline::clear();
         self.user.set(None);
     }
 
     pub fn login(&self, user: User) {
+        offline::save(offline::USER_KEY, &Some(user.clone()));
         self.user.set(Some(user));
     }
 }
@@ -51,6 +75,9 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
             <head>
                 <meta charset="utf-8"/>
                 <meta name="viewport" content="width=device-width, initial-scale=1"/>
+                <meta name="csrf-token" content=csrf::request_token().unwrap_or_default()/>
+                <meta name="theme-color" content="#007bff"/>
+                <link rel="manifest" href="/manifest.webmanifest"/>
                 <AutoReload options=options.clone() />
                 <HydrationScripts options/>
                 <MetaTags/>
@@ -89,6 +116,8 @@ pub fn App() -> impl IntoView {
                     <Route path=StaticSegment("") view=HomePage/>
                     <Route path=StaticSegment("login") view=LoginPage/>
                     <Route path=StaticSegment("signup") view=SignupPage/>
+                    <Route path=StaticSegment("settings") view=SettingsPage/>
+                    <Route path=StaticSegment("import") view=ImportPage/>
                 </Routes>
             </main>
         </Router>
@@ -146,27 +175,68 @@ fn HomePage() -> impl IntoView {
     }
 }
 
+#[component]
+fn SettingsPage() -> impl IntoView {
+    let user_context = expect_context::<UserContext>();
+
+    view! {
+        {move || {
+            if user_context.loading.get() {
+                view! { <div class="loading">"Loading..."</div> }.into_any()
+            } else if user_context.user.get().is_some() {
+                view! {
+                    <div class="container">
+                        <ApiTokenSettings/>
+                        <WebhookSettings/>
+                        <CalendarSettings/>
+                    </div>
+                }.into_any()
+            } else {
+                view! { <Redirect path="/login"/> }.into_any()
+            }
+        }}
+    }
+}
+
+#[component]
+fn ImportPage() -> impl IntoView {
+    let user_context = expect_context::<UserContext>();
+
+    view! {
+        {move || {
+            if user_context.loading.get() {
+                view! { <div class="loading">"Loading..."</div> }.into_any()
+            } else if user_context.user.get().is_some() {
+                view! {
+                    <div class="container">
+                        <ImportForm/>
+                    </div>
+                }.into_any()
+            } else {
+                view! { <Redirect path="/login"/> }.into_any()
+            }
+        }}
+    }
+}
+
 #[component]
 fn TodoApp(user: User) -> impl IntoView {
-    let todos = Resource::new(|| (), |_| get_todos());
-    let add_todo_action = ServerAction::<AddTodo>::new();
-    let toggle_todo_action = ServerAction::<ToggleTodo>::new();
-    let delete_todo_action = ServerAction::<DeleteTodo>::new();
+    let user_context = expect_context::<UserContext>();
+    let navigate = use_navigate();
+    let store = TodoStore::new();
+    let todos = store.todos;
+    let user_id = user.id;
 
     let (new_todo_title, set_new_todo_title) = signal(String::new());
     let (search_id, set_search_id) = signal(String::new());
-    let (search_result, set_search_result) = signal(None::<Option<Todo>>);
+    let (search_result, set_search_result) = signal(None::<Todo>);
     let (search_error, set_search_error) = signal(None::<String>);
 
     let submit_todo = move |ev: leptos::ev::SubmitEvent| {
         ev.prevent_default();
         let title = new_todo_title.get();
         if !title.trim().is_empty() {
-            add_todo_action.dispatch(AddTodo {
-                todo: CreateTodo {
-                    title: title.trim().to_string(),
-                },
-            });
+            store.add(title.trim().to_string(), user_id);
             set_new_todo_title.set(String::new());
         }
     };
@@ -182,9 +252,13 @@ fn TodoApp(user: User) -> impl IntoView {
                         Ok(todo) => {
                             set_search_result.set(Some(todo));
                         }
+                        Err(AppError::NotFound) => {
+                            set_search_result.set(None);
+                            set_search_error.set(Some("No todo found with that ID".to_string()));
+                        }
                         Err(e) => {
                             set_search_result.set(None);
-                            set_search_error.set(Some(e.to_string()));
+                            set_search_error.set(Some(e.user_message()));
                         }
                     }
                 });
@@ -195,11 +269,21 @@ fn TodoApp(user: User) -> impl IntoView {
         }
     };
 
+    // Keep the list in step with changes made in other tabs and devices
+    events::subscribe(move |event| store.apply(event));
+
+    // A lapsed session surfaces as `Unauthorized` from any of the calls above;
+    // send the user back to the login page instead of showing an error.
     Effect::new(move |_| {
-        add_todo_action.version().get();
-        toggle_todo_action.version().get();
-        delete_todo_action.version().get();
-        todos.refetch();
+        let unauthorized = |result: Option<Result<_, AppError>>| {
+            matches!(result, Some(Err(AppError::Unauthorized)))
+        };
+        if unauthorized(todos.get().map(|r| r.map(|_| ())))
+            || unauthorized(store.error.get().map(Err))
+        {
+            user_context.logout();
+            navigate("/login", Default::default());
+        }
     });
 
     view! {
@@ -209,6 +293,8 @@ fn TodoApp(user: User) -> impl IntoView {
                 <p class="user-welcome">"Welcome back, " {user.username.clone()}</p>
             </div>
 
+            <ExportMenu/>
+
             <form on:submit=submit_todo class="todo-form">
                 <div class="input-group">
                     <input
@@ -221,6 +307,22 @@ fn TodoApp(user: User) -> impl IntoView {
                 </div>
             </form>
 
+            {move || {
+                let pending = store.queue.with(Vec::len);
+                (pending > 0).then(|| view! {
+                    <p class="sync-status">
+                        {format!("Offline: {pending} change{} waiting to sync", if pending == 1 { "" } else { "s" })}
+                    </p>
+                })
+            }}
+
+            {move || store.error.get().map(|e| view! {
+                <div class="toast" role="alert">
+                    <span>{e.user_message()}</span>
+                    <button class="toast-close" on:click=move |_| store.error.set(None)>"✕"</button>
+                </div>
+            })}
+
             <form on:submit=search_todo class="search-form">
                 <div class="input-group">
                     <input
@@ -236,21 +338,17 @@ fn TodoApp(user: User) -> impl IntoView {
             {move || {
                 if let Some(error) = search_error.get() {
                     view! { <p class="error">"Search error: " {error}</p> }.into_any()
-                } else if let Some(result) = search_result.get() {
-                    if let Some(todo) = result {
-                        view! {
-                            <div class="search-result">
-                                <h3>"Search Result:"</h3>
-                                <div class="todo-item">
-                                    <span class="todo-id">"ID: " {todo.id}</span>
-                                    <span class="todo-title" class:completed=todo.completed>{todo.title}</span>
-                                    <span class="todo-status">{if todo.completed { "✓ Completed" } else { "○ Pending" }}</span>
-                                </div>
+                } else if let Some(todo) = search_result.get() {
+                    view! {
+                        <div class="search-result">
+                            <h3>"Search Result:"</h3>
+                            <div class="todo-item">
+                                <span class="todo-id">"ID: " {todo.id}</span>
+                                <span class="todo-title" class:completed=todo.completed>{todo.title}</span>
+                                <span class="todo-status">{if todo.completed { "✓ Completed" } else { "○ Pending" }}</span>
                             </div>
-                        }.into_any()
-                    } else {
-                        view! { <p class="no-result">"No todo found with that ID"</p> }.into_any()
-                    }
+                        </div>
+                    }.into_any()
                 } else {
                     view! { <div></div> }.into_any()
                 }
@@ -271,29 +369,25 @@ fn TodoApp(user: User) -> impl IntoView {
                                             children=move |todo: Todo| {
                                                 let todo_id = todo.id;
                                                 let is_completed = todo.completed;
+                                                let is_pending = TodoStore::is_pending(&todo);
                                                 let todo_title = todo.title.clone();
 
                                                 view! {
-                                                    <li class:completed=is_completed>
+                                                    <li class:completed=is_completed class:pending=is_pending>
                                                         <div class="todo-content">
                                                             <input
                                                                 type="checkbox"
                                                                 checked=is_completed
-                                                                on:change=move |_| {
-                                                                    toggle_todo_action.dispatch(ToggleTodo {
-                                                                        id: todo_id,
-                                                                        completed: !is_completed,
-                                                                    });
-                                                                }
+                                                                disabled=is_pending
+                                                                on:change=move |_| store.toggle(todo_id, !is_completed)
                                                             />
                                                             <span class="todo-id">"ID: " {todo_id} " - "</span>
                                                             <span class="todo-title">{todo_title}</span>
                                                         </div>
                                                         <button
                                                             class="delete-btn"
-                                                            on:click=move |_| {
-                                                                delete_todo_action.dispatch(DeleteTodo { id: todo_id });
-                                                            }
+                                                            disabled=is_pending
+                                                            on:click=move |_| store.delete(todo_id)
                                                         >
                                                             "✕"
                                                         </button>
@@ -305,7 +399,7 @@ fn TodoApp(user: User) -> impl IntoView {
                                 }.into_any()
                             }
                         }
-                        Some(Err(e)) => view! { <p class="error">"Error loading todos: " {e.to_string()}</p> }.into_any(),
+                        Some(Err(e)) => view! { <p class="error">"Error loading todos: " {e.user_message()}</p> }.into_any(),
                         None => view! { <p class="loading">"Loading todos..."</p> }.into_any()
                     }
                 }}
@@ -1,98 +1,336 @@
+use crate::csrf::CsrfClient;
+use crate::error::AppError;
 use crate::models::{LoginUser, RegisterUser, User};
+#[cfg(feature = "ssr")]
+use crate::models::TokenScope;
 use leptos::prelude::*;
 
-#[cfg(feature = "ssr")]
-use crate::database::queries;
 #[cfg(feature = "ssr")]
 use axum::http::{header::SET_COOKIE, HeaderValue};
 #[cfg(feature = "ssr")]
 use leptos_axum::ResponseOptions;
 #[cfg(feature = "ssr")]
-use sqlx::SqlitePool;
-
-#[server(Register, "/api")]
-pub async fn register(user_data: RegisterUser) -> Result<User, ServerFnError> {
-    let pool = expect_context::<SqlitePool>();
-    // Check if username or email already exists
-    let existing = sqlx::query!(
-        "SELECT id FROM users WHERE username = ? OR email = ?",
-        user_data.username,
-        user_data.email
-    )
-    .fetch_optional(&pool)
-    .await
-    .map_err(|e| ServerFnError::new(e.to_string()))?;
-
-    if existing.is_some() {
-        return Err(ServerFnError::new(
+use crate::database::Database;
+
+#[server(Register, "/api", client = CsrfClient)]
+pub async fn register(user_data: RegisterUser) -> Result<User, AppError> {
+    let db = expect_context::<Database>();
+    let config = expect_context::<crate::config::Config>();
+
+    register_user(&db, user_data, config.auth.bcrypt_cost).await
+}
+
+/// Validates and creates a new account. Shared by the `Register` server
+/// function, the REST API and `todo-leptos user create`.
+#[cfg(feature = "ssr")]
+pub(crate) async fn register_user(
+    db: &Database,
+    user_data: RegisterUser,
+    bcrypt_cost: u32,
+) -> Result<User, AppError> {
+    validate_registration(&user_data)?;
+
+    if db.user_exists(&user_data.username, &user_data.email).await? {
+        return Err(AppError::Conflict(
             "Username or email already exists".to_string(),
         ));
     }
 
-    queries::create_user(&pool, user_data)
-        .await
-        .map_err(|e| ServerFnError::new(e.to_string()))
+    Ok(db.create_user(user_data, bcrypt_cost).await?)
 }
 
-#[server(Login, "/api")]
-pub async fn login(login_data: LoginUser) -> Result<User, ServerFnError> {
-    let pool = expect_context::<SqlitePool>();
+#[server(Login, "/api", client = CsrfClient)]
+pub async fn login(login_data: LoginUser) -> Result<User, AppError> {
+    let db = expect_context::<Database>();
     let response = expect_context::<ResponseOptions>();
 
-    let user = queries::authenticate_user(&pool, login_data)
-        .await
-        .map_err(|e| ServerFnError::new(e.to_string()))?;
+    let user = db.authenticate_user(login_data).await?;
 
     if let Some(user) = user {
-        // Create session
-        let session = queries::create_session(&pool, user.id)
-            .await
-            .map_err(|e| ServerFnError::new(e.to_string()))?;
-
-        // Set session cookie
-        let cookie = format!(
-            "session_id={}; Path=/; HttpOnly; SameSite=Strict; Max-Age=2592000",
-            session.id
-        );
-        response.insert_header(SET_COOKIE, HeaderValue::from_str(&cookie).unwrap());
+        rotate_session(&db, &response, user.id).await?;
+        provide_context(CurrentUser::session(Some(user.clone())));
 
         Ok(user)
     } else {
-        Err(ServerFnError::new("Invalid credentials".to_string()))
+        Err(AppError::Unauthorized)
     }
 }
 
-#[server(Logout, "/api")]
-pub async fn logout() -> Result<(), ServerFnError> {
-    let pool = expect_context::<SqlitePool>();
+#[server(Logout, "/api", client = CsrfClient)]
+pub async fn logout() -> Result<(), AppError> {
+    let db = expect_context::<Database>();
     let response = expect_context::<ResponseOptions>();
+    let config = expect_context::<SessionConfig>();
 
     // Get session from cookie
     if let Some(session_id) = get_session_id().await {
-        queries::delete_session(&pool, &session_id)
-            .await
-            .map_err(|e| ServerFnError::new(e.to_string()))?;
+        db.delete_session(&session_id).await?;
     }
 
     // Clear session cookie
-    let cookie = "session_id=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0";
-    response.insert_header(SET_COOKIE, HeaderValue::from_str(cookie).unwrap());
+    let cookie = config.expired_session_cookie();
+    response.insert_header(SET_COOKIE, HeaderValue::from_str(&cookie).unwrap());
+    provide_context(CurrentUser::session(None));
 
     Ok(())
 }
 
-#[server(GetCurrentUser, "/api")]
-pub async fn get_current_user() -> Result<Option<User>, ServerFnError> {
-    let pool = expect_context::<SqlitePool>();
+#[server(GetCurrentUser, "/api", client = CsrfClient)]
+pub async fn get_current_user() -> Result<Option<User>, AppError> {
+    current_user().await
+}
 
-    if let Some(session_id) = get_session_id().await {
-        let result = queries::get_session(&pool, &session_id)
-            .await
-            .map_err(|e| ServerFnError::new(e.to_string()))?;
+/// Session lifetime and cookie attributes, set by the `session` part of
+/// [`crate::config::Config`].
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub cookie_name: String,
+    pub cookie_domain: Option<String>,
+    pub cookie_secure: bool,
+    /// How long a session survives without activity.
+    pub idle_timeout: chrono::Duration,
+    /// How long a session survives at most, however active it is.
+    pub absolute_timeout: chrono::Duration,
+}
+
+#[cfg(feature = "ssr")]
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            cookie_name: "session_id".to_string(),
+            cookie_domain: None,
+            cookie_secure: true,
+            idle_timeout: chrono::Duration::days(7),
+            absolute_timeout: chrono::Duration::days(30),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl SessionConfig {
+    /// Attributes shared by every cookie the server sets.
+    pub fn cookie_attributes(&self) -> String {
+        let mut attributes = "Path=/; HttpOnly; SameSite=Strict".to_string();
+        if self.cookie_secure {
+            attributes.push_str("; Secure");
+        }
+        if let Some(domain) = &self.cookie_domain {
+            attributes.push_str("; Domain=");
+            attributes.push_str(domain);
+        }
+        attributes
+    }
+
+    pub fn session_cookie(&self, token: &str) -> String {
+        format!(
+            "{}={}; {}; Max-Age={}",
+            self.cookie_name,
+            token,
+            self.cookie_attributes(),
+            self.absolute_timeout.num_seconds()
+        )
+    }
 
-        Ok(result.map(|(_, user)| user))
+    pub fn expired_session_cookie(&self) -> String {
+        format!("{}=; {}; Max-Age=0", self.cookie_name, self.cookie_attributes())
+    }
+}
+
+/// Replaces the request's session (if any) with a fresh one for `user_id` and
+/// sets the new cookie. Call this on login and whenever the user's privileges
+/// change, so a session id captured earlier can't be reused.
+#[cfg(feature = "ssr")]
+pub async fn rotate_session(
+    db: &Database,
+    response: &ResponseOptions,
+    user_id: i64,
+) -> Result<(), AppError> {
+    let config = expect_context::<SessionConfig>();
+
+    if let Some(old_token) = get_session_id().await {
+        db.delete_session(&old_token).await?;
+    }
+
+    let (token, _) = db
+        .create_session(user_id, config.idle_timeout, config.absolute_timeout)
+        .await?;
+
+    let cookie = config.session_cookie(&token);
+    response.insert_header(SET_COOKIE, HeaderValue::from_str(&cookie).unwrap());
+    Ok(())
+}
+
+/// Who is behind the current request, resolved once by
+/// [`middleware::resolve_session`] and cached for the rest of the request.
+#[cfg(feature = "ssr")]
+#[derive(Debug, Clone, Default)]
+pub struct CurrentUser {
+    pub user: Option<User>,
+    /// Set when the request authenticated with a personal access token
+    /// rather than the session cookie.
+    pub token_scope: Option<TokenScope>,
+}
+
+#[cfg(feature = "ssr")]
+impl CurrentUser {
+    pub fn session(user: Option<User>) -> Self {
+        Self {
+            user,
+            token_scope: None,
+        }
+    }
+
+    /// Returns the user, failing with [`AppError::Unauthorized`] when nobody
+    /// is signed in and [`AppError::Forbidden`] when an API token's scope
+    /// doesn't cover `required`.
+    pub fn require(&self, required: TokenScope) -> Result<User, AppError> {
+        let user = self.user.clone().ok_or(AppError::Unauthorized)?;
+
+        match self.token_scope {
+            Some(scope) if !scope.allows(required) => Err(AppError::Forbidden),
+            _ => Ok(user),
+        }
+    }
+}
+
+/// Resolves a `Authorization: Bearer` API token or, failing that, the
+/// session cookie. An invalid bearer token never falls back to the cookie.
+#[cfg(feature = "ssr")]
+async fn resolve_current_user(
+    db: &Database,
+    config: &SessionConfig,
+    headers: &axum::http::HeaderMap,
+) -> Result<CurrentUser, sqlx::Error> {
+    if let Some(token) = bearer_token(headers) {
+        return Ok(match db.authenticate_api_token(token).await? {
+            Some((scope, user)) => CurrentUser {
+                user: Some(user),
+                token_scope: Some(scope),
+            },
+            None => CurrentUser::default(),
+        });
+    }
+
+    let Some(token) = session_id_from_headers(headers, &config.cookie_name) else {
+        return Ok(CurrentUser::default());
+    };
+    let Some((session, user)) = db.get_session(&token).await? else {
+        return Ok(CurrentUser::default());
+    };
+
+    if let Err(e) = db.refresh_session(&session.id, config.idle_timeout).await {
+        tracing::error!(error = %e, "failed to refresh session");
+    }
+    Ok(CurrentUser::session(Some(user)))
+}
+
+/// Returns how the current request is authenticated.
+///
+/// Reads the result of the session middleware; when the middleware did not
+/// run it resolves the request's credentials once and caches the result in
+/// the request context.
+#[cfg(feature = "ssr")]
+pub async fn current_auth() -> Result<CurrentUser, AppError> {
+    use axum::http::request::Parts;
+
+    if let Some(current) = use_context::<CurrentUser>() {
+        return Ok(current);
+    }
+
+    let resolved = use_context::<Parts>()
+        .and_then(|parts| parts.extensions.get::<CurrentUser>().cloned());
+
+    let current = match resolved {
+        Some(current) => current,
+        None => {
+            let db = expect_context::<Database>();
+            let config = expect_context::<SessionConfig>();
+            let headers = leptos_axum::extract::<axum::http::HeaderMap>()
+                .await
+                .unwrap_or_default();
+            resolve_current_user(&db, &config, &headers).await?
+        }
+    };
+
+    provide_context(current.clone());
+    Ok(current)
+}
+
+/// Returns the signed-in user for this request, if any.
+#[cfg(feature = "ssr")]
+pub async fn current_user() -> Result<Option<User>, AppError> {
+    Ok(current_auth().await?.user)
+}
+
+/// Returns the signed-in user if the request may perform an action needing
+/// `required`; see [`CurrentUser::require`].
+#[cfg(feature = "ssr")]
+pub async fn require_scope(required: TokenScope) -> Result<User, AppError> {
+    current_auth().await?.require(required)
+}
+
+/// Returns the signed-in user for a read-only operation.
+#[cfg(feature = "ssr")]
+pub async fn require_user() -> Result<User, AppError> {
+    require_scope(TokenScope::Read).await
+}
+
+/// Returns the user signed in through the browser session. Used for account
+/// management that API tokens must not be able to do, such as minting more
+/// tokens.
+#[cfg(feature = "ssr")]
+pub async fn require_session_user() -> Result<User, AppError> {
+    let current = current_auth().await?;
+    let user = current.user.ok_or(AppError::Unauthorized)?;
+
+    if current.token_scope.is_some() {
+        Err(AppError::Forbidden)
+    } else {
+        Ok(user)
+    }
+}
+
+/// Like [`require_scope`], but also fails with [`AppError::Forbidden`]
+/// unless the user is an administrator.
+#[cfg(feature = "ssr")]
+pub async fn require_admin(required: TokenScope) -> Result<User, AppError> {
+    let user = require_scope(required).await?;
+    if user.is_admin {
+        Ok(user)
+    } else {
+        Err(AppError::Forbidden)
+    }
+}
+
+#[cfg(feature = "ssr")]
+fn validate_registration(user_data: &RegisterUser) -> Result<(), AppError> {
+    use std::collections::BTreeMap;
+
+    let mut fields = BTreeMap::new();
+    if user_data.username.trim().is_empty() {
+        fields.insert("username".to_string(), "Username is required.".to_string());
+    }
+    if !user_data.email.contains('@') {
+        fields.insert("email".to_string(), "Email address is invalid.".to_string());
+    }
+    if let Err(message) = validate_password(&user_data.password) {
+        fields.insert("password".to_string(), message.to_string());
+    }
+
+    if fields.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation { fields })
+    }
+}
+
+#[cfg(feature = "ssr")]
+pub(crate) fn validate_password(password: &str) -> Result<(), &'static str> {
+    if password.len() < 6 {
+        Err("Password must be at least 6 characters.")
     } else {
-        Ok(None)
+        Ok(())
     }
 }
 
@@ -102,14 +340,57 @@ async fn get_session_id() -> Option<String> {
 
     // Extract headers from the request
     let headers = extract::<axum::http::HeaderMap>().await.ok()?;
+    let config = expect_context::<SessionConfig>();
+    session_id_from_headers(&headers, &config.cookie_name)
+}
+
+#[cfg(feature = "ssr")]
+fn bearer_token(headers: &axum::http::HeaderMap) -> Option<&str> {
+    let value = headers.get(axum::http::header::AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(str::trim)
+}
+
+#[cfg(feature = "ssr")]
+fn session_id_from_headers(headers: &axum::http::HeaderMap, cookie_name: &str) -> Option<String> {
     let cookie_header = headers.get("cookie")?;
     let cookie_str = cookie_header.to_str().ok()?;
 
     for cookie in cookie_str.split(';') {
-        let cookie = cookie.trim();
-        if cookie.starts_with("session_id=") {
-            return Some(cookie[11..].to_string());
+        if let Some((name, value)) = cookie.trim().split_once('=') {
+            if name == cookie_name && !value.is_empty() {
+                return Some(value.to_string());
+            }
         }
     }
     None
 }
+
+#[cfg(feature = "ssr")]
+pub mod middleware {
+    use super::{resolve_current_user, CurrentUser, SessionConfig};
+    use axum::{extract::State, middleware::Next, response::Response};
+    use crate::database::Database;
+
+    /// Resolves the request's API token or session cookie into a
+    /// [`CurrentUser`] request extension so server functions don't each
+    /// query the database, and slides the session's expiry forward.
+    pub async fn resolve_session(
+        State((db, config)): State<(Database, SessionConfig)>,
+        mut req: axum::extract::Request,
+        next: Next,
+    ) -> Response {
+        let current = match resolve_current_user(&db, &config, req.headers()).await {
+            Ok(current) => current,
+            Err(e) => {
+                tracing::error!(error = %e, "failed to resolve session");
+                CurrentUser::default()
+            }
+        };
+        if let Some(user) = &current.user {
+            crate::telemetry::record_user(user.id);
+        }
+
+        req.extensions_mut().insert(current);
+        next.run(req).await
+    }
+}
@@ -0,0 +1,246 @@
+//! Online backups of the SQLite database, and restoring from one.
+//!
+//! Backups are consistent snapshots written with `VACUUM INTO` while the
+//! server keeps running, named `todos-<UTC timestamp>.db` so they sort by
+//! age. The `backup_database` job writes one on a schedule and admins can
+//! ask for one with [`crate::admin::create_backup`]; either way only the
+//! newest [`BackupConfig::keep`] are kept.
+//!
+//! `todo-leptos restore <file>` swaps a backup in for the database file.
+//! It checks the backup first, refuses while anything else has the
+//! database open, and keeps the replaced file next to it.
+
+use crate::config::{BackupConfig, DatabaseConfig};
+use crate::database::sqlite::MIGRATOR;
+use crate::database::{Backend, Database};
+use crate::models::Backup;
+use chrono::Utc;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteLockingMode};
+use sqlx::{ConnectOptions, Connection, SqliteConnection};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+const FILE_PREFIX: &str = "todos-";
+const FILE_SUFFIX: &str = ".db";
+
+#[derive(Debug, thiserror::Error)]
+pub enum BackupError {
+    #[error("no backup directory is configured")]
+    NotConfigured,
+    #[error("backups are only supported for SQLite database files")]
+    Unsupported,
+    #[error("{} already exists, a backup was just written", path.display())]
+    Exists { path: PathBuf },
+    #[error("{}: {source}", path.display())]
+    Io { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error("{} can't be restored: {reason}", path.display())]
+    Invalid { path: PathBuf, reason: String },
+    #[error("{} is in use, stop the server before restoring", path.display())]
+    InUse { path: PathBuf },
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> BackupError + '_ {
+    move |source| BackupError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// Writes a new backup to the configured directory and deletes the ones
+/// beyond [`BackupConfig::keep`].
+pub async fn create(db: &Database, config: &BackupConfig) -> Result<Backup, BackupError> {
+    let directory = config.directory.as_deref().ok_or(BackupError::NotConfigured)?;
+    std::fs::create_dir_all(directory).map_err(io_error(directory))?;
+
+    let now = Utc::now();
+    let file_name = format!("{FILE_PREFIX}{}{FILE_SUFFIX}", now.format("%Y%m%dT%H%M%SZ"));
+    let path = directory.join(&file_name);
+    if path.exists() {
+        return Err(BackupError::Exists { path });
+    }
+
+    // Written under another name first, so a crash mid-backup never leaves
+    // a truncated file that looks like a backup
+    let partial = directory.join(format!("{file_name}.partial"));
+    let _ = std::fs::remove_file(&partial);
+    db.backup(&partial).await.map_err(|e| match e {
+        sqlx::Error::Configuration(_) => BackupError::Unsupported,
+        e => e.into(),
+    })?;
+    std::fs::rename(&partial, &path).map_err(io_error(&path))?;
+
+    let size_bytes = std::fs::metadata(&path).map_err(io_error(&path))?.len();
+    rotate(directory, config.keep)?;
+
+    Ok(Backup {
+        file_name,
+        size_bytes,
+        created_at: now.format("%Y-%m-%d %H:%M:%S").to_string(),
+    })
+}
+
+/// Deletes all but the newest `keep` backups in `directory`.
+fn rotate(directory: &Path, keep: usize) -> Result<(), BackupError> {
+    let mut backups: Vec<PathBuf> = std::fs::read_dir(directory)
+        .map_err(io_error(directory))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX))
+        })
+        .collect();
+    backups.sort();
+
+    let excess = backups.len().saturating_sub(keep);
+    for path in &backups[..excess] {
+        std::fs::remove_file(path).map_err(io_error(path))?;
+    }
+    Ok(())
+}
+
+/// Replaces the database file in `config` with `backup`, returning where
+/// the replaced file was moved to, if there was one.
+///
+/// The server must be stopped: the database is locked exclusively for the
+/// swap, failing with [`BackupError::InUse`] while any other connection
+/// has it open. The backup has to pass SQLite's integrity
+/// check and may not contain migrations this binary doesn't know; older
+/// backups are brought up to date the next time the server starts.
+pub async fn restore(config: &DatabaseConfig, backup: &Path) -> Result<Option<PathBuf>, BackupError> {
+    if Backend::from_url(&config.url) != Some(Backend::Sqlite)
+        || config.url.contains(":memory:")
+        || config.url.contains("mode=memory")
+    {
+        return Err(BackupError::Unsupported);
+    }
+    let target = SqliteConnectOptions::from_str(&config.url)?
+        .get_filename()
+        .into_owned();
+
+    validate(backup).await?;
+
+    // Held until the files are swapped, so no server can open the old file
+    // in between
+    let lock = if target.exists() {
+        Some(lock_exclusively(&target).await?)
+    } else {
+        None
+    };
+
+    let directory = target.parent().unwrap_or(Path::new("."));
+    let file_name = target
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("database");
+    let with_suffix = |suffix: &str| directory.join(format!("{file_name}{suffix}"));
+
+    // Copied next to the target first, so the final step is a rename on
+    // the same file system
+    let incoming = with_suffix(".restoring");
+    std::fs::copy(backup, &incoming).map_err(io_error(&incoming))?;
+
+    let previous = if target.exists() {
+        let previous = with_suffix(&format!(".pre-restore-{}", Utc::now().format("%Y%m%dT%H%M%SZ")));
+        std::fs::rename(&target, &previous).map_err(io_error(&target))?;
+        // The WAL may hold commits the main file doesn't have yet
+        for suffix in ["-wal", "-shm"] {
+            let sidecar = with_suffix(suffix);
+            if sidecar.exists() {
+                let moved = PathBuf::from(format!("{}{suffix}", previous.display()));
+                std::fs::rename(&sidecar, &moved).map_err(io_error(&sidecar))?;
+            }
+        }
+        Some(previous)
+    } else {
+        None
+    };
+
+    std::fs::rename(&incoming, &target).map_err(io_error(&target))?;
+    if let Some(lock) = lock {
+        lock.close().await?;
+    }
+    Ok(previous)
+}
+
+/// Opens `path` and takes an exclusive lock on it, which only succeeds if
+/// no other connection has the database open, then checkpoints its WAL into
+/// the main file.
+async fn lock_exclusively(path: &Path) -> Result<SqliteConnection, BackupError> {
+    let mut connection = SqliteConnectOptions::new()
+        .filename(path)
+        .locking_mode(SqliteLockingMode::Exclusive)
+        .busy_timeout(Duration::ZERO)
+        .connect()
+        .await?;
+
+    let in_use = |_| BackupError::InUse {
+        path: path.to_path_buf(),
+    };
+    sqlx::query("BEGIN EXCLUSIVE")
+        .execute(&mut connection)
+        .await
+        .map_err(in_use)?;
+    sqlx::query("COMMIT").execute(&mut connection).await?;
+    // Still exclusive: in this locking mode the lock outlives the transaction
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(&mut connection)
+        .await
+        .map_err(in_use)?;
+    Ok(connection)
+}
+
+/// Checks that `backup` is an intact database with a schema this binary
+/// can run against.
+async fn validate(backup: &Path) -> Result<(), BackupError> {
+    let invalid = |reason: String| BackupError::Invalid {
+        path: backup.to_path_buf(),
+        reason,
+    };
+    if !backup.is_file() {
+        return Err(invalid("no such file".to_string()));
+    }
+
+    let mut connection = SqliteConnectOptions::new()
+        .filename(backup)
+        .read_only(true)
+        .connect()
+        .await?;
+
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut connection)
+        .await
+        .map_err(|e| invalid(e.to_string()))?;
+    if integrity != "ok" {
+        return Err(invalid(format!("integrity check failed: {integrity}")));
+    }
+
+    let applied: Vec<(i64, bool, Vec<u8>)> =
+        sqlx::query_as("SELECT version, success, checksum FROM _sqlx_migrations ORDER BY version")
+            .fetch_all(&mut connection)
+            .await
+            .map_err(|_| invalid("it has no migration history".to_string()))?;
+    connection.close().await?;
+
+    if applied.is_empty() {
+        return Err(invalid("it has no migration history".to_string()));
+    }
+    for (version, success, checksum) in applied {
+        let Some(known) = MIGRATOR.iter().find(|migration| migration.version == version) else {
+            return Err(invalid(format!(
+                "it has migration {version}, which this version of the server doesn't know"
+            )));
+        };
+        if !success {
+            return Err(invalid(format!("migration {version} didn't complete")));
+        }
+        if *known.checksum != checksum[..] {
+            return Err(invalid(format!("migration {version} differs from this server's")));
+        }
+    }
+    Ok(())
+}
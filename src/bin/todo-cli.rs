@@ -0,0 +1,375 @@
+//! Terminal client for a todo-leptos server:
+//!
+//! ```text
+//! todo-cli login --server https://todos.example.com --username alice
+//! todo-cli add Buy milk
+//! todo-cli ls --open
+//! todo-cli done 12 13
+//! ```
+//!
+//! `login` saves the server and a personal API token to a config file;
+//! `TODO_SERVER` and `TODO_TOKEN` take precedence over it.
+
+use clap::{Args as ClapArgs, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use todo_leptos::client::{Client, ClientError};
+use todo_leptos::models::{Todo, UpdateTodo};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Manages your todos on a todo-leptos server")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+    /// Where the credentials are saved [env: TODO_CLI_CONFIG] [default: ~/.config/todo-cli/config.toml]
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Print JSON instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Sign in to a server and save the credentials
+    Login {
+        /// e.g. https://todos.example.com
+        #[arg(long)]
+        server: String,
+        /// A personal API token from Settings
+        #[arg(long, conflicts_with = "username")]
+        token: Option<String>,
+        /// Sign in with a password instead, read from standard input
+        #[arg(long)]
+        username: Option<String>,
+    },
+    /// Forget the saved credentials
+    Logout,
+    /// Add a todo
+    Add {
+        #[arg(required = true)]
+        title: Vec<String>,
+    },
+    /// List todos, newest first
+    Ls(Filter),
+    /// Mark todos as done
+    Done {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+        /// Mark them as not done instead
+        #[arg(long)]
+        undo: bool,
+    },
+    /// Delete todos
+    Rm {
+        #[arg(required = true)]
+        ids: Vec<i64>,
+    },
+    /// Change the title of a todo
+    Edit {
+        id: i64,
+        #[arg(required = true)]
+        title: Vec<String>,
+    },
+    /// List todos whose title contains all of the words
+    Search {
+        #[arg(required = true)]
+        words: Vec<String>,
+        #[command(flatten)]
+        filter: Filter,
+    },
+}
+
+#[derive(Debug, ClapArgs)]
+struct Filter {
+    /// Only completed todos
+    #[arg(long, conflicts_with = "open")]
+    done: bool,
+    /// Only todos that aren't completed
+    #[arg(long)]
+    open: bool,
+}
+
+impl Filter {
+    fn completed(&self) -> Option<bool> {
+        match (self.done, self.open) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// What `login` saves.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Credentials {
+    server: Option<String>,
+    token: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum Error {
+    #[error("not signed in, run `todo-cli login --server <URL>` first")]
+    NotSignedIn,
+    #[error("no config file location, pass --config")]
+    NoConfigPath,
+    #[error("{}: {reason}", path.display())]
+    Config { path: PathBuf, reason: String },
+    #[error(transparent)]
+    Client(#[from] ClientError),
+    /// The ones before `id` went through and were reported.
+    #[error("stopped at todo {id}: {source}")]
+    Stopped { id: i64, source: ClientError },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+    if let Err(e) = run(args, &mut io::stdin().lock(), &mut io::stdout().lock()).await {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    }
+}
+
+async fn run(args: Args, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), Error> {
+    let path = match args.config.or_else(|| std::env::var_os("TODO_CLI_CONFIG").map(PathBuf::from)) {
+        Some(path) => path,
+        None => default_config_path().ok_or(Error::NoConfigPath)?,
+    };
+
+    match args.command {
+        Command::Login {
+            server,
+            token,
+            username,
+        } => {
+            let token = match (token, username) {
+                (Some(token), _) => token,
+                (None, username) => {
+                    let username = match username {
+                        Some(username) => username,
+                        None => prompt("Username: ", input)?,
+                    };
+                    let password = prompt_password(input)?;
+                    Client::new(&server, None)?
+                        .create_token(&username, &password, &token_name())
+                        .await?
+                        .token
+                }
+            };
+            let client = Client::new(&server, Some(token.clone()))?;
+            let user = client.current_user().await?;
+
+            save(
+                &path,
+                &Credentials {
+                    server: Some(client.server().to_string()),
+                    token: Some(token),
+                },
+            )?;
+            writeln!(output, "signed in to {} as {}", client.server(), user.username)?;
+        }
+        Command::Logout => {
+            match std::fs::remove_file(&path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(config_error(&path, e)),
+                _ => {}
+            }
+            writeln!(
+                output,
+                "removed the saved credentials; the token works until you revoke it in Settings"
+            )?;
+        }
+        command => {
+            let client = signed_in(&path)?;
+            todos(&client, command, args.json, output).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn todos(client: &Client, command: Command, json: bool, output: &mut dyn Write) -> Result<(), Error> {
+    match command {
+        Command::Login { .. } | Command::Logout => unreachable!("handled without a client"),
+        Command::Add { title } => {
+            let todo = client.create_todo(&title.join(" ")).await?;
+            print_todos(&[todo], json, output)?;
+        }
+        Command::Ls(filter) => {
+            let todos = client.list_todos(filter.completed()).await?;
+            print_todos(&todos, json, output)?;
+        }
+        Command::Done { ids, undo } => {
+            let update = UpdateTodo {
+                completed: Some(!undo),
+                ..UpdateTodo::default()
+            };
+            let (updated, stopped) = each(&ids, |id| client.update_todo(id, &update)).await;
+            if !updated.is_empty() || stopped.is_none() {
+                print_todos(&updated, json, output)?;
+            }
+            if let Some(e) = stopped {
+                return Err(e);
+            }
+        }
+        Command::Rm { ids } => {
+            let (deleted, stopped) = each(&ids, |id| async move { client.delete_todo(id).await.map(|()| id) }).await;
+            if json {
+                if !deleted.is_empty() || stopped.is_none() {
+                    writeln!(output, "{}", serde_json::json!({ "deleted": deleted }))?;
+                }
+            } else {
+                for id in deleted {
+                    writeln!(output, "deleted {id}")?;
+                }
+            }
+            if let Some(e) = stopped {
+                return Err(e);
+            }
+        }
+        Command::Edit { id, title } => {
+            let update = UpdateTodo {
+                title: Some(title.join(" ")),
+                ..UpdateTodo::default()
+            };
+            let todo = client.update_todo(id, &update).await?;
+            print_todos(&[todo], json, output)?;
+        }
+        Command::Search { words, filter } => {
+            let words: Vec<String> = words.iter().map(|word| word.to_lowercase()).collect();
+            let todos: Vec<Todo> = client
+                .list_todos(filter.completed())
+                .await?
+                .into_iter()
+                .filter(|todo| {
+                    let title = todo.title.to_lowercase();
+                    words.iter().all(|word| title.contains(word.as_str()))
+                })
+                .collect();
+            print_todos(&todos, json, output)?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `action` on each of `ids` in turn, stopping at the first that
+/// fails. Returns the results so far along with that failure, so what did
+/// go through can still be reported.
+async fn each<T, F, Fut>(ids: &[i64], mut action: F) -> (Vec<T>, Option<Error>)
+where
+    F: FnMut(i64) -> Fut,
+    Fut: Future<Output = Result<T, ClientError>>,
+{
+    let mut results = Vec::new();
+    for &id in ids {
+        match action(id).await {
+            Ok(result) => results.push(result),
+            Err(source) => return (results, Some(Error::Stopped { id, source })),
+        }
+    }
+    (results, None)
+}
+
+fn print_todos(todos: &[Todo], json: bool, output: &mut dyn Write) -> io::Result<()> {
+    if json {
+        let json = serde_json::to_string_pretty(todos).map_err(io::Error::other)?;
+        return writeln!(output, "{json}");
+    }
+    writeln!(output, "{:>6}  DONE  {:19}  TITLE", "ID", "UPDATED")?;
+    for todo in todos {
+        let done = if todo.completed { "x" } else { "" };
+        writeln!(output, "{:>6}  {done:4}  {:19}  {}", todo.id, todo.updated_at, todo.title)?;
+    }
+    Ok(())
+}
+
+/// A client for the saved server and token, or the ones in the environment.
+fn signed_in(path: &Path) -> Result<Client, Error> {
+    let saved = match std::fs::read_to_string(path) {
+        Ok(content) => toml::from_str(&content).map_err(|e| Error::Config {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Credentials::default(),
+        Err(e) => return Err(config_error(path, e)),
+    };
+    let server = std::env::var("TODO_SERVER").ok().or(saved.server);
+    let token = std::env::var("TODO_TOKEN").ok().or(saved.token);
+
+    match (server, token) {
+        (Some(server), Some(token)) => Ok(Client::new(&server, Some(token))?),
+        _ => Err(Error::NotSignedIn),
+    }
+}
+
+/// Writes the credentials where only the current user can read them.
+fn save(path: &Path, credentials: &Credentials) -> Result<(), Error> {
+    if let Some(directory) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(directory).map_err(|e| config_error(directory, e))?;
+    }
+    let content = toml::to_string(credentials).map_err(|e| Error::Config {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files
+        if path.exists() {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+                .map_err(|e| config_error(path, e))?;
+        }
+    }
+    let mut file = options.open(path).map_err(|e| config_error(path, e))?;
+    file.write_all(content.as_bytes()).map_err(|e| config_error(path, e))?;
+    Ok(())
+}
+
+fn config_error(path: &Path, error: io::Error) -> Error {
+    Error::Config {
+        path: path.to_path_buf(),
+        reason: error.to_string(),
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let directory = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(directory.join("todo-cli").join("config.toml"))
+}
+
+/// Names the token after the machine, so it can be told apart in Settings.
+fn token_name() -> String {
+    match std::env::var("HOSTNAME").or_else(|_| std::env::var("COMPUTERNAME")) {
+        Ok(host) if !host.is_empty() => format!("todo-cli on {host}"),
+        _ => "todo-cli".to_string(),
+    }
+}
+
+/// The password a person types without it being echoed or, when piped
+/// in, the next line of `input`.
+fn prompt_password(input: &mut dyn BufRead) -> io::Result<String> {
+    if io::stdin().is_terminal() {
+        return rpassword::prompt_password("Password: ");
+    }
+    prompt("Password: ", input)
+}
+
+/// The next line of `input`, after a prompt if a person is typing it.
+fn prompt(label: &str, input: &mut dyn BufRead) -> io::Result<String> {
+    if io::stdin().is_terminal() {
+        eprint!("{label}");
+    }
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
@@ -0,0 +1,791 @@
+//! A minimal CalDAV (RFC 4791) server so task apps can sync todos both ways.
+//!
+//! Every user has a single calendar, [`CALENDAR_PATH`], holding one `VTODO`
+//! resource per todo. Supported are `PROPFIND`, the `calendar-query` and
+//! `calendar-multiget` reports, and `GET`/`PUT`/`DELETE` of resources with
+//! ETags. Only a task's summary and completion state are stored; anything
+//! else a client sends is dropped.
+//!
+//! Clients authenticate with a personal token: as the password of HTTP
+//! Basic, or as a bearer token. The account password isn't accepted, as
+//! clients sync often and every request would cost a bcrypt check. Neither
+//! are session cookies: these routes live outside `/api` and so outside
+//! CSRF protection.
+
+use super::ics;
+use crate::auth::CurrentUser;
+use crate::database::{hash_token, Database};
+use crate::error::AppError;
+use crate::models::{Todo, TokenScope, UpdateTodo, User};
+use crate::rest::{if_match_satisfied, if_none_match_satisfied, todo_etag};
+use crate::server_functions::validate_title;
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, Method, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::any,
+    Extension, Router,
+};
+use base64::Engine;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use quick_xml::events::Event;
+use quick_xml::name::{Namespace, ResolveResult};
+use quick_xml::NsReader;
+use std::collections::HashMap;
+
+/// Where clients look for the server when only given a host name (RFC 6764).
+pub const WELL_KNOWN_PATH: &str = "/.well-known/caldav";
+pub const ROOT_PATH: &str = "/dav/";
+pub const PRINCIPAL_PATH: &str = "/dav/principal/";
+pub const HOME_PATH: &str = "/dav/calendars/";
+/// The calendar holding the user's todos; resources follow as `{name}`.
+pub const CALENDAR_PATH: &str = "/dav/calendars/todos/";
+
+const DAV_NS: &str = "DAV:";
+const CALDAV_NS: &str = "urn:ietf:params:xml:ns:caldav";
+const CALENDARSERVER_NS: &str = "http://calendarserver.org/ns/";
+
+const XML_CONTENT_TYPE: &str = "application/xml; charset=utf-8";
+const CALENDAR_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+const ALLOWED_METHODS: &str = "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, REPORT";
+
+/// Characters escaped in resource names when building hrefs.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~')
+    .remove(b'@');
+
+pub fn router<S>(db: Database) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let mut router = Router::new()
+        .route(WELL_KNOWN_PATH, any(well_known))
+        .route(&format!("{CALENDAR_PATH}{{name}}"), any(resource));
+    for collection in Collection::ALL {
+        // Clients don't agree on trailing slashes for collections
+        let path = collection.href();
+        router = router
+            .route(path, any(collection_handler))
+            .route(path.trim_end_matches('/'), any(collection_handler));
+    }
+    router.with_state(db)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Collection {
+    Root,
+    Principal,
+    Home,
+    Calendar,
+}
+
+impl Collection {
+    const ALL: [Collection; 4] = [
+        Collection::Root,
+        Collection::Principal,
+        Collection::Home,
+        Collection::Calendar,
+    ];
+
+    fn href(self) -> &'static str {
+        match self {
+            Collection::Root => ROOT_PATH,
+            Collection::Principal => PRINCIPAL_PATH,
+            Collection::Home => HOME_PATH,
+            Collection::Calendar => CALENDAR_PATH,
+        }
+    }
+
+    fn from_path(path: &str) -> Option<Self> {
+        let path = path.trim_end_matches('/');
+        Self::ALL
+            .into_iter()
+            .find(|collection| collection.href().trim_end_matches('/') == path)
+    }
+
+    fn children(self) -> &'static [Collection] {
+        match self {
+            Collection::Root => &[Collection::Principal, Collection::Home],
+            Collection::Home => &[Collection::Calendar],
+            Collection::Principal | Collection::Calendar => &[],
+        }
+    }
+}
+
+/// A todo as a calendar resource.
+struct Entry {
+    todo: Todo,
+    name: String,
+    uid: String,
+}
+
+impl Entry {
+    fn href(&self) -> String {
+        format!(
+            "{CALENDAR_PATH}{}",
+            utf8_percent_encode(&self.name, SEGMENT)
+        )
+    }
+
+    fn etag(&self) -> String {
+        todo_etag(&self.todo)
+    }
+
+    fn data(&self) -> String {
+        ics::single(&self.todo, &self.uid)
+    }
+}
+
+/// Prefix of the names todos get when no client picked one. Clients can't
+/// create resources under it, so a picked name never shadows a todo's own.
+const DEFAULT_NAME_PREFIX: &str = "todo-";
+
+/// The user's todos under the names clients know them by: the one a client
+/// picked when it created the todo, or `todo-{id}.ics`.
+async fn entries(db: &Database, user_id: i64) -> Result<Vec<Entry>, sqlx::Error> {
+    let mut resources: HashMap<i64, _> = db
+        .list_dav_resources(user_id)
+        .await?
+        .into_iter()
+        .map(|resource| (resource.todo_id, resource))
+        .collect();
+
+    Ok(db
+        .get_user_todos(user_id)
+        .await?
+        .into_iter()
+        .map(|todo| match resources.remove(&todo.id) {
+            Some(resource) => Entry {
+                todo,
+                name: resource.name,
+                uid: resource.uid,
+            },
+            None => Entry {
+                name: format!("{DEFAULT_NAME_PREFIX}{}.ics", todo.id),
+                uid: ics::uid(&todo),
+                todo,
+            },
+        })
+        .collect())
+}
+
+/// Changes whenever any resource in the calendar does, so clients can skip
+/// a full sync when it hasn't.
+fn ctag(entries: &[Entry]) -> String {
+    let state: String = entries
+        .iter()
+        .map(|entry| format!("{}={};", entry.name, entry.etag()))
+        .collect();
+    hash_token(&state)[..16].to_string()
+}
+
+/// Resolves Basic credentials holding an API token, falling back to a bearer
+/// token already resolved by the session middleware.
+async fn authenticate(
+    db: &Database,
+    current: CurrentUser,
+    headers: &HeaderMap,
+) -> Result<CurrentUser, sqlx::Error> {
+    if current.token_scope.is_some() {
+        return Ok(current);
+    }
+    let Some((username, password)) = basic_credentials(headers) else {
+        return Ok(CurrentUser::default());
+    };
+
+    Ok(match db.authenticate_api_token(&password).await? {
+        Some((scope, user)) if user.username == username => CurrentUser {
+            user: Some(user),
+            token_scope: Some(scope),
+        },
+        _ => CurrentUser::default(),
+    })
+}
+
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, encoded) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let credentials = String::from_utf8(decoded).ok()?;
+    let (username, password) = credentials.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// Turns a handler result into a response, asking for credentials when
+/// there weren't any.
+fn respond(result: Result<Response, AppError>) -> Response {
+    match result {
+        Ok(response) => response,
+        Err(AppError::Unauthorized) => (
+            StatusCode::UNAUTHORIZED,
+            [(
+                header::WWW_AUTHENTICATE,
+                "Basic realm=\"todo-leptos\", charset=\"UTF-8\"",
+            )],
+        )
+            .into_response(),
+        Err(e) => e.into_response(),
+    }
+}
+
+fn options() -> Response {
+    (
+        StatusCode::OK,
+        [
+            (header::HeaderName::from_static("dav"), "1, calendar-access"),
+            (header::ALLOW, ALLOWED_METHODS),
+        ],
+    )
+        .into_response()
+}
+
+fn method_not_allowed() -> Response {
+    (
+        StatusCode::METHOD_NOT_ALLOWED,
+        [(header::ALLOW, ALLOWED_METHODS)],
+    )
+        .into_response()
+}
+
+/// A `DAV:error` body naming the precondition that failed.
+fn dav_error(status: StatusCode, condition: &str) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, XML_CONTENT_TYPE)],
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+             <d:error xmlns:d=\"{DAV_NS}\" xmlns:c=\"{CALDAV_NS}\">{condition}</d:error>"
+        ),
+    )
+        .into_response()
+}
+
+/// Whether a `PROPFIND` should include the target's children.
+fn include_children(headers: &HeaderMap) -> bool {
+    headers
+        .get("depth")
+        .and_then(|v| v.to_str().ok())
+        .is_none_or(|depth| depth.trim() != "0")
+}
+
+/// `/.well-known/caldav`: points clients at the server root.
+async fn well_known() -> Response {
+    (
+        StatusCode::MOVED_PERMANENTLY,
+        [(header::LOCATION, ROOT_PATH)],
+    )
+        .into_response()
+}
+
+async fn collection_handler(
+    State(db): State<Database>,
+    Extension(current): Extension<CurrentUser>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let Some(collection) = Collection::from_path(uri.path()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    respond(collection_request(&db, current, collection, &method, &headers, &body).await)
+}
+
+async fn collection_request(
+    db: &Database,
+    current: CurrentUser,
+    collection: Collection,
+    method: &Method,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, AppError> {
+    match method.as_str() {
+        "OPTIONS" => return Ok(options()),
+        "PROPFIND" => {}
+        "REPORT" if collection == Collection::Calendar => {}
+        _ => return Ok(method_not_allowed()),
+    }
+
+    let current = authenticate(db, current, headers).await?;
+    let user = current.require(TokenScope::Read)?;
+    let Ok(request) = DavRequest::parse(body) else {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    };
+
+    let entries = match collection {
+        Collection::Home | Collection::Calendar => entries(db, user.id).await?,
+        Collection::Root | Collection::Principal => Vec::new(),
+    };
+    let context = Context {
+        can_write: current.require(TokenScope::Write).is_ok(),
+        ctag: ctag(&entries),
+        user,
+    };
+
+    let mut response = Multistatus::new();
+    if method.as_str() == "REPORT" {
+        match request.kind.as_str() {
+            "calendar-multiget" => {
+                for href in &request.hrefs {
+                    let entry = resource_name(href)
+                        .and_then(|name| entries.iter().find(|entry| entry.name == name));
+                    match entry {
+                        Some(entry) => response.add(&context, &Target::Todo(entry), &request.props),
+                        None => response.not_found(href),
+                    }
+                }
+            }
+            "calendar-query" => {
+                for entry in entries.iter().filter(|entry| request.matches(&entry.todo)) {
+                    response.add(&context, &Target::Todo(entry), &request.props);
+                }
+            }
+            _ => return Ok(dav_error(StatusCode::FORBIDDEN, "<d:supported-report/>")),
+        }
+        return Ok(response.finish());
+    }
+
+    response.add(&context, &Target::Collection(collection), &request.props);
+    if include_children(headers) {
+        for child in collection.children() {
+            response.add(&context, &Target::Collection(*child), &request.props);
+        }
+        if collection == Collection::Calendar {
+            for entry in &entries {
+                response.add(&context, &Target::Todo(entry), &request.props);
+            }
+        }
+    }
+    Ok(response.finish())
+}
+
+async fn resource(
+    State(db): State<Database>,
+    Extension(current): Extension<CurrentUser>,
+    Path(name): Path<String>,
+    method: Method,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    respond(resource_request(&db, current, name, &method, &headers, &body).await)
+}
+
+async fn resource_request(
+    db: &Database,
+    current: CurrentUser,
+    name: String,
+    method: &Method,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, AppError> {
+    let required = match method.as_str() {
+        "OPTIONS" => return Ok(options()),
+        "GET" | "HEAD" | "PROPFIND" => TokenScope::Read,
+        "PUT" | "DELETE" => TokenScope::Write,
+        _ => return Ok(method_not_allowed()),
+    };
+
+    let current = authenticate(db, current, headers).await?;
+    let user = current.require(required)?;
+    let entry = entries(db, user.id)
+        .await?
+        .into_iter()
+        .find(|entry| entry.name == name);
+
+    if method == Method::PUT {
+        return put(db, &user, &name, entry, headers, body).await;
+    }
+    let entry = entry.ok_or(AppError::NotFound)?;
+    let etag = entry.etag();
+
+    match method.as_str() {
+        "PROPFIND" => {
+            let Ok(request) = DavRequest::parse(body) else {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            };
+            let context = Context {
+                can_write: current.require(TokenScope::Write).is_ok(),
+                ctag: String::new(),
+                user,
+            };
+            let mut response = Multistatus::new();
+            response.add(&context, &Target::Todo(&entry), &request.props);
+            Ok(response.finish())
+        }
+        "DELETE" => {
+            if !if_match_satisfied(headers, &etag) {
+                return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+            }
+            if !db.delete_user_todo(user.id, entry.todo.id, Some(entry.todo.version)).await? {
+                return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+            }
+            Ok(StatusCode::NO_CONTENT.into_response())
+        }
+        _ if if_none_match_satisfied(headers, &etag) => {
+            Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response())
+        }
+        _ => Ok((
+            [
+                (header::CONTENT_TYPE, CALENDAR_CONTENT_TYPE.to_string()),
+                (header::ETAG, etag),
+            ],
+            entry.data(),
+        )
+            .into_response()),
+    }
+}
+
+/// Creates or replaces a resource from the client's `VTODO`.
+///
+/// No ETag is returned: we only keep part of what was sent, so the client
+/// has to fetch the resource to see what it now holds (RFC 4791, 5.3.4).
+async fn put(
+    db: &Database,
+    user: &User,
+    name: &str,
+    entry: Option<Entry>,
+    headers: &HeaderMap,
+    body: &str,
+) -> Result<Response, AppError> {
+    let Some(parsed) = ics::parse_vtodo(body) else {
+        return Ok(dav_error(
+            StatusCode::FORBIDDEN,
+            "<c:supported-calendar-component/>",
+        ));
+    };
+    let title = validate_title(&parsed.summary)?;
+
+    let Some(entry) = entry else {
+        if headers.contains_key(header::IF_MATCH) {
+            return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+        }
+        if name.starts_with(DEFAULT_NAME_PREFIX) {
+            return Ok(StatusCode::FORBIDDEN.into_response());
+        }
+
+        db.create_dav_todo(user.id, name, parsed.uid.as_deref(), &title, parsed.completed)
+            .await?;
+        return Ok(StatusCode::CREATED.into_response());
+    };
+
+    let etag = entry.etag();
+    if !if_match_satisfied(headers, &etag) || if_none_match_satisfied(headers, &etag) {
+        return Ok(StatusCode::PRECONDITION_FAILED.into_response());
+    }
+
+    let update = UpdateTodo {
+        title: Some(title),
+        completed: Some(parsed.completed),
+    };
+    db.update_user_todo(user.id, entry.todo.id, update, Some(entry.todo.version))
+        .await?
+        .ok_or(AppError::PreconditionFailed)?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// The resource name an href (absolute or not) points at in the calendar.
+fn resource_name(href: &str) -> Option<String> {
+    let path = match href.split_once("://") {
+        Some((_, rest)) => &rest[rest.find('/')?..],
+        None => href,
+    };
+    let name = path.strip_prefix(CALENDAR_PATH)?;
+    Some(percent_decode_str(name).decode_utf8().ok()?.into_owned())
+}
+
+/// A WebDAV property, by namespace and local name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Prop {
+    ns: String,
+    name: String,
+}
+
+impl Prop {
+    fn new(ns: &str, name: &str) -> Self {
+        Self {
+            ns: ns.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    /// An empty element for this property, or one wrapping `value` (which is
+    /// already XML).
+    fn element(&self, value: &str) -> String {
+        let prefix = match self.ns.as_str() {
+            DAV_NS => "d",
+            CALDAV_NS => "c",
+            CALENDARSERVER_NS => "cs",
+            ns => {
+                let ns = quick_xml::escape::escape(ns);
+                return format!("<x:{} xmlns:x=\"{ns}\"/>", self.name);
+            }
+        };
+        if value.is_empty() {
+            format!("<{prefix}:{}/>", self.name)
+        } else {
+            format!("<{prefix}:{0}>{value}</{prefix}:{0}>", self.name)
+        }
+    }
+
+    /// What `allprop`, or a `PROPFIND` without a body, returns.
+    fn all() -> Vec<Prop> {
+        vec![
+            Prop::new(DAV_NS, "resourcetype"),
+            Prop::new(DAV_NS, "displayname"),
+            Prop::new(DAV_NS, "getetag"),
+            Prop::new(DAV_NS, "getcontenttype"),
+            Prop::new(CALENDARSERVER_NS, "getctag"),
+        ]
+    }
+}
+
+/// The parts of a `PROPFIND` or `REPORT` body we act on.
+#[derive(Debug, Default)]
+struct DavRequest {
+    /// Local name of the root element, e.g. `propfind` or `calendar-query`.
+    kind: String,
+    /// Requested properties; `None` for `allprop`.
+    props: Option<Vec<Prop>>,
+    /// Resources named by a `calendar-multiget`.
+    hrefs: Vec<String>,
+    /// Components a `calendar-query` filters on.
+    comp_filters: Vec<String>,
+    /// Properties a `calendar-query` requires to be absent.
+    undefined_props: Vec<String>,
+}
+
+impl DavRequest {
+    fn parse(body: &str) -> Result<Self, quick_xml::Error> {
+        let mut request = Self::default();
+        let mut reader = NsReader::from_str(body);
+        reader.config_mut().trim_text(true);
+        // Namespace, local name and `name` attribute of each open element
+        let mut open: Vec<(String, String, Option<String>)> = Vec::new();
+
+        loop {
+            let (ns, event) = reader.read_resolved_event()?;
+            let ns = match ns {
+                ResolveResult::Bound(Namespace(ns)) => String::from_utf8_lossy(ns).into_owned(),
+                _ => String::new(),
+            };
+            let empty = matches!(event, Event::Empty(_));
+            match event {
+                Event::Start(element) | Event::Empty(element) => {
+                    let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                    let name_attr = match element.try_get_attribute("name")? {
+                        Some(attr) => Some(attr.unescape_value()?.into_owned()),
+                        None => None,
+                    };
+
+                    match open.last() {
+                        None => request.kind = name.clone(),
+                        Some((parent_ns, parent, parent_attr)) => {
+                            if parent_ns == DAV_NS && parent == "prop" {
+                                request
+                                    .props
+                                    .get_or_insert_with(Vec::new)
+                                    .push(Prop::new(&ns, &name));
+                            }
+                            if ns == CALDAV_NS && name == "comp-filter" {
+                                request.comp_filters.extend(name_attr.clone());
+                            }
+                            if ns == CALDAV_NS
+                                && name == "is-not-defined"
+                                && parent == "prop-filter"
+                            {
+                                request
+                                    .undefined_props
+                                    .extend(parent_attr.as_deref().map(str::to_ascii_uppercase));
+                            }
+                        }
+                    }
+                    if !empty {
+                        open.push((ns, name, name_attr));
+                    }
+                }
+                Event::Text(text) => {
+                    if open
+                        .last()
+                        .is_some_and(|(ns, name, _)| ns == DAV_NS && name == "href")
+                    {
+                        request.hrefs.push(text.unescape()?.into_owned());
+                    }
+                }
+                Event::End(_) => {
+                    open.pop();
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(request)
+    }
+
+    /// Whether a `calendar-query` selects `todo`. Only component filters and
+    /// "has no `COMPLETED`" are understood; time ranges and text matches are
+    /// ignored, which at worst returns more than asked for.
+    fn matches(&self, todo: &Todo) -> bool {
+        let components_match = self.comp_filters.iter().all(|comp| {
+            comp.eq_ignore_ascii_case("VCALENDAR") || comp.eq_ignore_ascii_case("VTODO")
+        });
+        let completed_excluded = self.undefined_props.iter().any(|prop| prop == "COMPLETED");
+        components_match && !(completed_excluded && todo.completed)
+    }
+}
+
+enum Target<'a> {
+    Collection(Collection),
+    Todo(&'a Entry),
+}
+
+impl Target<'_> {
+    fn href(&self) -> String {
+        match self {
+            Target::Collection(collection) => collection.href().to_string(),
+            Target::Todo(entry) => entry.href(),
+        }
+    }
+}
+
+/// What property values depend on besides the resource itself.
+struct Context {
+    user: User,
+    can_write: bool,
+    ctag: String,
+}
+
+impl Context {
+    /// `prop`'s value on `target` as XML, or `None` if it doesn't have it.
+    fn value(&self, target: &Target, prop: &Prop) -> Option<String> {
+        use Collection::{Calendar, Principal};
+
+        let href = |path: &str| format!("<d:href>{path}</d:href>");
+        let value = match (prop.ns.as_str(), prop.name.as_str(), target) {
+            (DAV_NS, "resourcetype", Target::Collection(Principal)) => {
+                "<d:collection/><d:principal/>".to_string()
+            }
+            (DAV_NS, "resourcetype", Target::Collection(Calendar)) => {
+                "<d:collection/><c:calendar/>".to_string()
+            }
+            (DAV_NS, "resourcetype", Target::Collection(_)) => "<d:collection/>".to_string(),
+            (DAV_NS, "resourcetype", Target::Todo(_)) => String::new(),
+            (DAV_NS, "current-user-principal", _) => href(PRINCIPAL_PATH),
+            (DAV_NS, "principal-URL", Target::Collection(Principal)) => href(PRINCIPAL_PATH),
+            (DAV_NS, "displayname", Target::Collection(Principal)) => {
+                quick_xml::escape::partial_escape(&self.user.username).into_owned()
+            }
+            (DAV_NS, "displayname", Target::Collection(Calendar)) => "Todos".to_string(),
+            (CALDAV_NS, "calendar-home-set", Target::Collection(Principal)) => href(HOME_PATH),
+            (CALDAV_NS, "calendar-user-address-set", Target::Collection(Principal)) => {
+                href(&format!(
+                    "mailto:{}",
+                    quick_xml::escape::partial_escape(&self.user.email)
+                ))
+            }
+            (CALDAV_NS, "supported-calendar-component-set", Target::Collection(Calendar)) => {
+                "<c:comp name=\"VTODO\"/>".to_string()
+            }
+            (DAV_NS, "supported-report-set", Target::Collection(Calendar)) => [
+                "calendar-query",
+                "calendar-multiget",
+            ]
+            .map(|report| {
+                format!(
+                    "<d:supported-report><d:report><c:{report}/></d:report></d:supported-report>"
+                )
+            })
+            .concat(),
+            (
+                DAV_NS,
+                "current-user-privilege-set",
+                Target::Collection(Calendar) | Target::Todo(_),
+            ) => {
+                let mut privileges = "<d:privilege><d:read/></d:privilege>".to_string();
+                if self.can_write {
+                    privileges.push_str("<d:privilege><d:write/></d:privilege>");
+                }
+                privileges
+            }
+            (CALENDARSERVER_NS, "getctag", Target::Collection(Calendar)) => self.ctag.clone(),
+            (DAV_NS, "getetag", Target::Todo(entry)) => {
+                quick_xml::escape::partial_escape(entry.etag()).into_owned()
+            }
+            (DAV_NS, "getcontenttype", Target::Todo(_)) => {
+                format!("{CALENDAR_CONTENT_TYPE}; component=VTODO")
+            }
+            (CALDAV_NS, "calendar-data", Target::Todo(entry)) => {
+                quick_xml::escape::partial_escape(entry.data()).into_owned()
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// A `207 Multi-Status` body being built up one resource at a time.
+struct Multistatus(String);
+
+impl Multistatus {
+    fn new() -> Self {
+        Self(format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+             <d:multistatus xmlns:d=\"{DAV_NS}\" xmlns:c=\"{CALDAV_NS}\" xmlns:cs=\"{CALENDARSERVER_NS}\">"
+        ))
+    }
+
+    /// Adds `target` with the requested properties; ones it doesn't have are
+    /// listed as not found, except under `allprop`.
+    fn add(&mut self, context: &Context, target: &Target, props: &Option<Vec<Prop>>) {
+        let (requested, report_missing) = match props {
+            Some(props) => (props.clone(), true),
+            None => (Prop::all(), false),
+        };
+
+        let mut found = String::new();
+        let mut missing = String::new();
+        for prop in &requested {
+            match context.value(target, prop) {
+                Some(value) => found.push_str(&prop.element(&value)),
+                None if report_missing => missing.push_str(&prop.element("")),
+                None => {}
+            }
+        }
+
+        self.0.push_str("<d:response>");
+        self.0
+            .push_str(&format!("<d:href>{}</d:href>", target.href()));
+        for (props, status) in [(found, "200 OK"), (missing, "404 Not Found")] {
+            if !props.is_empty() {
+                self.0.push_str(&format!(
+                    "<d:propstat><d:prop>{props}</d:prop><d:status>HTTP/1.1 {status}</d:status></d:propstat>"
+                ));
+            }
+        }
+        self.0.push_str("</d:response>");
+    }
+
+    fn not_found(&mut self, href: &str) {
+        self.0.push_str(&format!(
+            "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>",
+            quick_xml::escape::partial_escape(href)
+        ));
+    }
+
+    fn finish(mut self) -> Response {
+        self.0.push_str("</d:multistatus>");
+        (
+            StatusCode::MULTI_STATUS,
+            [(header::CONTENT_TYPE, XML_CONTENT_TYPE)],
+            self.0,
+        )
+            .into_response()
+    }
+}
@@ -0,0 +1,76 @@
+//! HTTP endpoints serving todos as iCalendar.
+
+use super::ics;
+use crate::auth::CurrentUser;
+use crate::database::Database;
+use crate::error::AppError;
+use crate::models::TokenScope;
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+
+/// Where the secret per-user feeds live; the token follows as `{token}.ics`.
+pub const FEED_PATH: &str = "/api/calendar/feed";
+/// One-off download of the signed-in user's todos.
+pub const DOWNLOAD_PATH: &str = "/api/calendar/todos.ics";
+
+pub fn router<S>(db: Database) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route(&format!("{FEED_PATH}/{{file}}"), get(feed))
+        .route(DOWNLOAD_PATH, get(download))
+        .with_state(db)
+}
+
+fn calendar_response(body: String, attachment: bool) -> Response {
+    let disposition = if attachment {
+        "attachment; filename=\"todos.ics\""
+    } else {
+        "inline; filename=\"todos.ics\""
+    };
+    (
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CONTENT_DISPOSITION, disposition),
+            (header::CACHE_CONTROL, "private, no-cache"),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+/// `GET /api/calendar/feed/{token}.ics`: subscribable feed authenticated by
+/// the secret in the URL, since calendar apps can't log in.
+async fn feed(
+    State(db): State<Database>,
+    Path(file): Path<String>,
+) -> Result<Response, AppError> {
+    let token = file.strip_suffix(".ics").unwrap_or(&file);
+    let user_id = db.calendar_feed_user(token)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    let user = db.get_user_by_id(user_id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+
+    let todos = db.get_user_todos(user.id).await?;
+    let name = format!("{}'s todos", user.username);
+    Ok(calendar_response(ics::calendar(&name, &todos), false))
+}
+
+/// `GET /api/calendar/todos.ics`: the current user's todos as a file.
+async fn download(
+    State(db): State<Database>,
+    Extension(current): Extension<CurrentUser>,
+) -> Result<Response, AppError> {
+    let user = current.require(TokenScope::Read)?;
+
+    let todos = db.get_user_todos(user.id).await?;
+    Ok(calendar_response(ics::calendar("Todos", &todos), true))
+}
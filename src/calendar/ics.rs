@@ -0,0 +1,194 @@
+//! Minimal iCalendar (RFC 5545) serialization of todos.
+
+use crate::models::Todo;
+
+/// Product identifier stamped on every calendar we produce.
+pub const PRODID: &str = "-//todo-leptos//Todos//EN";
+
+/// A calendar containing one `VTODO` per todo.
+pub fn calendar(name: &str, todos: &[Todo]) -> String {
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, &format!("PRODID:{PRODID}"));
+    line(&mut out, "CALSCALE:GREGORIAN");
+    line(&mut out, &format!("X-WR-CALNAME:{}", escape(name)));
+    for todo in todos {
+        vtodo(&mut out, todo, &uid(todo));
+    }
+    line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// A calendar holding only `todo`, identified by `uid`.
+pub fn single(todo: &Todo, uid: &str) -> String {
+    let mut out = String::new();
+    line(&mut out, "BEGIN:VCALENDAR");
+    line(&mut out, "VERSION:2.0");
+    line(&mut out, &format!("PRODID:{PRODID}"));
+    vtodo(&mut out, todo, uid);
+    line(&mut out, "END:VCALENDAR");
+    out
+}
+
+/// Stable UID for a todo that wasn't given one by a CalDAV client.
+pub fn uid(todo: &Todo) -> String {
+    format!("todo-{}@todo-leptos", todo.id)
+}
+
+fn vtodo(out: &mut String, todo: &Todo, uid: &str) {
+    let updated = timestamp(&todo.updated_at);
+    let created = timestamp(&todo.created_at);
+    // Required on every VTODO, so it falls back to the creation time and
+    // then to now
+    let stamp = updated
+        .clone()
+        .or_else(|| created.clone())
+        .unwrap_or_else(|| chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string());
+
+    line(out, "BEGIN:VTODO");
+    line(out, &format!("UID:{}", escape(uid)));
+    line(out, &format!("DTSTAMP:{stamp}"));
+    if let Some(updated) = &updated {
+        line(out, &format!("LAST-MODIFIED:{updated}"));
+    }
+    if let Some(created) = &created {
+        line(out, &format!("CREATED:{created}"));
+    }
+    line(out, &format!("SUMMARY:{}", escape(&todo.title)));
+    if todo.completed {
+        line(out, "STATUS:COMPLETED");
+        line(out, "PERCENT-COMPLETE:100");
+        // We don't track when a todo was completed; its last change is the
+        // closest we have
+        if let Some(updated) = &updated {
+            line(out, &format!("COMPLETED:{updated}"));
+        }
+    } else {
+        line(out, "STATUS:NEEDS-ACTION");
+    }
+    line(out, "END:VTODO");
+}
+
+/// Converts a stored `YYYY-MM-DD HH:MM:SS` (UTC) timestamp to iCalendar's
+/// `YYYYMMDDTHHMMSSZ`.
+pub fn timestamp(value: &str) -> Option<String> {
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|dt| dt.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+/// Escapes a TEXT value.
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The parts of a client-supplied `VTODO` we keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedTodo {
+    pub uid: Option<String>,
+    pub summary: String,
+    pub completed: bool,
+}
+
+/// Reads the first `VTODO` in an iCalendar object. Everything we don't
+/// store (descriptions, due dates, alarms, ...) is ignored.
+pub fn parse_vtodo(calendar: &str) -> Option<ParsedTodo> {
+    let unfolded = calendar
+        .replace("\r\n ", "")
+        .replace("\r\n\t", "")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut todo = None;
+    // Components nested inside the VTODO, e.g. VALARM
+    let mut nested = 0;
+    for content in unfolded.lines() {
+        let Some((name, value)) = split_line(content) else {
+            continue;
+        };
+        match (&mut todo, name.as_str()) {
+            (None, "BEGIN") if value.eq_ignore_ascii_case("VTODO") => {
+                todo = Some(ParsedTodo {
+                    uid: None,
+                    summary: String::new(),
+                    completed: false,
+                });
+            }
+            (None, _) => {}
+            (Some(_), "BEGIN") => nested += 1,
+            (Some(_), "END") if nested > 0 => nested -= 1,
+            (Some(_), "END") => break,
+            (Some(_), _) if nested > 0 => {}
+            (Some(todo), "UID") => todo.uid = Some(unescape(value)),
+            (Some(todo), "SUMMARY") => todo.summary = unescape(value),
+            (Some(todo), "STATUS") => todo.completed = value.eq_ignore_ascii_case("COMPLETED"),
+            (Some(todo), "COMPLETED") => todo.completed = true,
+            (Some(todo), "PERCENT-COMPLETE") if value.trim() == "100" => todo.completed = true,
+            (Some(_), _) => {}
+        }
+    }
+    todo
+}
+
+/// Splits an unfolded content line into its upper-cased property name and
+/// raw value, skipping any parameters.
+fn split_line(content: &str) -> Option<(String, &str)> {
+    let content = content.trim_end_matches('\r');
+    // Parameter values may contain ':' when quoted
+    let mut quoted = false;
+    let colon = content.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&content[..colon], &content[colon + 1..]);
+    let name = head.split(';').next().unwrap_or(head);
+    Some((name.trim().to_ascii_uppercase(), value))
+}
+
+/// Reverses [`escape`].
+pub fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Appends a content line, folded at 75 octets and terminated with CRLF.
+fn line(out: &mut String, content: &str) {
+    let mut width = 0;
+    for c in content.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
@@ -0,0 +1,50 @@
+//! Todos in calendar apps: a secret per-user iCalendar feed, a one-off
+//! `.ics` download, and two-way sync over CalDAV.
+
+use crate::csrf::CsrfClient;
+use crate::error::AppError;
+use crate::models::CalendarFeed;
+use leptos::prelude::*;
+
+#[cfg(feature = "ssr")]
+pub mod caldav;
+#[cfg(feature = "ssr")]
+pub mod feed;
+#[cfg(feature = "ssr")]
+pub mod ics;
+
+#[cfg(feature = "ssr")]
+use crate::auth::require_session_user;
+#[cfg(feature = "ssr")]
+use crate::database::Database;
+
+#[server(GetCalendarFeed, "/api", client = CsrfClient)]
+pub async fn get_calendar_feed() -> Result<Option<CalendarFeed>, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    Ok(db.get_calendar_feed(user.id).await?)
+}
+
+/// Creates a new feed URL for the user, invalidating the previous one.
+/// Returns the URL's path; it can't be retrieved again later.
+#[server(ResetCalendarFeed, "/api", client = CsrfClient)]
+pub async fn reset_calendar_feed() -> Result<String, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    let token = db.reset_calendar_feed(user.id).await?;
+    Ok(format!("{}/{token}.ics", feed::FEED_PATH))
+}
+
+#[server(DisableCalendarFeed, "/api", client = CsrfClient)]
+pub async fn disable_calendar_feed() -> Result<(), AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    db.delete_calendar_feed(user.id).await?;
+    Ok(())
+}
@@ -0,0 +1,359 @@
+//! Operator commands of the server binary, for managing an instance without
+//! writing SQL:
+//!
+//! ```text
+//! todo-leptos user create alice --email alice@example.com --admin < password.txt
+//! todo-leptos user disable bob
+//! todo-leptos export-user alice --format todo_txt > alice.txt
+//! ```
+//!
+//! They take the same settings as the server. Only `migrate` changes the
+//! schema; the others refuse to run against a database that isn't migrated
+//! to this version, so they're safe to run next to a server of another
+//! version. Passwords are read from the first line of standard input, so
+//! they can be piped in and stay out of the shell history, or typed without
+//! echo at a terminal.
+
+use crate::auth::{register_user, validate_password};
+use crate::backup::{self, BackupError};
+use crate::config::Config;
+use crate::database::Database;
+use crate::error::AppError;
+use crate::export::write;
+use crate::import::parse;
+use crate::models::{ImportRequest, RegisterUser, TodoFormat, User};
+use clap::Subcommand;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the server; the default without a command
+    Serve,
+    /// Replace the SQLite database with a backup; stop the server first
+    Restore {
+        /// The backup file
+        backup: PathBuf,
+    },
+    #[command(flatten)]
+    Admin(AdminCommand),
+}
+
+/// Commands that work on the database while it's in use.
+#[derive(Debug, Subcommand)]
+pub enum AdminCommand {
+    /// Create the database or bring its schema up to date; the other
+    /// commands need this done first
+    Migrate,
+    /// Manage accounts
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Manage sign-in sessions
+    #[command(subcommand)]
+    Sessions(SessionsCommand),
+    /// Write a user's todos to standard output
+    ExportUser {
+        username: String,
+        /// todo_txt, csv, markdown or json
+        #[arg(long, default_value = "json", value_parser = parse_format)]
+        format: TodoFormat,
+    },
+    /// Create todos for a user from an export or another todo list
+    Import {
+        username: String,
+        file: PathBuf,
+        /// todo_txt, csv, markdown or json [default: from the file extension]
+        #[arg(long, value_parser = parse_format)]
+        format: Option<TodoFormat>,
+        /// Only report what would be imported
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum UserCommand {
+    /// Create an account
+    Create {
+        username: String,
+        #[arg(long)]
+        email: String,
+        /// Make it an administrator
+        #[arg(long)]
+        admin: bool,
+    },
+    /// List all accounts
+    List,
+    /// Stop an account from signing in and end its sessions
+    Disable { username: String },
+    /// Let a disabled account sign in again
+    Enable { username: String },
+    /// Make an account an administrator and end its sessions
+    Promote { username: String },
+    /// Take administrator rights away from an account and end its sessions
+    Demote { username: String },
+    /// Set a new password and end the account's sessions
+    ResetPassword { username: String },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SessionsCommand {
+    /// Delete expired sessions, or all sessions of one user
+    Purge {
+        /// Sign this user out everywhere instead
+        #[arg(long, value_name = "USERNAME")]
+        user: Option<String>,
+    },
+}
+
+fn parse_format(value: &str) -> Result<TodoFormat, String> {
+    TodoFormat::parse(value).ok_or_else(|| {
+        let names: Vec<_> = TodoFormat::ALL.iter().map(TodoFormat::as_str).collect();
+        format!("expected one of {}", names.join(", "))
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("no user named {0:?}")]
+    NoSuchUser(String),
+    #[error("{0}")]
+    Rejected(String),
+    #[error("{}: {source}", path.display())]
+    Read { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Database(#[from] sqlx::Error),
+    #[error(transparent)]
+    Backup(#[from] BackupError),
+}
+
+impl From<AppError> for CliError {
+    fn from(error: AppError) -> Self {
+        CliError::Rejected(error.user_message())
+    }
+}
+
+/// Swaps in `backup` for the database file in `config`.
+pub async fn restore(config: &Config, backup: &Path, output: &mut dyn Write) -> Result<(), CliError> {
+    match backup::restore(&config.database, backup).await? {
+        Some(previous) => writeln!(
+            output,
+            "restored {}; the replaced database is at {}",
+            backup.display(),
+            previous.display()
+        )?,
+        None => writeln!(output, "restored {}", backup.display())?,
+    }
+    Ok(())
+}
+
+/// Runs `command` against the database in `config`, reading passwords
+/// from `input` and writing results to `output`.
+pub async fn run(
+    command: AdminCommand,
+    config: &Config,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), CliError> {
+    let db = match command {
+        AdminCommand::Migrate => Database::connect(&config.database).await?,
+        _ => Database::open(&config.database).await?,
+    };
+    let result = run_with(&db, command, config, input, output).await;
+    db.close().await;
+    result
+}
+
+async fn run_with(
+    db: &Database,
+    command: AdminCommand,
+    config: &Config,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), CliError> {
+    match command {
+        // Connecting for it migrated the database
+        AdminCommand::Migrate => writeln!(output, "the database is up to date")?,
+        AdminCommand::User(command) => user(db, command, config, input, output).await?,
+        AdminCommand::Sessions(SessionsCommand::Purge { user: None }) => {
+            let purged = db.purge_expired_sessions().await?;
+            writeln!(output, "purged {purged} expired sessions")?;
+        }
+        AdminCommand::Sessions(SessionsCommand::Purge { user: Some(username) }) => {
+            let user = find_user(db, &username).await?;
+            let ended = db.delete_user_sessions(user.id).await?;
+            writeln!(output, "ended {ended} sessions of {username}")?;
+        }
+        AdminCommand::ExportUser { username, format } => {
+            let user = find_user(db, &username).await?;
+            export_user(db, &user, format, output).await?;
+        }
+        AdminCommand::Import {
+            username,
+            file,
+            format,
+            dry_run,
+        } => {
+            let user = find_user(db, &username).await?;
+            let Some(format) = format.or_else(|| TodoFormat::from_filename(&file.to_string_lossy())) else {
+                return Err(CliError::Rejected(format!(
+                    "can't tell the format of {} from its name, pass --format",
+                    file.display()
+                )));
+            };
+            let content = std::fs::read_to_string(&file).map_err(|source| CliError::Read {
+                path: file.clone(),
+                source,
+            })?;
+
+            let preview = parse::parse(&ImportRequest {
+                format,
+                content,
+                columns: Default::default(),
+                dry_run,
+            })?;
+            for skipped in &preview.skipped {
+                writeln!(output, "skipped {skipped}")?;
+            }
+            if preview.todos.is_empty() {
+                writeln!(output, "found nothing to import")?;
+            } else if dry_run {
+                writeln!(output, "would import {} todos for {username}", preview.todos.len())?;
+            } else {
+                let imported = db.import_user_todos(user.id, &preview.todos).await?;
+                writeln!(output, "imported {} todos for {username}", imported.len())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn user(
+    db: &Database,
+    command: UserCommand,
+    config: &Config,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), CliError> {
+    match command {
+        UserCommand::Create { username, email, admin } => {
+            let password = read_password(input)?;
+            let mut user = register_user(
+                db,
+                RegisterUser {
+                    username,
+                    email,
+                    password,
+                },
+                config.auth.bcrypt_cost,
+            )
+            .await?;
+            if admin {
+                db.set_user_admin(user.id, true).await?;
+                user.is_admin = true;
+            }
+            let role = if user.is_admin { "administrator" } else { "user" };
+            writeln!(output, "created {role} {} with id {}", user.username, user.id)?;
+        }
+        UserCommand::List => {
+            let users = db.list_users().await?;
+            let width = |column: fn(&User) -> &str, title: &str| {
+                users.iter().map(|user| column(user).len()).fold(title.len(), usize::max)
+            };
+            let username_width = width(|user| &user.username, "USERNAME");
+            let email_width = width(|user| &user.email, "EMAIL");
+
+            writeln!(
+                output,
+                "{:>6}  {:username_width$}  {:email_width$}  {:19}  STATUS",
+                "ID", "USERNAME", "EMAIL", "CREATED"
+            )?;
+            for user in &users {
+                let status = match (&user.disabled_at, user.is_admin) {
+                    (Some(at), _) => format!("disabled {at}"),
+                    (None, true) => "admin".to_string(),
+                    (None, false) => "active".to_string(),
+                };
+                writeln!(
+                    output,
+                    "{:>6}  {:username_width$}  {:email_width$}  {:19}  {status}",
+                    user.id, user.username, user.email, user.created_at
+                )?;
+            }
+        }
+        UserCommand::Disable { username } => {
+            let user = find_user(db, &username).await?;
+            db.set_user_disabled(user.id, true).await?;
+            let ended = db.delete_user_sessions(user.id).await?;
+            writeln!(output, "disabled {username} and ended {ended} sessions")?;
+        }
+        UserCommand::Enable { username } => {
+            let user = find_user(db, &username).await?;
+            db.set_user_disabled(user.id, false).await?;
+            writeln!(output, "enabled {username}")?;
+        }
+        UserCommand::Promote { username } => {
+            let user = find_user(db, &username).await?;
+            db.set_user_admin(user.id, true).await?;
+            let ended = db.delete_user_sessions(user.id).await?;
+            writeln!(output, "made {username} an administrator and ended {ended} sessions")?;
+        }
+        UserCommand::Demote { username } => {
+            let user = find_user(db, &username).await?;
+            db.set_user_admin(user.id, false).await?;
+            let ended = db.delete_user_sessions(user.id).await?;
+            writeln!(output, "{username} is no longer an administrator, ended {ended} sessions")?;
+        }
+        UserCommand::ResetPassword { username } => {
+            let user = find_user(db, &username).await?;
+            let password = read_password(input)?;
+            validate_password(&password).map_err(|message| CliError::Rejected(message.to_string()))?;
+
+            db.set_user_password(user.id, &password, config.auth.bcrypt_cost)
+                .await?;
+            let ended = db.delete_user_sessions(user.id).await?;
+            writeln!(output, "changed the password of {username} and ended {ended} sessions")?;
+        }
+    }
+    Ok(())
+}
+
+async fn find_user(db: &Database, username: &str) -> Result<User, CliError> {
+    db.get_user_by_username(username)
+        .await?
+        .ok_or_else(|| CliError::NoSuchUser(username.to_string()))
+}
+
+/// Writes the user's todos the way `/api/export` does, as they're read.
+async fn export_user(db: &Database, user: &User, format: TodoFormat, output: &mut dyn Write) -> Result<(), CliError> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(64);
+    let reader = {
+        let db = db.clone();
+        let user_id = user.id;
+        tokio::spawn(async move { db.send_user_todos(user_id, None, sender).await })
+    };
+
+    write!(output, "{}", write::header(format))?;
+    let mut index = 0;
+    while let Some(todo) = receiver.recv().await {
+        write!(output, "{}", write::todo(format, index, &todo?))?;
+        index += 1;
+    }
+    write!(output, "{}", write::footer(format))?;
+
+    let _ = reader.await;
+    Ok(())
+}
+
+/// The first line of `input` or, if a person is typing it, the password
+/// they enter at a prompt without it being echoed.
+fn read_password(input: &mut dyn BufRead) -> Result<String, CliError> {
+    if io::stdin().is_terminal() {
+        return Ok(rpassword::prompt_password("Password: ")?);
+    }
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
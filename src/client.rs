@@ -0,0 +1,140 @@
+//! Client for the `/api/v1` JSON API, as used by `todo-cli`.
+//!
+//! Requests are authenticated with a personal API token. Without one, the
+//! only call that works is [`Client::create_token`], which trades a
+//! username and password for a token.
+
+use crate::error::AppError;
+use crate::models::{
+    CreateApiToken, CreateTodo, NewApiToken, PasswordTokenRequest, Todo, TokenScope, UpdateTodo, User,
+};
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("{0:?} is not a server URL, expected e.g. https://todos.example.com")]
+    InvalidServer(String),
+    #[error("couldn't reach the server: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The server refused the request and said why.
+    #[error("{}", .0.user_message())]
+    Api(AppError),
+    #[error("the server answered {0}")]
+    Unexpected(StatusCode),
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    server: Url,
+    token: Option<String>,
+}
+
+impl Client {
+    /// A client for the server at `server`, e.g. `https://todos.example.com`.
+    pub fn new(server: &str, token: Option<String>) -> Result<Self, ClientError> {
+        let server = Url::parse(server)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+            .ok_or_else(|| ClientError::InvalidServer(server.to_string()))?;
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            server,
+            token,
+        })
+    }
+
+    pub fn server(&self) -> &str {
+        self.server.as_str().trim_end_matches('/')
+    }
+
+    /// Creates a token named `name` that can read and change todos, signing
+    /// in with `username` and `password`.
+    pub async fn create_token(&self, username: &str, password: &str, name: &str) -> Result<NewApiToken, ClientError> {
+        let request = PasswordTokenRequest {
+            username: username.to_string(),
+            password: password.to_string(),
+            token: CreateApiToken {
+                name: name.to_string(),
+                scope: TokenScope::Write,
+                expires_in_days: None,
+            },
+        };
+        let response = self.request(Method::POST, "tokens").json(&request).send().await?;
+        parse(response).await
+    }
+
+    /// Who the token belongs to.
+    pub async fn current_user(&self) -> Result<User, ClientError> {
+        self.send(self.request(Method::GET, "user")).await
+    }
+
+    /// The user's todos, newest first, optionally only those with the given
+    /// completion state.
+    pub async fn list_todos(&self, completed: Option<bool>) -> Result<Vec<Todo>, ClientError> {
+        let mut request = self.request(Method::GET, "todos");
+        if let Some(completed) = completed {
+            request = request.query(&[("completed", completed)]);
+        }
+        self.send(request).await
+    }
+
+    pub async fn create_todo(&self, title: &str) -> Result<Todo, ClientError> {
+        let todo = CreateTodo {
+            title: title.to_string(),
+        };
+        self.send(self.request(Method::POST, "todos").json(&todo)).await
+    }
+
+    pub async fn update_todo(&self, id: i64, update: &UpdateTodo) -> Result<Todo, ClientError> {
+        self.send(self.request(Method::PATCH, &format!("todos/{id}")).json(update))
+            .await
+    }
+
+    pub async fn delete_todo(&self, id: i64) -> Result<(), ClientError> {
+        let response = self
+            .authorize(self.request(Method::DELETE, &format!("todos/{id}")))
+            .send()
+            .await?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(error(response).await)
+        }
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let url = format!("{}/api/v1/{path}", self.server());
+        self.http.request(method, url)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, ClientError> {
+        parse(self.authorize(request).send().await?).await
+    }
+}
+
+async fn parse<T: DeserializeOwned>(response: Response) -> Result<T, ClientError> {
+    if response.status().is_success() {
+        Ok(response.json().await?)
+    } else {
+        Err(error(response).await)
+    }
+}
+
+/// The [`AppError`] the server sent with a failed response, if it sent one.
+async fn error(response: Response) -> ClientError {
+    let status = response.status();
+    match response.json::<AppError>().await {
+        Ok(error) => ClientError::Api(error),
+        Err(_) => ClientError::Unexpected(status),
+    }
+}
@@ -1,5 +1,6 @@
 use crate::auth::*;
-use crate::models::{LoginUser, RegisterUser, User};
+use crate::error::AppError;
+use crate::models::{LoginUser, RegisterUser};
 use leptos::prelude::*;
 use leptos_router::hooks::use_navigate;
 
@@ -32,8 +33,11 @@ pub fn LoginForm() -> impl IntoView {
                     user_context.login(user);
                     navigate("/", Default::default());
                 }
+                Err(AppError::Unauthorized) => {
+                    set_error_message.set(Some("Invalid username or password".to_string()));
+                }
                 Err(e) => {
-                    set_error_message.set(Some(e.to_string()));
+                    set_error_message.set(Some(e.user_message()));
                 }
             }
         }
@@ -133,7 +137,7 @@ pub fn SignupForm() -> impl IntoView {
                     navigate("/login", Default::default());
                 }
                 Err(e) => {
-                    set_error_message.set(Some(e.to_string()));
+                    set_error_message.set(Some(e.user_message()));
                 }
             }
         }
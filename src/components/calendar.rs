@@ -0,0 +1,59 @@
+use crate::calendar::*;
+use leptos::prelude::*;
+
+#[component]
+pub fn CalendarSettings() -> impl IntoView {
+    let reset_action = ServerAction::<ResetCalendarFeed>::new();
+    let disable_action = ServerAction::<DisableCalendarFeed>::new();
+    let feed = Resource::new(
+        move || (reset_action.version().get(), disable_action.version().get()),
+        |_| get_calendar_feed(),
+    );
+
+    view! {
+        <section class="token-settings">
+            <h2>"Calendar"</h2>
+            <p class="settings-hint">
+                "Subscribe to your todos from a calendar app with a private feed URL, or "
+                <a href="/api/calendar/todos.ics" download="todos.ics">"download them as .ics"</a> "."
+            </p>
+
+            {move || match reset_action.value().get() {
+                Some(Ok(path)) => {
+                    let origin = window().location().origin().unwrap_or_default();
+                    view! {
+                        <div class="new-token">
+                            <p>"Copy your feed URL now. It won't be shown again."</p>
+                            <code>{format!("{origin}{path}")}</code>
+                        </div>
+                    }.into_any()
+                }
+                Some(Err(e)) => view! { <p class="error">{e.user_message()}</p> }.into_any(),
+                None => view! { <div></div> }.into_any(),
+            }}
+
+            <Suspense fallback=move || view! { <p class="loading">"Loading..."</p> }>
+                {move || match feed.get() {
+                    Some(Ok(Some(feed))) => view! {
+                        <div class="input-group">
+                            <p class="settings-hint">"Feed URL created " {feed.created_at} "."</p>
+                            <button on:click=move |_| { reset_action.dispatch(ResetCalendarFeed {}); }>
+                                "Reset URL"
+                            </button>
+                            <button class="delete-btn" on:click=move |_| { disable_action.dispatch(DisableCalendarFeed {}); }>
+                                "Disable"
+                            </button>
+                        </div>
+                    }.into_any(),
+                    Some(Ok(None)) => view! {
+                        <button on:click=move |_| { reset_action.dispatch(ResetCalendarFeed {}); }>
+                            "Create feed URL"
+                        </button>
+                    }.into_any(),
+                    Some(Err(e)) => view! { <p class="error">{e.user_message()}</p> }.into_any(),
+                    None => view! { <p class="loading">"Loading..."</p> }.into_any(),
+                }}
+            </Suspense>
+        </section>
+    }
+}
@@ -0,0 +1,37 @@
+use crate::export::export_url;
+use crate::models::TodoFormat;
+use leptos::prelude::*;
+
+#[component]
+pub fn ExportMenu() -> impl IntoView {
+    // `None` exports every todo
+    let completed = RwSignal::new(None::<bool>);
+
+    view! {
+        <details class="export-menu">
+            <summary>"Export"</summary>
+            <div class="export-options">
+                <select on:change=move |ev| {
+                    completed.set(match event_target_value(&ev).as_str() {
+                        "open" => Some(false),
+                        "completed" => Some(true),
+                        _ => None,
+                    });
+                }>
+                    <option value="all">"All todos"</option>
+                    <option value="open">"Open todos"</option>
+                    <option value="completed">"Completed todos"</option>
+                </select>
+                <ul>
+                    {TodoFormat::ALL.into_iter().map(|format| view! {
+                        <li>
+                            <a href=move || export_url(format, completed.get()) download="">
+                                {format.label()}
+                            </a>
+                        </li>
+                    }).collect_view()}
+                </ul>
+            </div>
+        </details>
+    }
+}
@@ -0,0 +1,265 @@
+use crate::import::*;
+use crate::models::{CsvColumns, TodoFormat, ImportPreview, ImportRequest, ImportedTodo};
+use leptos::prelude::*;
+
+/// Most preview rows shown; the rest are only counted.
+const PREVIEW_ROWS: usize = 50;
+
+#[component]
+pub fn ImportForm() -> impl IntoView {
+    let preview_action = ServerAction::<ImportTodos>::new();
+    let import_action = ServerAction::<ImportTodos>::new();
+
+    let format = RwSignal::new(TodoFormat::default());
+    let content = RwSignal::new(String::new());
+    let columns = RwSignal::new(CsvColumns::default());
+    // What the current preview was made from, so the import creates exactly
+    // what was previewed
+    let previewed = RwSignal::new(None::<ImportRequest>);
+
+    let preview = move || {
+        let request = ImportRequest {
+            format: format.get_untracked(),
+            content: content.get_untracked(),
+            columns: columns.get_untracked(),
+            dry_run: true,
+        };
+        previewed.set(Some(request.clone()));
+        preview_action.dispatch(ImportTodos { request });
+    };
+
+    // Show the columns the server settled on, so they can be changed
+    Effect::new(move |_| {
+        if let Some(Ok(result)) = preview_action.value().get() {
+            columns.set(result.mapping);
+        }
+    });
+
+    let submit_preview = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        columns.set(CsvColumns::default());
+        import_action.clear();
+        preview();
+    };
+
+    let import = move |_| {
+        if let Some(request) = previewed.get_untracked() {
+            import_action.dispatch(ImportTodos {
+                request: ImportRequest {
+                    dry_run: false,
+                    ..request
+                },
+            });
+        }
+    };
+
+    let choose_file = move |ev: leptos::ev::Event| {
+        read_file(ev, move |name, text| {
+            if let Some(guessed) = TodoFormat::from_filename(&name) {
+                format.set(guessed);
+            }
+            content.set(text);
+        });
+    };
+
+    view! {
+        <section class="token-settings">
+            <h2>"Import todos"</h2>
+            <p class="settings-hint">
+                "Bring in tasks from a todo.txt file, a CSV spreadsheet, a Markdown checklist "
+                "or a JSON export. You'll see what will be created before anything is."
+            </p>
+
+            <form on:submit=submit_preview class="token-form">
+                <div class="import-options">
+                    <select
+                        prop:value=move || format.get().as_str()
+                        on:change=move |ev| {
+                            if let Some(chosen) = TodoFormat::parse(&event_target_value(&ev)) {
+                                format.set(chosen);
+                            }
+                        }
+                    >
+                        {TodoFormat::ALL.into_iter().map(|format| view! {
+                            <option value=format.as_str()>{format.label()}</option>
+                        }).collect_view()}
+                    </select>
+                    <input type="file" accept=".txt,.csv,.md,.markdown,.json" on:change=choose_file/>
+                </div>
+                <textarea
+                    class="import-input"
+                    rows="10"
+                    placeholder="...or paste here"
+                    prop:value=content
+                    on:input=move |ev| content.set(event_target_value(&ev))
+                ></textarea>
+                <button type="submit" disabled=move || preview_action.pending().get() || content.get().trim().is_empty()>
+                    "Preview"
+                </button>
+            </form>
+
+            {move || match import_action.value().get() {
+                Some(Ok(result)) => view! {
+                    <div class="new-token">
+                        <p>{format!("Imported {} todos.", result.todos.len())} " " <a href="/">"Go to your list"</a></p>
+                    </div>
+                }.into_any(),
+                Some(Err(e)) => view! { <p class="error">"Import failed, nothing was created: " {e.user_message()}</p> }.into_any(),
+                None => match preview_action.value().get() {
+                    Some(Ok(result)) => view! {
+                        <ImportPreviewTable
+                            result=result
+                            is_csv=move || previewed.get().is_some_and(|request| request.format == TodoFormat::Csv)
+                            columns=columns
+                            on_columns=preview
+                            on_import=import
+                            importing=import_action.pending()
+                        />
+                    }.into_any(),
+                    Some(Err(e)) => view! { <p class="error">{e.user_message()}</p> }.into_any(),
+                    None => view! { <div></div> }.into_any(),
+                },
+            }}
+        </section>
+    }
+}
+
+#[component]
+fn ImportPreviewTable(
+    result: ImportPreview,
+    is_csv: impl Fn() -> bool + Send + Sync + 'static,
+    columns: RwSignal<CsvColumns>,
+    on_columns: impl Fn() + Clone + Send + Sync + 'static,
+    on_import: impl Fn(leptos::ev::MouseEvent) + Send + Sync + 'static,
+    #[prop(into)] importing: Signal<bool>,
+) -> impl IntoView {
+    let count = result.todos.len();
+    let hidden = count.saturating_sub(PREVIEW_ROWS);
+    let header = result.columns.clone();
+    let on_title = on_columns.clone();
+
+    view! {
+        <Show when=is_csv>
+            <div class="import-options">
+                <label>
+                    "Title column "
+                    <select
+                        prop:value=move || columns.get().title.unwrap_or_default()
+                        on:change={
+                            let on_title = on_title.clone();
+                            move |ev| {
+                                columns.update(|columns| {
+                                    columns.title = Some(event_target_value(&ev));
+                                    columns.chosen = true;
+                                });
+                                on_title();
+                            }
+                        }
+                    >
+                        {header.iter().map(|name| view! { <option value=name.clone()>{name.clone()}</option> }).collect_view()}
+                    </select>
+                </label>
+                <label>
+                    "Done column "
+                    <select
+                        prop:value=move || columns.get().completed.unwrap_or_default()
+                        on:change={
+                            let on_columns = on_columns.clone();
+                            move |ev| {
+                                columns.update(|columns| {
+                                    columns.completed = Some(event_target_value(&ev)).filter(|name| !name.is_empty());
+                                    columns.chosen = true;
+                                });
+                                on_columns();
+                            }
+                        }
+                    >
+                        <option value="">"(none)"</option>
+                        {header.iter().map(|name| view! { <option value=name.clone()>{name.clone()}</option> }).collect_view()}
+                    </select>
+                </label>
+            </div>
+        </Show>
+
+        {if count == 0 {
+            view! { <p class="empty-state">"No todos found in that file."</p> }.into_any()
+        } else {
+            view! {
+                <table class="token-table">
+                    <thead>
+                        <tr>
+                            <th>"Title"</th>
+                            <th>"Done"</th>
+                            <th>"Details"</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        {result.todos.into_iter().take(PREVIEW_ROWS).map(|todo| {
+                            let details = details(&todo);
+                            view! {
+                                <tr>
+                                    <td>{todo.title}</td>
+                                    <td>{if todo.completed { "✓" } else { "" }}</td>
+                                    <td>{details}</td>
+                                </tr>
+                            }
+                        }).collect_view()}
+                    </tbody>
+                </table>
+            }.into_any()
+        }}
+        {(hidden > 0).then(|| view! { <p class="settings-hint">{format!("...and {hidden} more.")}</p> })}
+
+        {(!result.skipped.is_empty()).then(|| view! {
+            <div class="import-skipped">
+                <p>{format!("{} entries will be skipped:", result.skipped.len())}</p>
+                <ul>
+                    {result.skipped.into_iter().map(|reason| view! { <li>{reason}</li> }).collect_view()}
+                </ul>
+            </div>
+        })}
+
+        <button on:click=on_import disabled=move || count == 0 || importing.get()>
+            {format!("Import {count} todos")}
+        </button>
+    }
+}
+
+/// The todo.txt extras of a previewed todo, which stay in its title.
+fn details(todo: &ImportedTodo) -> String {
+    let mut details = Vec::new();
+    if let Some(priority) = todo.priority {
+        details.push(format!("priority {priority}"));
+    }
+    if let Some(due) = &todo.due {
+        details.push(format!("due {due}"));
+    }
+    details.extend(todo.projects.iter().map(|project| format!("+{project}")));
+    details.extend(todo.contexts.iter().map(|context| format!("@{context}")));
+    details.join(", ")
+}
+
+/// Reads the file picked in a file input, calling `on_read` with its name
+/// and contents.
+#[cfg(feature = "hydrate")]
+fn read_file(ev: leptos::ev::Event, on_read: impl Fn(String, String) + 'static) {
+    use wasm_bindgen::JsCast;
+
+    let Some(input) = ev
+        .target()
+        .and_then(|target| target.dyn_into::<web_sys::HtmlInputElement>().ok())
+    else {
+        return;
+    };
+    let Some(file) = input.files().and_then(|files| files.get(0)) else {
+        return;
+    };
+    leptos::task::spawn_local(async move {
+        if let Ok(text) = wasm_bindgen_futures::JsFuture::from(file.text()).await {
+            on_read(file.name(), text.as_string().unwrap_or_default());
+        }
+    });
+}
+
+#[cfg(not(feature = "hydrate"))]
+fn read_file(_ev: leptos::ev::Event, _on_read: impl Fn(String, String) + 'static) {}
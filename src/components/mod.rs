@@ -1,2 +1,7 @@
 pub mod auth;
+pub mod calendar;
+pub mod export;
+pub mod import;
 pub mod nav;
+pub mod settings;
+pub mod webhooks;
@@ -1,5 +1,4 @@
 use crate::auth::*;
-use crate::models::User;
 use leptos::prelude::*;
 use leptos_router::hooks::use_navigate;
 
@@ -34,6 +33,8 @@ pub fn Navigation() -> impl IntoView {
                     if let Some(user_data) = user_context.user.get() {
                         view! {
                             <span class="user-info">"Welcome, " {user_data.username}</span>
+                            <a class="nav-link" href="/import">"Import"</a>
+                            <a class="nav-link" href="/settings">"Settings"</a>
                             <button
                                 class="logout-btn"
                                 on:click=handle_logout
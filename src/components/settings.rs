@@ -0,0 +1,127 @@
+use crate::api_tokens::*;
+use crate::models::{ApiToken, CreateApiToken, TokenScope};
+use leptos::prelude::*;
+
+#[component]
+pub fn ApiTokenSettings() -> impl IntoView {
+    let create_action = ServerAction::<GenerateApiToken>::new();
+    let revoke_action = ServerAction::<RevokeApiToken>::new();
+    let tokens = Resource::new(
+        move || (create_action.version().get(), revoke_action.version().get()),
+        |_| list_api_tokens(),
+    );
+
+    let (name, set_name) = signal(String::new());
+    let (scope, set_scope) = signal(TokenScope::Read);
+    let (expires_in_days, set_expires_in_days) = signal(Some(90i64));
+
+    let submit_token = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        create_action.dispatch(GenerateApiToken {
+            request: CreateApiToken {
+                name: name.get().trim().to_string(),
+                scope: scope.get(),
+                expires_in_days: expires_in_days.get(),
+            },
+        });
+        set_name.set(String::new());
+    };
+
+    view! {
+        <section class="token-settings">
+            <h2>"Personal access tokens"</h2>
+            <p class="settings-hint">
+                "Use a token as " <code>"Authorization: Bearer <token>"</code>
+                " to call the API from scripts and CI."
+            </p>
+
+            {move || match create_action.value().get() {
+                Some(Ok(new_token)) => view! {
+                    <div class="new-token">
+                        <p>"Copy your new token now. It won't be shown again."</p>
+                        <code>{new_token.token}</code>
+                    </div>
+                }.into_any(),
+                Some(Err(e)) => view! { <p class="error">{e.user_message()}</p> }.into_any(),
+                None => view! { <div></div> }.into_any(),
+            }}
+
+            <form on:submit=submit_token class="token-form">
+                <div class="input-group">
+                    <input
+                        type="text"
+                        placeholder="Token name, e.g. CI"
+                        required
+                        prop:value=name
+                        on:input=move |ev| set_name.set(event_target_value(&ev))
+                    />
+                    <select on:change=move |ev| {
+                        set_scope.set(TokenScope::parse(&event_target_value(&ev)).unwrap_or(TokenScope::Read))
+                    }>
+                        <option value="read" selected=true>"Read only"</option>
+                        <option value="write">"Read & write"</option>
+                    </select>
+                    <select on:change=move |ev| {
+                        set_expires_in_days.set(event_target_value(&ev).parse().ok())
+                    }>
+                        <option value="30">"30 days"</option>
+                        <option value="90" selected=true>"90 days"</option>
+                        <option value="365">"1 year"</option>
+                        <option value="never">"Never"</option>
+                    </select>
+                    <button type="submit" disabled=move || create_action.pending().get()>"Create"</button>
+                </div>
+            </form>
+
+            <Suspense fallback=move || view! { <p class="loading">"Loading tokens..."</p> }>
+                {move || match tokens.get() {
+                    Some(Ok(tokens_list)) if tokens_list.is_empty() => {
+                        view! { <p class="empty-state">"No tokens yet."</p> }.into_any()
+                    }
+                    Some(Ok(tokens_list)) => view! {
+                        <table class="token-table">
+                            <thead>
+                                <tr>
+                                    <th>"Name"</th>
+                                    <th>"Scope"</th>
+                                    <th>"Expires"</th>
+                                    <th>"Last used"</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                <For
+                                    each=move || tokens_list.clone()
+                                    key=|token| token.id
+                                    children=move |token: ApiToken| {
+                                        let token_id = token.id;
+                                        view! {
+                                            <tr>
+                                                <td>{token.name}</td>
+                                                <td>{token.scope.as_str()}</td>
+                                                <td>{token.expires_at.unwrap_or_else(|| "never".to_string())}</td>
+                                                <td>{token.last_used_at.unwrap_or_else(|| "never".to_string())}</td>
+                                                <td>
+                                                    <button
+                                                        class="delete-btn"
+                                                        on:click=move |_| {
+                                                            revoke_action.dispatch(RevokeApiToken { id: token_id });
+                                                        }
+                                                    >
+                                                        "Revoke"
+                                                    </button>
+                                                </td>
+                                            </tr>
+                                        }
+                                    }
+                                />
+                            </tbody>
+                        </table>
+                    }.into_any(),
+                    Some(Err(e)) => view! { <p class="error">"Error loading tokens: " {e.user_message()}</p> }.into_any(),
+                    None => view! { <p class="loading">"Loading tokens..."</p> }.into_any(),
+                }}
+            </Suspense>
+        </section>
+    }
+}
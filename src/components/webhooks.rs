@@ -0,0 +1,191 @@
+use crate::models::{CreateWebhook, Webhook, WebhookDelivery, WebhookEvent};
+use crate::webhooks::*;
+use leptos::prelude::*;
+
+#[component]
+pub fn WebhookSettings() -> impl IntoView {
+    let add_action = ServerAction::<AddWebhook>::new();
+    let remove_action = ServerAction::<RemoveWebhook>::new();
+    let webhooks = Resource::new(
+        move || (add_action.version().get(), remove_action.version().get()),
+        |_| list_webhooks(),
+    );
+    let (refresh, set_refresh) = signal(0);
+    let deliveries = Resource::new(
+        move || (add_action.version().get(), remove_action.version().get(), refresh.get()),
+        |_| list_webhook_deliveries(),
+    );
+
+    let (url, set_url) = signal(String::new());
+    let (events, set_events) = signal(WebhookEvent::ALL.to_vec());
+
+    let submit_webhook = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        add_action.dispatch(AddWebhook {
+            request: CreateWebhook {
+                url: url.get().trim().to_string(),
+                events: events.get(),
+            },
+        });
+        set_url.set(String::new());
+    };
+
+    view! {
+        <section class="token-settings">
+            <h2>"Webhooks"</h2>
+            <p class="settings-hint">
+                "Each delivery is a JSON POST signed with the webhook's secret in the "
+                <code>"X-Todo-Signature"</code> " header."
+            </p>
+
+            {move || match add_action.value().get() {
+                Some(Ok(new_webhook)) => view! {
+                    <div class="new-token">
+                        <p>"Copy the signing secret now. It won't be shown again."</p>
+                        <code>{new_webhook.secret}</code>
+                    </div>
+                }.into_any(),
+                Some(Err(e)) => view! { <p class="error">{e.user_message()}</p> }.into_any(),
+                None => view! { <div></div> }.into_any(),
+            }}
+
+            <form on:submit=submit_webhook class="token-form">
+                <div class="input-group">
+                    <input
+                        type="url"
+                        placeholder="https://example.com/hooks/todos"
+                        required
+                        prop:value=url
+                        on:input=move |ev| set_url.set(event_target_value(&ev))
+                    />
+                    <button type="submit" disabled=move || add_action.pending().get()>"Add"</button>
+                </div>
+                <div class="event-filters">
+                    {WebhookEvent::ALL.into_iter().map(|event| view! {
+                        <label>
+                            <input
+                                type="checkbox"
+                                prop:checked=move || events.get().contains(&event)
+                                on:change=move |ev| {
+                                    let checked = event_target_checked(&ev);
+                                    set_events.update(|events| {
+                                        events.retain(|e| *e != event);
+                                        if checked {
+                                            events.push(event);
+                                        }
+                                    });
+                                }
+                            />
+                            {event.as_str()}
+                        </label>
+                    }).collect_view()}
+                </div>
+            </form>
+
+            <Suspense fallback=move || view! { <p class="loading">"Loading webhooks..."</p> }>
+                {move || match webhooks.get() {
+                    Some(Ok(webhook_list)) if webhook_list.is_empty() => {
+                        view! { <p class="empty-state">"No webhooks yet."</p> }.into_any()
+                    }
+                    Some(Ok(webhook_list)) => view! {
+                        <table class="token-table">
+                            <thead>
+                                <tr>
+                                    <th>"URL"</th>
+                                    <th>"Events"</th>
+                                    <th>"Created"</th>
+                                    <th></th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                <For
+                                    each=move || webhook_list.clone()
+                                    key=|webhook| webhook.id
+                                    children=move |webhook: Webhook| {
+                                        let webhook_id = webhook.id;
+                                        let events = webhook
+                                            .events
+                                            .iter()
+                                            .map(WebhookEvent::as_str)
+                                            .collect::<Vec<_>>()
+                                            .join(", ");
+                                        view! {
+                                            <tr>
+                                                <td class="webhook-url">{webhook.url}</td>
+                                                <td>{events}</td>
+                                                <td>{webhook.created_at}</td>
+                                                <td>
+                                                    <button
+                                                        class="delete-btn"
+                                                        on:click=move |_| {
+                                                            remove_action.dispatch(RemoveWebhook { id: webhook_id });
+                                                        }
+                                                    >
+                                                        "Remove"
+                                                    </button>
+                                                </td>
+                                            </tr>
+                                        }
+                                    }
+                                />
+                            </tbody>
+                        </table>
+                    }.into_any(),
+                    Some(Err(e)) => view! { <p class="error">"Error loading webhooks: " {e.user_message()}</p> }.into_any(),
+                    None => view! { <p class="loading">"Loading webhooks..."</p> }.into_any(),
+                }}
+            </Suspense>
+
+            <div class="delivery-log-header">
+                <h3>"Recent deliveries"</h3>
+                <button on:click=move |_| set_refresh.update(|n| *n += 1)>"Refresh"</button>
+            </div>
+            <Suspense fallback=move || view! { <p class="loading">"Loading deliveries..."</p> }>
+                {move || match deliveries.get() {
+                    Some(Ok(delivery_list)) if delivery_list.is_empty() => {
+                        view! { <p class="empty-state">"Nothing delivered yet."</p> }.into_any()
+                    }
+                    Some(Ok(delivery_list)) => view! {
+                        <table class="token-table">
+                            <thead>
+                                <tr>
+                                    <th>"Event"</th>
+                                    <th>"URL"</th>
+                                    <th>"Status"</th>
+                                    <th>"Attempts"</th>
+                                    <th>"Last response"</th>
+                                    <th>"Queued"</th>
+                                </tr>
+                            </thead>
+                            <tbody>
+                                <For
+                                    each=move || delivery_list.clone()
+                                    key=|delivery| (delivery.id, delivery.attempts)
+                                    children=move |delivery: WebhookDelivery| {
+                                        let last_response = match (delivery.last_status_code, delivery.last_error) {
+                                            (_, Some(error)) => error,
+                                            (Some(code), None) => code.to_string(),
+                                            (None, None) => "-".to_string(),
+                                        };
+                                        view! {
+                                            <tr>
+                                                <td>{delivery.event}</td>
+                                                <td class="webhook-url">{delivery.url}</td>
+                                                <td class=format!("delivery-{}", delivery.status)>{delivery.status.clone()}</td>
+                                                <td>{delivery.attempts}</td>
+                                                <td>{last_response}</td>
+                                                <td>{delivery.created_at}</td>
+                                            </tr>
+                                        }
+                                    }
+                                />
+                            </tbody>
+                        </table>
+                    }.into_any(),
+                    Some(Err(e)) => view! { <p class="error">"Error loading deliveries: " {e.user_message()}</p> }.into_any(),
+                    None => view! { <p class="loading">"Loading deliveries..."</p> }.into_any(),
+                }}
+            </Suspense>
+        </section>
+    }
+}
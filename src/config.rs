@@ -0,0 +1,574 @@
+//! Server settings, layered from lowest to highest precedence: built-in
+//! defaults, a TOML file, environment variables and command-line flags.
+//!
+//! [`Config::load`] validates the merged result before anything starts;
+//! the server then provides the [`Config`] as context so server functions
+//! can read it.
+
+use crate::auth::SessionConfig;
+use crate::cli::Command;
+use crate::database::Backend;
+use clap::Parser;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Read when neither `--config` nor `CONFIG_FILE` names a file, if it exists.
+pub const DEFAULT_CONFIG_FILE: &str = "todo-leptos.toml";
+
+const DEFAULT_DATABASE_URL: &str = "sqlite:Todos.db";
+const DEFAULT_MAX_CONNECTIONS: u32 = 10;
+const DEFAULT_BUSY_TIMEOUT_SECS: u32 = 5;
+const DEFAULT_BACKUP_INTERVAL_HOURS: u32 = 24;
+const DEFAULT_BACKUP_KEEP: u32 = 7;
+const DEFAULT_LOG_FILTER: &str = "info";
+const MIN_METRICS_TOKEN_LEN: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Where to listen, overriding the Leptos `site-addr`.
+    pub address: Option<SocketAddr>,
+    pub database: DatabaseConfig,
+    pub session: SessionConfig,
+    pub auth: AuthConfig,
+    pub backup: BackupConfig,
+    pub metrics: MetricsConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseConfig {
+    pub url: String,
+    /// Size of the pool, or of the read pool for SQLite, which writes
+    /// through a single connection.
+    pub max_connections: u32,
+    /// How long SQLite waits for a lock before failing with `SQLITE_BUSY`.
+    pub busy_timeout: Duration,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: DEFAULT_DATABASE_URL.to_string(),
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            busy_timeout: Duration::from_secs(DEFAULT_BUSY_TIMEOUT_SECS.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// Where backups are written. Without one there are no scheduled
+    /// backups and the admin endpoint refuses to make any.
+    pub directory: Option<PathBuf>,
+    /// Time between scheduled backups.
+    pub interval: Duration,
+    /// How many backups to keep; older ones are deleted.
+    pub keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            interval: Duration::from_secs(u64::from(DEFAULT_BACKUP_INTERVAL_HOURS) * 60 * 60),
+            keep: DEFAULT_BACKUP_KEEP as usize,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MetricsConfig {
+    /// The bearer token Prometheus scrapes `/metrics` with. Without one
+    /// the endpoint isn't served.
+    pub token: Option<String>,
+}
+
+impl std::fmt::Debug for MetricsConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetricsConfig")
+            .field("token", &self.token.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    /// Which events to log, in `RUST_LOG` syntax, e.g.
+    /// `info,todo_leptos=debug`.
+    pub filter: String,
+    pub format: LogFormat,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            filter: DEFAULT_LOG_FILTER.to_string(),
+            format: LogFormat::Text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One human-readable line per event.
+    #[default]
+    Text,
+    /// One JSON object per event, for log collectors.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("expected text or json, got \"{value}\"")),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// Work factor for hashing new passwords, 4 to 31.
+    pub bcrypt_cost: u32,
+}
+
+/// Command-line flags of the server. Each setting also has an environment
+/// variable and a key in the config file.
+#[derive(Debug, Default, Parser)]
+#[command(version, about = "Runs and manages the todo-leptos server")]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// TOML config file [env: CONFIG_FILE] [default: todo-leptos.toml if present]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// Address to listen on [env: SERVER_ADDRESS]
+    #[arg(long, global = true, value_name = "HOST:PORT")]
+    pub address: Option<SocketAddr>,
+    /// A sqlite: or postgres:// URL [env: DATABASE_URL] [default: sqlite:Todos.db]
+    #[arg(long, global = true, value_name = "URL")]
+    pub database_url: Option<String>,
+    /// Connection pool size [env: DATABASE_MAX_CONNECTIONS] [default: 10]
+    #[arg(long, global = true, value_name = "N")]
+    pub database_max_connections: Option<u32>,
+    /// Seconds SQLite waits for a lock [env: DATABASE_BUSY_TIMEOUT_SECS] [default: 5]
+    #[arg(long, global = true, value_name = "SECS")]
+    pub database_busy_timeout_secs: Option<u32>,
+    /// [env: SESSION_COOKIE_NAME] [default: session_id]
+    #[arg(long, global = true, value_name = "NAME")]
+    pub session_cookie_name: Option<String>,
+    /// [env: SESSION_COOKIE_DOMAIN]
+    #[arg(long, global = true, value_name = "DOMAIN")]
+    pub session_cookie_domain: Option<String>,
+    /// Mark cookies `Secure` [env: SESSION_COOKIE_SECURE] [default: true in production]
+    #[arg(long, global = true, value_name = "BOOL")]
+    pub session_cookie_secure: Option<bool>,
+    /// Hours a session survives without activity [env: SESSION_IDLE_TIMEOUT_HOURS] [default: 168]
+    #[arg(long, global = true, value_name = "HOURS")]
+    pub session_idle_timeout_hours: Option<u32>,
+    /// Hours a session survives at most [env: SESSION_ABSOLUTE_TIMEOUT_HOURS] [default: 720]
+    #[arg(long, global = true, value_name = "HOURS")]
+    pub session_absolute_timeout_hours: Option<u32>,
+    /// Password hashing work factor [env: BCRYPT_COST] [default: 12]
+    #[arg(long, global = true, value_name = "COST")]
+    pub bcrypt_cost: Option<u32>,
+    /// Directory for SQLite backups; unset disables them [env: BACKUP_DIR]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub backup_dir: Option<PathBuf>,
+    /// Hours between scheduled backups [env: BACKUP_INTERVAL_HOURS] [default: 24]
+    #[arg(long, global = true, value_name = "HOURS")]
+    pub backup_interval_hours: Option<u32>,
+    /// Backups to keep [env: BACKUP_KEEP] [default: 7]
+    #[arg(long, global = true, value_name = "N")]
+    pub backup_keep: Option<u32>,
+    /// Bearer token for scraping /metrics; unset disables it [env: METRICS_TOKEN]
+    #[arg(long, global = true, value_name = "TOKEN")]
+    pub metrics_token: Option<String>,
+    /// Which events to log, e.g. info,todo_leptos=debug [env: RUST_LOG] [default: info]
+    #[arg(long, global = true, value_name = "FILTER")]
+    pub log_filter: Option<String>,
+    /// text or json [env: LOG_FORMAT] [default: text]
+    #[arg(long, global = true, value_name = "FORMAT")]
+    pub log_format: Option<LogFormat>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("couldn't read config file {}: {source}", path.display())]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("invalid config file {}: {source}", path.display())]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    #[error("environment variable {name}: {message}")]
+    Env { name: &'static str, message: String },
+    #[error("invalid setting `{key}`: {message}")]
+    Invalid { key: &'static str, message: String },
+}
+
+/// One source's settings, shaped like the config file. Unset values fall
+/// through to the layer below.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Layer {
+    server: ServerLayer,
+    database: DatabaseLayer,
+    session: SessionLayer,
+    auth: AuthLayer,
+    backup: BackupLayer,
+    metrics: MetricsLayer,
+    log: LogLayer,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerLayer {
+    address: Option<SocketAddr>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct DatabaseLayer {
+    url: Option<String>,
+    max_connections: Option<u32>,
+    busy_timeout_secs: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SessionLayer {
+    cookie_name: Option<String>,
+    cookie_domain: Option<String>,
+    cookie_secure: Option<bool>,
+    idle_timeout_hours: Option<u32>,
+    absolute_timeout_hours: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AuthLayer {
+    bcrypt_cost: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BackupLayer {
+    directory: Option<PathBuf>,
+    interval_hours: Option<u32>,
+    keep: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct MetricsLayer {
+    token: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LogLayer {
+    filter: Option<String>,
+    format: Option<LogFormat>,
+}
+
+fn overlay<T>(base: &mut Option<T>, over: Option<T>) {
+    if over.is_some() {
+        *base = over;
+    }
+}
+
+impl Layer {
+    fn overlay(&mut self, over: Layer) {
+        overlay(&mut self.server.address, over.server.address);
+        overlay(&mut self.database.url, over.database.url);
+        overlay(&mut self.database.max_connections, over.database.max_connections);
+        overlay(&mut self.database.busy_timeout_secs, over.database.busy_timeout_secs);
+        overlay(&mut self.session.cookie_name, over.session.cookie_name);
+        overlay(&mut self.session.cookie_domain, over.session.cookie_domain);
+        overlay(&mut self.session.cookie_secure, over.session.cookie_secure);
+        overlay(&mut self.session.idle_timeout_hours, over.session.idle_timeout_hours);
+        overlay(&mut self.session.absolute_timeout_hours, over.session.absolute_timeout_hours);
+        overlay(&mut self.auth.bcrypt_cost, over.auth.bcrypt_cost);
+        overlay(&mut self.backup.directory, over.backup.directory);
+        overlay(&mut self.backup.interval_hours, over.backup.interval_hours);
+        overlay(&mut self.backup.keep, over.backup.keep);
+        overlay(&mut self.metrics.token, over.metrics.token);
+        overlay(&mut self.log.filter, over.log.filter);
+        overlay(&mut self.log.format, over.log.format);
+    }
+
+    fn from_file(path: PathBuf) -> Result<Self, ConfigError> {
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(source) => return Err(ConfigError::Read { path, source }),
+        };
+        toml::from_str(&content).map_err(|source| ConfigError::Parse { path, source })
+    }
+
+    fn from_env(env: &impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        fn parsed<T: FromStr>(
+            env: &impl Fn(&str) -> Option<String>,
+            name: &'static str,
+            expected: &str,
+        ) -> Result<Option<T>, ConfigError> {
+            env(name)
+                .map(|value| {
+                    value.parse().map_err(|_| ConfigError::Env {
+                        name,
+                        message: format!("expected {expected}, got \"{value}\""),
+                    })
+                })
+                .transpose()
+        }
+        let flag = |name: &'static str| {
+            env(name)
+                .map(|value| match value.to_ascii_lowercase().as_str() {
+                    "1" | "true" | "yes" => Ok(true),
+                    "0" | "false" | "no" => Ok(false),
+                    _ => Err(ConfigError::Env {
+                        name,
+                        message: format!("expected true or false, got \"{value}\""),
+                    }),
+                })
+                .transpose()
+        };
+
+        Ok(Layer {
+            server: ServerLayer {
+                address: parsed(env, "SERVER_ADDRESS", "an address like 127.0.0.1:3000")?,
+            },
+            database: DatabaseLayer {
+                url: env("DATABASE_URL"),
+                max_connections: parsed(env, "DATABASE_MAX_CONNECTIONS", "a number")?,
+                busy_timeout_secs: parsed(env, "DATABASE_BUSY_TIMEOUT_SECS", "a whole number of seconds")?,
+            },
+            session: SessionLayer {
+                cookie_name: env("SESSION_COOKIE_NAME"),
+                cookie_domain: env("SESSION_COOKIE_DOMAIN"),
+                cookie_secure: flag("SESSION_COOKIE_SECURE")?,
+                idle_timeout_hours: parsed(env, "SESSION_IDLE_TIMEOUT_HOURS", "a whole number of hours")?,
+                absolute_timeout_hours: parsed(env, "SESSION_ABSOLUTE_TIMEOUT_HOURS", "a whole number of hours")?,
+            },
+            auth: AuthLayer {
+                bcrypt_cost: parsed(env, "BCRYPT_COST", "a number")?,
+            },
+            backup: BackupLayer {
+                directory: env("BACKUP_DIR").map(PathBuf::from),
+                interval_hours: parsed(env, "BACKUP_INTERVAL_HOURS", "a whole number of hours")?,
+                keep: parsed(env, "BACKUP_KEEP", "a number")?,
+            },
+            metrics: MetricsLayer {
+                token: env("METRICS_TOKEN"),
+            },
+            log: LogLayer {
+                filter: env("RUST_LOG"),
+                format: parsed(env, "LOG_FORMAT", "text or json")?,
+            },
+        })
+    }
+
+    fn from_args(args: &Args) -> Self {
+        Layer {
+            server: ServerLayer {
+                address: args.address,
+            },
+            database: DatabaseLayer {
+                url: args.database_url.clone(),
+                max_connections: args.database_max_connections,
+                busy_timeout_secs: args.database_busy_timeout_secs,
+            },
+            session: SessionLayer {
+                cookie_name: args.session_cookie_name.clone(),
+                cookie_domain: args.session_cookie_domain.clone(),
+                cookie_secure: args.session_cookie_secure,
+                idle_timeout_hours: args.session_idle_timeout_hours,
+                absolute_timeout_hours: args.session_absolute_timeout_hours,
+            },
+            auth: AuthLayer {
+                bcrypt_cost: args.bcrypt_cost,
+            },
+            backup: BackupLayer {
+                directory: args.backup_dir.clone(),
+                interval_hours: args.backup_interval_hours,
+                keep: args.backup_keep,
+            },
+            metrics: MetricsLayer {
+                token: args.metrics_token.clone(),
+            },
+            log: LogLayer {
+                filter: args.log_filter.clone(),
+                format: args.log_format,
+            },
+        }
+    }
+}
+
+impl Config {
+    /// Loads the settings for this process: its flags and environment, and
+    /// the config file they point to.
+    pub fn from_process(args: &Args, secure_default: bool) -> Result<Self, ConfigError> {
+        Self::load(
+            args,
+            |name| std::env::var(name).ok().filter(|value| !value.is_empty()),
+            secure_default,
+        )
+    }
+
+    /// The built-in settings alone, without reading any config file,
+    /// environment or flags.
+    pub fn defaults(secure_default: bool) -> Self {
+        Self::validate(Layer::default(), secure_default).expect("the defaults are valid")
+    }
+
+    /// Merges the config file, the variables `env` returns and `args`.
+    /// `secure_default` decides the cookies' `Secure` flag when no layer
+    /// sets it.
+    pub fn load(
+        args: &Args,
+        env: impl Fn(&str) -> Option<String>,
+        secure_default: bool,
+    ) -> Result<Self, ConfigError> {
+        let path = args
+            .config
+            .clone()
+            .or_else(|| env("CONFIG_FILE").map(PathBuf::from));
+        let mut layer = match path {
+            Some(path) => Layer::from_file(path)?,
+            None if std::path::Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Layer::from_file(DEFAULT_CONFIG_FILE.into())?
+            }
+            None => Layer::default(),
+        };
+        layer.overlay(Layer::from_env(&env)?);
+        layer.overlay(Layer::from_args(args));
+
+        Self::validate(layer, secure_default)
+    }
+
+    fn validate(layer: Layer, secure_default: bool) -> Result<Self, ConfigError> {
+        let invalid = |key, message: &str| ConfigError::Invalid {
+            key,
+            message: message.to_string(),
+        };
+        let defaults = SessionConfig::default();
+
+        let url = layer
+            .database
+            .url
+            .unwrap_or_else(|| DEFAULT_DATABASE_URL.to_string());
+        if Backend::from_url(&url).is_none() {
+            return Err(invalid("database.url", "must be a sqlite: or postgres:// URL"));
+        }
+        let max_connections = layer.database.max_connections.unwrap_or(DEFAULT_MAX_CONNECTIONS);
+        if max_connections == 0 {
+            return Err(invalid("database.max_connections", "must be at least 1"));
+        }
+        let busy_timeout = Duration::from_secs(
+            layer
+                .database
+                .busy_timeout_secs
+                .unwrap_or(DEFAULT_BUSY_TIMEOUT_SECS)
+                .into(),
+        );
+
+        let cookie_name = layer.session.cookie_name.unwrap_or(defaults.cookie_name);
+        let is_token = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+        if cookie_name.is_empty() || !cookie_name.chars().all(is_token) {
+            return Err(invalid(
+                "session.cookie_name",
+                "must be letters, digits or punctuation other than separators",
+            ));
+        }
+        let cookie_domain = layer.session.cookie_domain;
+        if cookie_domain
+            .as_deref()
+            .is_some_and(|domain| domain.is_empty() || domain.contains(|c: char| c == ';' || c.is_whitespace()))
+        {
+            return Err(invalid("session.cookie_domain", "must be a bare domain name"));
+        }
+
+        let hours = |value: Option<u32>, default: chrono::Duration| {
+            value.map_or(default, |hours| chrono::Duration::hours(hours.into()))
+        };
+        let idle_timeout = hours(layer.session.idle_timeout_hours, defaults.idle_timeout);
+        let absolute_timeout = hours(layer.session.absolute_timeout_hours, defaults.absolute_timeout);
+        if idle_timeout < chrono::Duration::hours(1) {
+            return Err(invalid("session.idle_timeout_hours", "must be at least 1"));
+        }
+        if absolute_timeout < idle_timeout {
+            return Err(invalid(
+                "session.absolute_timeout_hours",
+                "must be at least session.idle_timeout_hours",
+            ));
+        }
+
+        let bcrypt_cost = layer.auth.bcrypt_cost.unwrap_or(bcrypt::DEFAULT_COST);
+        if !(4..=31).contains(&bcrypt_cost) {
+            return Err(invalid("auth.bcrypt_cost", "must be between 4 and 31"));
+        }
+
+        let backup_directory = layer.backup.directory;
+        if backup_directory.is_some() && Backend::from_url(&url) != Some(Backend::Sqlite) {
+            return Err(invalid("backup.directory", "backups are only supported for SQLite"));
+        }
+        let backup_interval_hours = layer.backup.interval_hours.unwrap_or(DEFAULT_BACKUP_INTERVAL_HOURS);
+        if backup_interval_hours == 0 {
+            return Err(invalid("backup.interval_hours", "must be at least 1"));
+        }
+        let backup_keep = layer.backup.keep.unwrap_or(DEFAULT_BACKUP_KEEP);
+        if backup_keep == 0 {
+            return Err(invalid("backup.keep", "must be at least 1"));
+        }
+
+        let metrics_token = layer.metrics.token;
+        if metrics_token.as_deref().is_some_and(|token| token.len() < MIN_METRICS_TOKEN_LEN) {
+            return Err(invalid(
+                "metrics.token",
+                &format!("must be at least {MIN_METRICS_TOKEN_LEN} characters"),
+            ));
+        }
+
+        let log_filter = layer.log.filter.unwrap_or_else(|| DEFAULT_LOG_FILTER.to_string());
+        if let Err(e) = tracing_subscriber::EnvFilter::try_new(&log_filter) {
+            return Err(invalid("log.filter", &e.to_string()));
+        }
+
+        Ok(Config {
+            address: layer.server.address,
+            database: DatabaseConfig {
+                url,
+                max_connections,
+                busy_timeout,
+            },
+            session: SessionConfig {
+                cookie_name,
+                cookie_domain,
+                cookie_secure: layer.session.cookie_secure.unwrap_or(secure_default),
+                idle_timeout,
+                absolute_timeout,
+            },
+            auth: AuthConfig { bcrypt_cost },
+            backup: BackupConfig {
+                directory: backup_directory,
+                interval: Duration::from_secs(u64::from(backup_interval_hours) * 60 * 60),
+                keep: backup_keep as usize,
+            },
+            metrics: MetricsConfig { token: metrics_token },
+            log: LogConfig {
+                filter: log_filter,
+                format: layer.log.format.unwrap_or_default(),
+            },
+        })
+    }
+}
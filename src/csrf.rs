@@ -0,0 +1,188 @@
+use leptos::server_fn::client::{browser::BrowserClient, Client};
+use leptos::server_fn::error::FromServerFnError;
+use leptos::server_fn::request::browser::BrowserRequest;
+use leptos::server_fn::response::browser::BrowserResponse;
+use leptos::server_fn::Bytes;
+use std::future::Future;
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Server function client that attaches the page's CSRF token to every call.
+///
+/// The token is rendered into `<meta name="csrf-token">` by `shell` and
+/// checked against the `csrf_token` cookie by [`middleware::csrf_protection`].
+pub struct CsrfClient;
+
+impl<E, IS, OS> Client<E, IS, OS> for CsrfClient
+where
+    E: FromServerFnError,
+    IS: FromServerFnError,
+    OS: FromServerFnError,
+{
+    type Request = BrowserRequest;
+    type Response = BrowserResponse;
+
+    fn send(req: Self::Request) -> impl Future<Output = Result<Self::Response, E>> + Send {
+        if let Some(token) = page_token() {
+            req.headers().append(CSRF_HEADER, &token);
+        }
+        <BrowserClient as Client<E, IS, OS>>::send(req)
+    }
+
+    fn open_websocket(
+        path: &str,
+    ) -> impl Future<
+        Output = Result<
+            (
+                impl futures::Stream<Item = Result<Bytes, Bytes>> + Send + 'static,
+                impl futures::Sink<Bytes> + Send + 'static,
+            ),
+            E,
+        >,
+    > + Send {
+        <BrowserClient as Client<E, IS, OS>>::open_websocket(path)
+    }
+
+    fn spawn(future: impl Future<Output = ()> + Send + 'static) {
+        <BrowserClient as Client<E, IS, OS>>::spawn(future)
+    }
+}
+
+#[cfg(feature = "hydrate")]
+fn page_token() -> Option<String> {
+    leptos::prelude::document()
+        .query_selector("meta[name=csrf-token]")
+        .ok()
+        .flatten()?
+        .get_attribute("content")
+        .filter(|token| !token.is_empty())
+}
+
+#[cfg(not(feature = "hydrate"))]
+fn page_token() -> Option<String> {
+    None
+}
+
+/// The CSRF token issued for the request being rendered, for embedding in
+/// the page shell.
+#[cfg(feature = "ssr")]
+pub fn request_token() -> Option<String> {
+    use axum::http::request::Parts;
+    use leptos::prelude::use_context;
+
+    use_context::<Parts>()?
+        .extensions
+        .get::<middleware::CsrfToken>()
+        .map(|token| token.0.clone())
+}
+
+#[cfg(not(feature = "ssr"))]
+pub fn request_token() -> Option<String> {
+    None
+}
+
+#[cfg(feature = "ssr")]
+pub mod middleware {
+    use super::{CSRF_COOKIE, CSRF_HEADER};
+    use crate::auth::SessionConfig;
+    use crate::error::AppError;
+    use axum::{
+        extract::State,
+        http::{header, HeaderMap, HeaderValue, Method},
+        middleware::Next,
+        response::{IntoResponse, Response},
+    };
+    use uuid::Uuid;
+
+    #[derive(Debug, Clone)]
+    pub struct CsrfToken(pub String);
+
+    /// `/api/v1` endpoints that take credentials in the request body rather
+    /// than from the browser's cookies: signing in for a token and signing
+    /// up. A forged request without a session cookie gains nothing the
+    /// forger couldn't do directly.
+    const CREDENTIAL_PATHS: [&str; 2] = ["/api/v1/tokens", "/api/v1/users"];
+
+    /// Issues a CSRF token cookie and rejects state-changing `/api` requests
+    /// that don't carry the matching token.
+    ///
+    /// Requests without the header (e.g. plain form posts) are accepted only
+    /// when their `Origin` or `Referer` matches the `Host` they were sent to.
+    /// Requests carrying an API token are exempt, as are requests to the
+    /// [`CREDENTIAL_PATHS`] without a session cookie.
+    pub async fn csrf_protection(
+        State(config): State<SessionConfig>,
+        mut req: axum::extract::Request,
+        next: Next,
+    ) -> Response {
+        let cookie_token = cookie_value(req.headers(), CSRF_COOKIE);
+
+        let safe_method = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+        // Bearer tokens are never sent automatically by the browser, so
+        // requests authenticated with them can't be forged cross-site
+        let uses_bearer_token = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("Bearer "));
+        let sends_credentials = CREDENTIAL_PATHS.contains(&req.uri().path())
+            && cookie_value(req.headers(), &config.cookie_name).is_none();
+        if !safe_method && !uses_bearer_token && !sends_credentials && req.uri().path().starts_with("/api/") {
+            let verified = match req.headers().get(CSRF_HEADER).and_then(|v| v.to_str().ok()) {
+                Some(header_token) => cookie_token
+                    .as_deref()
+                    .is_some_and(|cookie_token| constant_time_eq(cookie_token, header_token)),
+                None => is_same_origin(req.headers()),
+            };
+
+            if !verified {
+                return AppError::Forbidden.into_response();
+            }
+        }
+
+        let issue_cookie = cookie_token.is_none();
+        let token = cookie_token.unwrap_or_else(|| Uuid::new_v4().simple().to_string());
+        req.extensions_mut().insert(CsrfToken(token.clone()));
+
+        let mut response = next.run(req).await;
+        if issue_cookie {
+            let cookie = format!("{CSRF_COOKIE}={token}; {}", config.cookie_attributes());
+            if let Ok(value) = HeaderValue::from_str(&cookie) {
+                response.headers_mut().append(header::SET_COOKIE, value);
+            }
+        }
+        response
+    }
+
+    fn cookie_value(headers: &HeaderMap, name: &str) -> Option<String> {
+        let cookie_str = headers.get(header::COOKIE)?.to_str().ok()?;
+        cookie_str.split(';').find_map(|cookie| {
+            let (key, value) = cookie.trim().split_once('=')?;
+            (key == name && !value.is_empty()).then(|| value.to_string())
+        })
+    }
+
+    fn is_same_origin(headers: &HeaderMap) -> bool {
+        let Some(host) = headers.get(header::HOST).and_then(|v| v.to_str().ok()) else {
+            return false;
+        };
+        let source = headers
+            .get(header::ORIGIN)
+            .or_else(|| headers.get(header::REFERER))
+            .and_then(|v| v.to_str().ok());
+
+        source
+            .and_then(|url| url.split_once("://"))
+            .map(|(_, rest)| rest.split('/').next().unwrap_or_default())
+            .is_some_and(|authority| authority.eq_ignore_ascii_case(host))
+    }
+
+    pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+        a.len() == b.len()
+            && a.bytes()
+                .zip(b.bytes())
+                .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+                == 0
+    }
+}
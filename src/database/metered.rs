@@ -0,0 +1,326 @@
+//! A [`Repository`] that times every operation of the one it wraps, for
+//! [`crate::metrics`]. Each operation is observed under its method name.
+
+use super::repository::{
+    ApiTokenRepository, CalendarRepository, Repository, SessionRepository, TodoRepository, UserRepository,
+    WebhookRepository,
+};
+use super::{DavResource, DueDelivery, PoolStats};
+use crate::models::{
+    ApiToken, CalendarFeed, CreateTodo, ImportedTodo, LoginUser, RegisterUser, Session, Todo, TokenScope, UpdateTodo,
+    User, Webhook, WebhookDelivery, WebhookEvent,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use prometheus::HistogramVec;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+pub struct Metered {
+    inner: Arc<dyn Repository>,
+    /// Labelled by operation.
+    durations: HistogramVec,
+}
+
+impl Metered {
+    pub fn new(inner: Arc<dyn Repository>, durations: HistogramVec) -> Self {
+        Self { inner, durations }
+    }
+
+    async fn timed<T>(&self, operation: &str, query: impl Future<Output = T>) -> T {
+        let _timer = self.durations.with_label_values(&[operation]).start_timer();
+        query.await
+    }
+}
+
+#[async_trait]
+impl UserRepository for Metered {
+    async fn create_user(&self, user_data: RegisterUser, bcrypt_cost: u32) -> Result<User, sqlx::Error> {
+        self.timed("create_user", self.inner.create_user(user_data, bcrypt_cost)).await
+    }
+
+    async fn authenticate_user(&self, login_data: LoginUser) -> Result<Option<User>, sqlx::Error> {
+        self.timed("authenticate_user", self.inner.authenticate_user(login_data)).await
+    }
+
+    async fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, sqlx::Error> {
+        self.timed("get_user_by_id", self.inner.get_user_by_id(user_id)).await
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
+        self.timed("get_user_by_username", self.inner.get_user_by_username(username)).await
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        self.timed("list_users", self.inner.list_users()).await
+    }
+
+    async fn set_user_disabled(&self, user_id: i64, disabled: bool) -> Result<bool, sqlx::Error> {
+        self.timed("set_user_disabled", self.inner.set_user_disabled(user_id, disabled)).await
+    }
+
+    async fn set_user_admin(&self, user_id: i64, admin: bool) -> Result<bool, sqlx::Error> {
+        self.timed("set_user_admin", self.inner.set_user_admin(user_id, admin)).await
+    }
+
+    async fn set_user_password(&self, user_id: i64, password: &str, bcrypt_cost: u32) -> Result<bool, sqlx::Error> {
+        self.timed("set_user_password", self.inner.set_user_password(user_id, password, bcrypt_cost))
+            .await
+    }
+
+    async fn user_exists(&self, username: &str, email: &str) -> Result<bool, sqlx::Error> {
+        self.timed("user_exists", self.inner.user_exists(username, email)).await
+    }
+}
+
+#[async_trait]
+impl SessionRepository for Metered {
+    async fn create_session(
+        &self,
+        user_id: i64,
+        idle_timeout: Duration,
+        absolute_timeout: Duration,
+    ) -> Result<(String, Session), sqlx::Error> {
+        self.timed(
+            "create_session",
+            self.inner.create_session(user_id, idle_timeout, absolute_timeout),
+        )
+        .await
+    }
+
+    async fn get_session(&self, token: &str) -> Result<Option<(Session, User)>, sqlx::Error> {
+        self.timed("get_session", self.inner.get_session(token)).await
+    }
+
+    async fn refresh_session(&self, session_id: &str, idle_timeout: Duration) -> Result<bool, sqlx::Error> {
+        self.timed("refresh_session", self.inner.refresh_session(session_id, idle_timeout))
+            .await
+    }
+
+    async fn delete_session(&self, token: &str) -> Result<bool, sqlx::Error> {
+        self.timed("delete_session", self.inner.delete_session(token)).await
+    }
+
+    async fn purge_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+        self.timed("purge_expired_sessions", self.inner.purge_expired_sessions()).await
+    }
+
+    async fn delete_user_sessions(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        self.timed("delete_user_sessions", self.inner.delete_user_sessions(user_id)).await
+    }
+
+    async fn count_active_sessions(&self) -> Result<i64, sqlx::Error> {
+        self.timed("count_active_sessions", self.inner.count_active_sessions()).await
+    }
+}
+
+#[async_trait]
+impl ApiTokenRepository for Metered {
+    async fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        scope: TokenScope,
+        expires_in: Option<Duration>,
+    ) -> Result<(String, ApiToken), sqlx::Error> {
+        self.timed(
+            "create_api_token",
+            self.inner.create_api_token(user_id, name, scope, expires_in),
+        )
+        .await
+    }
+
+    async fn list_api_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>, sqlx::Error> {
+        self.timed("list_api_tokens", self.inner.list_api_tokens(user_id)).await
+    }
+
+    async fn delete_api_token(&self, user_id: i64, token_id: i64) -> Result<bool, sqlx::Error> {
+        self.timed("delete_api_token", self.inner.delete_api_token(user_id, token_id)).await
+    }
+
+    async fn authenticate_api_token(&self, token: &str) -> Result<Option<(TokenScope, User)>, sqlx::Error> {
+        self.timed("authenticate_api_token", self.inner.authenticate_api_token(token)).await
+    }
+}
+
+#[async_trait]
+impl CalendarRepository for Metered {
+    async fn reset_calendar_feed(&self, user_id: i64) -> Result<String, sqlx::Error> {
+        self.timed("reset_calendar_feed", self.inner.reset_calendar_feed(user_id)).await
+    }
+
+    async fn get_calendar_feed(&self, user_id: i64) -> Result<Option<CalendarFeed>, sqlx::Error> {
+        self.timed("get_calendar_feed", self.inner.get_calendar_feed(user_id)).await
+    }
+
+    async fn delete_calendar_feed(&self, user_id: i64) -> Result<bool, sqlx::Error> {
+        self.timed("delete_calendar_feed", self.inner.delete_calendar_feed(user_id)).await
+    }
+
+    async fn calendar_feed_user(&self, token: &str) -> Result<Option<i64>, sqlx::Error> {
+        self.timed("calendar_feed_user", self.inner.calendar_feed_user(token)).await
+    }
+
+    async fn list_dav_resources(&self, user_id: i64) -> Result<Vec<DavResource>, sqlx::Error> {
+        self.timed("list_dav_resources", self.inner.list_dav_resources(user_id)).await
+    }
+
+    async fn create_dav_todo(
+        &self,
+        user_id: i64,
+        name: &str,
+        uid: Option<&str>,
+        title: &str,
+        completed: bool,
+    ) -> Result<Todo, sqlx::Error> {
+        self.timed(
+            "create_dav_todo",
+            self.inner.create_dav_todo(user_id, name, uid, title, completed),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl TodoRepository for Metered {
+    async fn get_user_todos(&self, user_id: i64) -> Result<Vec<Todo>, sqlx::Error> {
+        self.timed("get_user_todos", self.inner.get_user_todos(user_id)).await
+    }
+
+    async fn send_user_todos(
+        &self,
+        user_id: i64,
+        completed: Option<bool>,
+        sender: mpsc::Sender<Result<Todo, sqlx::Error>>,
+    ) {
+        // Includes the time the receiver takes to keep up
+        self.timed("send_user_todos", self.inner.send_user_todos(user_id, completed, sender))
+            .await
+    }
+
+    async fn get_user_todo_by_id(&self, user_id: i64, todo_id: i64) -> Result<Option<Todo>, sqlx::Error> {
+        self.timed("get_user_todo_by_id", self.inner.get_user_todo_by_id(user_id, todo_id))
+            .await
+    }
+
+    async fn create_user_todo(&self, user_id: i64, todo: CreateTodo) -> Result<Todo, sqlx::Error> {
+        self.timed("create_user_todo", self.inner.create_user_todo(user_id, todo)).await
+    }
+
+    async fn import_user_todos(&self, user_id: i64, todos: &[ImportedTodo]) -> Result<Vec<Todo>, sqlx::Error> {
+        self.timed("import_user_todos", self.inner.import_user_todos(user_id, todos)).await
+    }
+
+    async fn update_user_todo(
+        &self,
+        user_id: i64,
+        todo_id: i64,
+        update: UpdateTodo,
+        expected_version: Option<i64>,
+    ) -> Result<Option<Todo>, sqlx::Error> {
+        self.timed(
+            "update_user_todo",
+            self.inner.update_user_todo(user_id, todo_id, update, expected_version),
+        )
+        .await
+    }
+
+    async fn delete_user_todo(
+        &self,
+        user_id: i64,
+        todo_id: i64,
+        expected_version: Option<i64>,
+    ) -> Result<bool, sqlx::Error> {
+        self.timed(
+            "delete_user_todo",
+            self.inner.delete_user_todo(user_id, todo_id, expected_version),
+        )
+        .await
+    }
+
+    async fn count_todos_created_since(&self, since: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+        self.timed("count_todos_created_since", self.inner.count_todos_created_since(since))
+            .await
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for Metered {
+    async fn create_webhook(
+        &self,
+        user_id: i64,
+        url: &str,
+        events: &[WebhookEvent],
+    ) -> Result<(String, Webhook), sqlx::Error> {
+        self.timed("create_webhook", self.inner.create_webhook(user_id, url, events)).await
+    }
+
+    async fn list_webhooks(&self, user_id: i64) -> Result<Vec<Webhook>, sqlx::Error> {
+        self.timed("list_webhooks", self.inner.list_webhooks(user_id)).await
+    }
+
+    async fn delete_webhook(&self, user_id: i64, webhook_id: i64) -> Result<bool, sqlx::Error> {
+        self.timed("delete_webhook", self.inner.delete_webhook(user_id, webhook_id)).await
+    }
+
+    async fn list_webhook_deliveries(&self, user_id: i64, limit: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        self.timed(
+            "list_webhook_deliveries",
+            self.inner.list_webhook_deliveries(user_id, limit),
+        )
+        .await
+    }
+
+    async fn due_webhook_deliveries(&self, limit: i64) -> Result<Vec<DueDelivery>, sqlx::Error> {
+        self.timed("due_webhook_deliveries", self.inner.due_webhook_deliveries(limit)).await
+    }
+
+    async fn mark_webhook_delivered(&self, delivery_id: i64, status_code: i64) -> Result<(), sqlx::Error> {
+        self.timed(
+            "mark_webhook_delivered",
+            self.inner.mark_webhook_delivered(delivery_id, status_code),
+        )
+        .await
+    }
+
+    async fn mark_webhook_attempt_failed(
+        &self,
+        delivery_id: i64,
+        status_code: Option<i64>,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        self.timed(
+            "mark_webhook_attempt_failed",
+            self.inner
+                .mark_webhook_attempt_failed(delivery_id, status_code, error, retry_at),
+        )
+        .await
+    }
+
+    async fn purge_webhook_deliveries(&self, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        self.timed("purge_webhook_deliveries", self.inner.purge_webhook_deliveries(before))
+            .await
+    }
+}
+
+#[async_trait]
+impl Repository for Metered {
+    async fn optimize(&self) -> Result<(), sqlx::Error> {
+        self.timed("optimize", self.inner.optimize()).await
+    }
+
+    async fn backup(&self, path: &Path) -> Result<(), sqlx::Error> {
+        self.timed("backup", self.inner.backup(path)).await
+    }
+
+    async fn close(&self) {
+        self.inner.close().await;
+    }
+
+    fn pool_stats(&self) -> Vec<PoolStats> {
+        self.inner.pool_stats()
+    }
+}
@@ -0,0 +1,202 @@
+//! Data access. The rest of the app goes through [`Database`], which is
+//! backed by SQLite or PostgreSQL depending on the scheme of the database
+//! URL; both implement the traits in [`repository`].
+
+pub mod metered;
+pub mod postgres;
+pub mod repository;
+pub mod sqlite;
+
+pub use repository::{
+    ApiTokenRepository, CalendarRepository, Repository, SessionRepository, TodoRepository, UserRepository,
+    WebhookRepository,
+};
+
+use crate::config::DatabaseConfig;
+use chrono::Utc;
+use sqlx::migrate::{Migrate, Migrator};
+use sqlx::{PgPool, SqlitePool};
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// Which database a URL points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Option<Self> {
+        match url.split_once(':')?.0 {
+            "sqlite" => Some(Backend::Sqlite),
+            "postgres" | "postgresql" => Some(Backend::Postgres),
+            _ => None,
+        }
+    }
+}
+
+/// Handle to the app's storage, cheap to clone.
+#[derive(Clone)]
+pub struct Database(Arc<dyn Repository>);
+
+impl Database {
+    /// Connects to the database in `config` and brings its schema up to date.
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        Self::connect_with(config, true).await
+    }
+
+    /// Connects to the database in `config` without changing its schema,
+    /// failing unless it has exactly the migrations this binary has. For
+    /// commands run next to a server that may be another version.
+    pub async fn open(config: &DatabaseConfig) -> Result<Self, sqlx::Error> {
+        Self::connect_with(config, false).await
+    }
+
+    async fn connect_with(config: &DatabaseConfig, migrate: bool) -> Result<Self, sqlx::Error> {
+        let database = match Backend::from_url(&config.url) {
+            Some(Backend::Sqlite) => Self(Arc::new(sqlite::Sqlite::connect(config, migrate).await?)),
+            Some(Backend::Postgres) => {
+                let pool = sqlx::postgres::PgPoolOptions::new()
+                    .max_connections(config.max_connections)
+                    .connect(&config.url)
+                    .await?;
+                if migrate {
+                    postgres::MIGRATOR.run(&pool).await?;
+                } else {
+                    check_schema(&pool, &postgres::MIGRATOR).await?;
+                }
+                Self::postgres(pool)
+            }
+            None => return Err(sqlx::Error::Configuration("unsupported database URL".into())),
+        };
+
+        Ok(database)
+    }
+
+    /// A private, migrated SQLite database that lives in memory until the
+    /// last clone is dropped. Meant for tests.
+    pub async fn memory() -> Result<Self, sqlx::Error> {
+        Self::connect(&DatabaseConfig {
+            url: "sqlite::memory:".to_string(),
+            ..DatabaseConfig::default()
+        })
+        .await
+    }
+
+    /// Uses an already connected and migrated SQLite pool for both reads
+    /// and writes.
+    pub fn sqlite(pool: SqlitePool) -> Self {
+        Self(Arc::new(sqlite::Sqlite::from_pool(pool)))
+    }
+
+    /// Uses an already connected and migrated PostgreSQL pool.
+    pub fn postgres(pool: PgPool) -> Self {
+        Self(Arc::new(pool))
+    }
+
+    /// Observes how long every operation takes in `durations`, labelled by
+    /// operation.
+    pub fn metered(self, durations: prometheus::HistogramVec) -> Self {
+        Self(Arc::new(metered::Metered::new(self.0, durations)))
+    }
+}
+
+impl Deref for Database {
+    type Target = dyn Repository;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+/// How many todos `send_user_todos` reads at a time. No connection is held
+/// while a page is being sent, however slowly the receiver takes it.
+const SEND_PAGE_SIZE: i64 = 200;
+
+/// Fails unless the database behind `pool` has had exactly the migrations
+/// of `migrator` applied.
+async fn check_schema<DB>(pool: &sqlx::Pool<DB>, migrator: &Migrator) -> Result<(), sqlx::Error>
+where
+    DB: sqlx::Database,
+    DB::Connection: Migrate,
+{
+    let mut connection = pool.acquire().await?;
+    // A database that was never migrated has no history table
+    let applied = connection.list_applied_migrations().await.unwrap_or_default();
+    let known: Vec<i64> = migrator
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| migration.version)
+        .collect();
+
+    if applied.len() == known.len() && applied.iter().all(|migration| known.contains(&migration.version)) {
+        Ok(())
+    } else {
+        Err(sqlx::Error::Configuration(
+            "the database schema doesn't match this version, run `todo-leptos migrate` first".into(),
+        ))
+    }
+}
+
+fn format_timestamp(timestamp: chrono::DateTime<Utc>) -> String {
+    // Same format as SQLite's `datetime('now')`, so comparisons in SQL work
+    timestamp.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+/// Generates a new random secret for a session or API token. Only its hash
+/// is stored.
+fn generate_token() -> String {
+    use rand::RngCore;
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+pub fn hash_token(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// The name and UID a CalDAV client chose for a todo it created.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DavResource {
+    pub todo_id: i64,
+    pub name: String,
+    pub uid: String,
+}
+
+/// The state of one connection pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    /// `reader` or `writer` for SQLite, `main` for PostgreSQL.
+    pub name: &'static str,
+    /// Open connections, idle or in use.
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+}
+
+impl PoolStats {
+    fn of<DB: sqlx::Database>(name: &'static str, pool: &sqlx::Pool<DB>) -> Self {
+        Self {
+            name,
+            size: pool.size(),
+            idle: pool.num_idle(),
+            max_connections: pool.options().get_max_connections(),
+        }
+    }
+}
+
+/// A queued webhook delivery with everything needed to send it.
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct DueDelivery {
+    pub id: i64,
+    pub url: String,
+    pub secret: String,
+    pub event: String,
+    pub payload: String,
+    pub attempts: i64,
+}
@@ -0,0 +1,930 @@
+//! The PostgreSQL backend. Its queries are checked at runtime, since the
+//! compile-time checks run against the SQLite database in `DATABASE_URL`.
+//!
+//! Timestamps are stored as UTC `TIMESTAMP(0)`, so they read back in the
+//! same form as SQLite's and the two backends' output is interchangeable.
+
+use super::repository::{
+    ApiTokenRepository, CalendarRepository, Repository, SessionRepository, TodoRepository, UserRepository,
+    WebhookRepository,
+};
+use super::{generate_token, hash_token, DavResource, DueDelivery, PoolStats, SEND_PAGE_SIZE};
+use crate::calendar::ics;
+use crate::events;
+use crate::models::{
+    ApiToken, CalendarFeed, CreateTodo, ImportedTodo, LoginUser, RegisterUser, Session, Todo, TodoEvent, TokenScope,
+    UpdateTodo, User, Webhook, WebhookDelivery, WebhookEvent,
+};
+use crate::webhooks;
+use async_trait::async_trait;
+use bcrypt::{hash, verify};
+use chrono::{DateTime, Duration, NaiveDateTime, Timelike, Utc};
+use sqlx::migrate::Migrator;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+/// The PostgreSQL schema migrations built into this binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+const USER_COLUMNS: &str = "id, username, email, password_hash, is_admin, created_at, disabled_at";
+const TODO_COLUMNS: &str = "id, title, completed, created_at, updated_at, version, user_id";
+
+/// A parameter for a `TIMESTAMP(0)` column.
+fn timestamp(at: DateTime<Utc>) -> NaiveDateTime {
+    let at = at.naive_utc();
+    at.with_nanosecond(0).unwrap_or(at)
+}
+
+#[derive(FromRow)]
+struct UserRow {
+    id: i64,
+    username: String,
+    email: String,
+    password_hash: String,
+    is_admin: bool,
+    created_at: NaiveDateTime,
+    disabled_at: Option<NaiveDateTime>,
+}
+
+impl From<UserRow> for User {
+    fn from(row: UserRow) -> Self {
+        User {
+            id: row.id,
+            username: row.username,
+            email: row.email,
+            password_hash: row.password_hash,
+            is_admin: row.is_admin,
+            created_at: row.created_at.to_string(),
+            disabled_at: row.disabled_at.map(|at| at.to_string()),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct TodoRow {
+    id: i64,
+    title: String,
+    completed: bool,
+    created_at: NaiveDateTime,
+    updated_at: NaiveDateTime,
+    version: i64,
+    user_id: i64,
+}
+
+impl From<TodoRow> for Todo {
+    fn from(row: TodoRow) -> Self {
+        Todo {
+            id: row.id,
+            title: row.title,
+            completed: row.completed,
+            created_at: row.created_at.to_string(),
+            updated_at: row.updated_at.to_string(),
+            version: row.version,
+            user_id: row.user_id,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct SessionRow {
+    id: String,
+    user_id: i64,
+    created_at: NaiveDateTime,
+    expires_at: NaiveDateTime,
+    absolute_expires_at: NaiveDateTime,
+}
+
+impl From<SessionRow> for Session {
+    fn from(row: SessionRow) -> Self {
+        Session {
+            id: row.id,
+            user_id: row.user_id,
+            created_at: row.created_at.to_string(),
+            expires_at: row.expires_at.to_string(),
+            absolute_expires_at: row.absolute_expires_at.to_string(),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct ApiTokenRow {
+    id: i64,
+    name: String,
+    scope: String,
+    created_at: NaiveDateTime,
+    expires_at: Option<NaiveDateTime>,
+    last_used_at: Option<NaiveDateTime>,
+}
+
+impl From<ApiTokenRow> for ApiToken {
+    fn from(row: ApiTokenRow) -> Self {
+        ApiToken {
+            id: row.id,
+            name: row.name,
+            scope: TokenScope::parse(&row.scope).unwrap_or(TokenScope::Read),
+            created_at: row.created_at.to_string(),
+            expires_at: row.expires_at.map(|dt| dt.to_string()),
+            last_used_at: row.last_used_at.map(|dt| dt.to_string()),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct WebhookRow {
+    id: i64,
+    url: String,
+    events: String,
+    created_at: NaiveDateTime,
+}
+
+impl From<WebhookRow> for Webhook {
+    fn from(row: WebhookRow) -> Self {
+        Webhook {
+            id: row.id,
+            url: row.url,
+            events: row.events.split(',').filter_map(WebhookEvent::parse).collect(),
+            created_at: row.created_at.to_string(),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct WebhookDeliveryRow {
+    id: i64,
+    webhook_id: i64,
+    url: String,
+    event: String,
+    status: String,
+    attempts: i64,
+    last_status_code: Option<i64>,
+    last_error: Option<String>,
+    created_at: NaiveDateTime,
+    delivered_at: Option<NaiveDateTime>,
+}
+
+impl From<WebhookDeliveryRow> for WebhookDelivery {
+    fn from(row: WebhookDeliveryRow) -> Self {
+        WebhookDelivery {
+            id: row.id,
+            webhook_id: row.webhook_id,
+            url: row.url,
+            event: row.event,
+            status: row.status,
+            attempts: row.attempts,
+            last_status_code: row.last_status_code,
+            last_error: row.last_error,
+            created_at: row.created_at.to_string(),
+            delivered_at: row.delivered_at.map(|dt| dt.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgPool {
+    async fn create_user(&self, user_data: RegisterUser, bcrypt_cost: u32) -> Result<User, sqlx::Error> {
+        let password_hash = hash(&user_data.password, bcrypt_cost)
+            .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+        let row: UserRow = sqlx::query_as(&format!(
+            "INSERT INTO users (username, email, password_hash) VALUES ($1, $2, $3) RETURNING {USER_COLUMNS}"
+        ))
+        .bind(&user_data.username)
+        .bind(&user_data.email)
+        .bind(&password_hash)
+        .fetch_one(self)
+        .await?;
+
+        Ok(row.into())
+    }
+
+    async fn authenticate_user(&self, login_data: LoginUser) -> Result<Option<User>, sqlx::Error> {
+        let row: Option<UserRow> = sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users WHERE username = $1 AND disabled_at IS NULL"))
+            .bind(&login_data.username)
+            .fetch_optional(self)
+            .await?;
+
+        let Some(user) = row.map(User::from) else {
+            return Ok(None);
+        };
+        let is_valid = verify(&login_data.password, &user.password_hash)
+            .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+        Ok(is_valid.then_some(user))
+    }
+
+    async fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, sqlx::Error> {
+        let row: Option<UserRow> = sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users WHERE id = $1"))
+            .bind(user_id)
+            .fetch_optional(self)
+            .await?;
+
+        Ok(row.map(User::from))
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
+        let row: Option<UserRow> = sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users WHERE username = $1"))
+            .bind(username)
+            .fetch_optional(self)
+            .await?;
+
+        Ok(row.map(User::from))
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        let rows: Vec<UserRow> = sqlx::query_as(&format!("SELECT {USER_COLUMNS} FROM users ORDER BY id"))
+            .fetch_all(self)
+            .await?;
+
+        Ok(rows.into_iter().map(User::from).collect())
+    }
+
+    async fn set_user_disabled(&self, user_id: i64, disabled: bool) -> Result<bool, sqlx::Error> {
+        // Disabling an already disabled account keeps the original time
+        let rows_affected = sqlx::query(
+            "UPDATE users SET disabled_at = CASE WHEN $1 THEN COALESCE(disabled_at, utc_now()) END WHERE id = $2",
+        )
+        .bind(disabled)
+        .bind(user_id)
+        .execute(self)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn set_user_admin(&self, user_id: i64, admin: bool) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query("UPDATE users SET is_admin = $1 WHERE id = $2")
+            .bind(admin)
+            .bind(user_id)
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn set_user_password(&self, user_id: i64, password: &str, bcrypt_cost: u32) -> Result<bool, sqlx::Error> {
+        let password_hash = hash(password, bcrypt_cost).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+        let rows_affected = sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+            .bind(&password_hash)
+            .bind(user_id)
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn user_exists(&self, username: &str, email: &str) -> Result<bool, sqlx::Error> {
+        sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE username = $1 OR email = $2)")
+            .bind(username)
+            .bind(email)
+            .fetch_one(self)
+            .await
+    }
+}
+
+#[async_trait]
+impl SessionRepository for PgPool {
+    async fn create_session(
+        &self,
+        user_id: i64,
+        idle_timeout: Duration,
+        absolute_timeout: Duration,
+    ) -> Result<(String, Session), sqlx::Error> {
+        let token = generate_token();
+        let now = Utc::now();
+
+        let row: SessionRow = sqlx::query_as(
+            "INSERT INTO sessions (id, user_id, expires_at, absolute_expires_at) VALUES ($1, $2, $3, $4)
+             RETURNING id, user_id, created_at, expires_at, absolute_expires_at",
+        )
+        .bind(hash_token(&token))
+        .bind(user_id)
+        .bind(timestamp(now + idle_timeout.min(absolute_timeout)))
+        .bind(timestamp(now + absolute_timeout))
+        .fetch_one(self)
+        .await?;
+
+        Ok((token, row.into()))
+    }
+
+    async fn get_session(&self, token: &str) -> Result<Option<(Session, User)>, sqlx::Error> {
+        let row: Option<(String, i64, NaiveDateTime, NaiveDateTime, NaiveDateTime, String, String, String, bool, NaiveDateTime)> =
+            sqlx::query_as(
+                "SELECT s.id, s.user_id, s.created_at, s.expires_at, s.absolute_expires_at, u.username, u.email, u.password_hash, u.is_admin, u.created_at
+                 FROM sessions s
+                 JOIN users u ON s.user_id = u.id
+                 WHERE s.id = $1 AND s.expires_at > utc_now() AND s.absolute_expires_at > utc_now() AND u.disabled_at IS NULL",
+            )
+            .bind(hash_token(token))
+            .fetch_optional(self)
+            .await?;
+
+        Ok(row.map(
+            |(id, user_id, created_at, expires_at, absolute_expires_at, username, email, password_hash, is_admin, user_created_at)| {
+                let session = SessionRow {
+                    id,
+                    user_id,
+                    created_at,
+                    expires_at,
+                    absolute_expires_at,
+                };
+                let user = UserRow {
+                    id: user_id,
+                    username,
+                    email,
+                    password_hash,
+                    is_admin,
+                    created_at: user_created_at,
+                    disabled_at: None,
+                };
+                (session.into(), user.into())
+            },
+        ))
+    }
+
+    async fn refresh_session(&self, session_id: &str, idle_timeout: Duration) -> Result<bool, sqlx::Error> {
+        let now = Utc::now();
+
+        let rows_affected = sqlx::query(
+            "UPDATE sessions SET expires_at = LEAST($1, absolute_expires_at) WHERE id = $2 AND expires_at < $3",
+        )
+        .bind(timestamp(now + idle_timeout))
+        .bind(session_id)
+        .bind(timestamp(now + idle_timeout - idle_timeout / 10))
+        .execute(self)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn delete_session(&self, token: &str) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query("DELETE FROM sessions WHERE id = $1")
+            .bind(hash_token(token))
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn purge_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+        let rows_affected =
+            sqlx::query("DELETE FROM sessions WHERE expires_at <= utc_now() OR absolute_expires_at <= utc_now()")
+                .execute(self)
+                .await?
+                .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_user_sessions(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        let rows_affected = sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+            .bind(user_id)
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn count_active_sessions(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM sessions WHERE expires_at > utc_now() AND absolute_expires_at > utc_now()")
+            .fetch_one(self)
+            .await
+    }
+}
+
+#[async_trait]
+impl ApiTokenRepository for PgPool {
+    async fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        scope: TokenScope,
+        expires_in: Option<Duration>,
+    ) -> Result<(String, ApiToken), sqlx::Error> {
+        let token = format!("tdl_{}", generate_token());
+
+        let row: ApiTokenRow = sqlx::query_as(
+            "INSERT INTO api_tokens (user_id, name, token_hash, scope, expires_at) VALUES ($1, $2, $3, $4, $5)
+             RETURNING id, name, scope, created_at, expires_at, last_used_at",
+        )
+        .bind(user_id)
+        .bind(name)
+        .bind(hash_token(&token))
+        .bind(scope.as_str())
+        .bind(expires_in.map(|ttl| timestamp(Utc::now() + ttl)))
+        .fetch_one(self)
+        .await?;
+
+        Ok((token, row.into()))
+    }
+
+    async fn list_api_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>, sqlx::Error> {
+        let rows: Vec<ApiTokenRow> = sqlx::query_as(
+            "SELECT id, name, scope, created_at, expires_at, last_used_at FROM api_tokens WHERE user_id = $1 ORDER BY created_at DESC, id DESC",
+        )
+        .bind(user_id)
+        .fetch_all(self)
+        .await?;
+
+        Ok(rows.into_iter().map(ApiToken::from).collect())
+    }
+
+    async fn delete_api_token(&self, user_id: i64, token_id: i64) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query("DELETE FROM api_tokens WHERE id = $1 AND user_id = $2")
+            .bind(token_id)
+            .bind(user_id)
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn authenticate_api_token(&self, token: &str) -> Result<Option<(TokenScope, User)>, sqlx::Error> {
+        let row: Option<(i64, String, i64, String, String, String, bool, NaiveDateTime)> = sqlx::query_as(
+            "SELECT t.id, t.scope, u.id, u.username, u.email, u.password_hash, u.is_admin, u.created_at
+             FROM api_tokens t
+             JOIN users u ON t.user_id = u.id
+             WHERE t.token_hash = $1 AND (t.expires_at IS NULL OR t.expires_at > utc_now()) AND u.disabled_at IS NULL",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(self)
+        .await?;
+
+        let Some((token_id, scope, id, username, email, password_hash, is_admin, created_at)) = row else {
+            return Ok(None);
+        };
+
+        // Only record usage once a minute to keep reads cheap
+        sqlx::query(
+            "UPDATE api_tokens SET last_used_at = utc_now() WHERE id = $1 AND (last_used_at IS NULL OR last_used_at < utc_now() - INTERVAL '1 minute')",
+        )
+        .bind(token_id)
+        .execute(self)
+        .await?;
+
+        let scope = TokenScope::parse(&scope).unwrap_or(TokenScope::Read);
+        let user = UserRow {
+            id,
+            username,
+            email,
+            password_hash,
+            is_admin,
+            created_at,
+            disabled_at: None,
+        };
+
+        Ok(Some((scope, user.into())))
+    }
+}
+
+#[async_trait]
+impl CalendarRepository for PgPool {
+    async fn reset_calendar_feed(&self, user_id: i64) -> Result<String, sqlx::Error> {
+        let token = format!("cal_{}", generate_token());
+
+        sqlx::query(
+            "INSERT INTO calendar_feeds (user_id, token_hash) VALUES ($1, $2)
+             ON CONFLICT (user_id) DO UPDATE SET token_hash = excluded.token_hash, created_at = utc_now()",
+        )
+        .bind(user_id)
+        .bind(hash_token(&token))
+        .execute(self)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn get_calendar_feed(&self, user_id: i64) -> Result<Option<CalendarFeed>, sqlx::Error> {
+        let created_at: Option<NaiveDateTime> =
+            sqlx::query_scalar("SELECT created_at FROM calendar_feeds WHERE user_id = $1")
+                .bind(user_id)
+                .fetch_optional(self)
+                .await?;
+
+        Ok(created_at.map(|created_at| CalendarFeed {
+            created_at: created_at.to_string(),
+        }))
+    }
+
+    async fn delete_calendar_feed(&self, user_id: i64) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query("DELETE FROM calendar_feeds WHERE user_id = $1")
+            .bind(user_id)
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn calendar_feed_user(&self, token: &str) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT f.user_id FROM calendar_feeds f
+             JOIN users u ON f.user_id = u.id
+             WHERE f.token_hash = $1 AND u.disabled_at IS NULL",
+        )
+        .bind(hash_token(token))
+        .fetch_optional(self)
+        .await
+    }
+
+    async fn list_dav_resources(&self, user_id: i64) -> Result<Vec<DavResource>, sqlx::Error> {
+        sqlx::query_as("SELECT todo_id, name, uid FROM caldav_resources WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_all(self)
+            .await
+    }
+
+    async fn create_dav_todo(
+        &self,
+        user_id: i64,
+        name: &str,
+        uid: Option<&str>,
+        title: &str,
+        completed: bool,
+    ) -> Result<Todo, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let todo = insert_user_todo(&mut tx, user_id, title, completed).await?;
+        let uid = uid.map_or_else(|| ics::uid(&todo), str::to_string);
+        sqlx::query("INSERT INTO caldav_resources (todo_id, user_id, name, uid) VALUES ($1, $2, $3, $4)")
+            .bind(todo.id)
+            .bind(user_id)
+            .bind(name)
+            .bind(&uid)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        events::publish(user_id, TodoEvent::Created { todo: todo.clone() });
+        Ok(todo)
+    }
+}
+
+/// Queues a delivery of `event` for `todo` to each of the user's webhooks
+/// subscribed to it, as part of the transaction that made the change.
+async fn enqueue_webhook_deliveries(
+    tx: &mut PgConnection,
+    user_id: i64,
+    event: WebhookEvent,
+    todo: &Todo,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload)
+         SELECT id, $1, $2 FROM webhooks
+         WHERE user_id = $3 AND position(',' || $1 || ',' IN ',' || events || ',') > 0",
+    )
+    .bind(event.as_str())
+    .bind(webhooks::delivery::payload(event, todo))
+    .bind(user_id)
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Inserts a todo and queues its `todo.created` webhooks, as part of a
+/// caller's transaction.
+async fn insert_user_todo(
+    tx: &mut PgConnection,
+    user_id: i64,
+    title: &str,
+    completed: bool,
+) -> Result<Todo, sqlx::Error> {
+    let row: TodoRow = sqlx::query_as(&format!(
+        "INSERT INTO todos (title, completed, user_id) VALUES ($1, $2, $3) RETURNING {TODO_COLUMNS}"
+    ))
+    .bind(title)
+    .bind(completed)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let todo = Todo::from(row);
+    enqueue_webhook_deliveries(tx, user_id, WebhookEvent::TodoCreated, &todo).await?;
+    Ok(todo)
+}
+
+#[async_trait]
+impl TodoRepository for PgPool {
+    async fn get_user_todos(&self, user_id: i64) -> Result<Vec<Todo>, sqlx::Error> {
+        let rows: Vec<TodoRow> = sqlx::query_as(&format!(
+            "SELECT {TODO_COLUMNS} FROM todos WHERE user_id = $1 ORDER BY created_at DESC, id DESC"
+        ))
+        .bind(user_id)
+        .fetch_all(self)
+        .await?;
+
+        Ok(rows.into_iter().map(Todo::from).collect())
+    }
+
+    async fn send_user_todos(
+        &self,
+        user_id: i64,
+        completed: Option<bool>,
+        sender: mpsc::Sender<Result<Todo, sqlx::Error>>,
+    ) {
+        let query = format!(
+            "SELECT {TODO_COLUMNS} FROM todos
+             WHERE user_id = $1 AND ($2::BOOLEAN IS NULL OR completed = $2)
+               AND ($4::BIGINT IS NULL OR (created_at, id) > ($3, $4))
+             ORDER BY created_at, id
+             LIMIT $5"
+        );
+        // The last todo sent, to read the next page after
+        let mut after: Option<(NaiveDateTime, i64)> = None;
+        loop {
+            let (after_created_at, after_id) = after.unzip();
+            let page = sqlx::query_as::<_, TodoRow>(&query)
+                .bind(user_id)
+                .bind(completed)
+                .bind(after_created_at)
+                .bind(after_id)
+                .bind(SEND_PAGE_SIZE)
+                .fetch_all(self)
+                .await;
+            let page = match page {
+                Ok(page) => page,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+
+            let last_page = (page.len() as i64) < SEND_PAGE_SIZE;
+            for row in page {
+                after = Some((row.created_at, row.id));
+                if sender.send(Ok(Todo::from(row))).await.is_err() {
+                    return;
+                }
+            }
+            if last_page {
+                return;
+            }
+        }
+    }
+
+    async fn get_user_todo_by_id(&self, user_id: i64, todo_id: i64) -> Result<Option<Todo>, sqlx::Error> {
+        let row: Option<TodoRow> =
+            sqlx::query_as(&format!("SELECT {TODO_COLUMNS} FROM todos WHERE user_id = $1 AND id = $2"))
+                .bind(user_id)
+                .bind(todo_id)
+                .fetch_optional(self)
+                .await?;
+
+        Ok(row.map(Todo::from))
+    }
+
+    async fn create_user_todo(&self, user_id: i64, todo: CreateTodo) -> Result<Todo, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let todo = insert_user_todo(&mut tx, user_id, &todo.title, false).await?;
+        tx.commit().await?;
+
+        events::publish(user_id, TodoEvent::Created { todo: todo.clone() });
+        Ok(todo)
+    }
+
+    async fn import_user_todos(&self, user_id: i64, todos: &[ImportedTodo]) -> Result<Vec<Todo>, sqlx::Error> {
+        let mut tx = self.begin().await?;
+        let mut created = Vec::with_capacity(todos.len());
+        for todo in todos {
+            created.push(insert_user_todo(&mut tx, user_id, &todo.title, todo.completed).await?);
+        }
+        tx.commit().await?;
+
+        for todo in &created {
+            events::publish(user_id, TodoEvent::Created { todo: todo.clone() });
+        }
+        Ok(created)
+    }
+
+    async fn update_user_todo(
+        &self,
+        user_id: i64,
+        todo_id: i64,
+        update: UpdateTodo,
+        expected_version: Option<i64>,
+    ) -> Result<Option<Todo>, sqlx::Error> {
+        let mut tx = self.begin().await?;
+
+        // Locks the row, so the completion check below can't race
+        let was_completed: Option<bool> =
+            sqlx::query_scalar("SELECT completed FROM todos WHERE id = $1 AND user_id = $2 FOR UPDATE")
+                .bind(todo_id)
+                .bind(user_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        let row: Option<TodoRow> = sqlx::query_as(&format!(
+            "UPDATE todos SET title = COALESCE($1, title), completed = COALESCE($2, completed), updated_at = utc_now(), version = version + 1
+             WHERE id = $3 AND user_id = $4 AND ($5::BIGINT IS NULL OR version = $5)
+             RETURNING {TODO_COLUMNS}"
+        ))
+        .bind(update.title)
+        .bind(update.completed)
+        .bind(todo_id)
+        .bind(user_id)
+        .bind(expected_version)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(todo) = row.map(Todo::from) else {
+            return Ok(None);
+        };
+        if todo.completed && was_completed == Some(false) {
+            enqueue_webhook_deliveries(&mut tx, user_id, WebhookEvent::TodoCompleted, &todo).await?;
+        }
+        tx.commit().await?;
+
+        events::publish(user_id, TodoEvent::Updated { todo: todo.clone() });
+        Ok(Some(todo))
+    }
+
+    async fn delete_user_todo(
+        &self,
+        user_id: i64,
+        todo_id: i64,
+        expected_version: Option<i64>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.begin().await?;
+
+        let row: Option<TodoRow> = sqlx::query_as(&format!(
+            "DELETE FROM todos WHERE id = $1 AND user_id = $2 AND ($3::BIGINT IS NULL OR version = $3)
+             RETURNING {TODO_COLUMNS}"
+        ))
+        .bind(todo_id)
+        .bind(user_id)
+        .bind(expected_version)
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(todo) = row.map(Todo::from) else {
+            return Ok(false);
+        };
+        enqueue_webhook_deliveries(&mut tx, user_id, WebhookEvent::TodoDeleted, &todo).await?;
+        tx.commit().await?;
+
+        events::publish(user_id, TodoEvent::Deleted { id: todo_id });
+        Ok(true)
+    }
+
+    async fn count_todos_created_since(&self, since: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM todos WHERE created_at >= $1")
+            .bind(timestamp(since))
+            .fetch_one(self)
+            .await
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for PgPool {
+    async fn create_webhook(
+        &self,
+        user_id: i64,
+        url: &str,
+        events: &[WebhookEvent],
+    ) -> Result<(String, Webhook), sqlx::Error> {
+        let secret = format!("whsec_{}", generate_token());
+        let events_str = events
+            .iter()
+            .map(WebhookEvent::as_str)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let row: WebhookRow = sqlx::query_as(
+            "INSERT INTO webhooks (user_id, url, secret, events) VALUES ($1, $2, $3, $4) RETURNING id, url, events, created_at",
+        )
+        .bind(user_id)
+        .bind(url)
+        .bind(&secret)
+        .bind(events_str)
+        .fetch_one(self)
+        .await?;
+
+        Ok((secret, row.into()))
+    }
+
+    async fn list_webhooks(&self, user_id: i64) -> Result<Vec<Webhook>, sqlx::Error> {
+        let rows: Vec<WebhookRow> = sqlx::query_as(
+            "SELECT id, url, events, created_at FROM webhooks WHERE user_id = $1 ORDER BY created_at DESC, id DESC",
+        )
+        .bind(user_id)
+        .fetch_all(self)
+        .await?;
+
+        Ok(rows.into_iter().map(Webhook::from).collect())
+    }
+
+    async fn delete_webhook(&self, user_id: i64, webhook_id: i64) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query("DELETE FROM webhooks WHERE id = $1 AND user_id = $2")
+            .bind(webhook_id)
+            .bind(user_id)
+            .execute(self)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn list_webhook_deliveries(&self, user_id: i64, limit: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        let rows: Vec<WebhookDeliveryRow> = sqlx::query_as(
+            "SELECT d.id, d.webhook_id, w.url, d.event, d.status, d.attempts, d.last_status_code, d.last_error, d.created_at, d.delivered_at
+             FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+             WHERE w.user_id = $1 ORDER BY d.id DESC LIMIT $2",
+        )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(self)
+        .await?;
+
+        Ok(rows.into_iter().map(WebhookDelivery::from).collect())
+    }
+
+    async fn due_webhook_deliveries(&self, limit: i64) -> Result<Vec<DueDelivery>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT d.id, w.url, w.secret, d.event, d.payload, d.attempts
+             FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+             WHERE d.status = 'pending' AND d.next_attempt_at <= utc_now()
+             ORDER BY d.id LIMIT $1",
+        )
+        .bind(limit)
+        .fetch_all(self)
+        .await
+    }
+
+    async fn mark_webhook_delivered(&self, delivery_id: i64, status_code: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE webhook_deliveries SET status = 'delivered', attempts = attempts + 1, last_status_code = $1, last_error = NULL, delivered_at = utc_now() WHERE id = $2",
+        )
+        .bind(status_code)
+        .bind(delivery_id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_webhook_attempt_failed(
+        &self,
+        delivery_id: i64,
+        status_code: Option<i64>,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        let status = if retry_at.is_some() { "pending" } else { "failed" };
+
+        sqlx::query(
+            "UPDATE webhook_deliveries SET status = $1, attempts = attempts + 1, last_status_code = $2, last_error = $3, next_attempt_at = COALESCE($4, next_attempt_at) WHERE id = $5",
+        )
+        .bind(status)
+        .bind(status_code)
+        .bind(error)
+        .bind(retry_at.map(timestamp))
+        .bind(delivery_id)
+        .execute(self)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn purge_webhook_deliveries(&self, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let rows_affected = sqlx::query(
+            "DELETE FROM webhook_deliveries WHERE status IN ('delivered', 'failed') AND created_at < $1",
+        )
+        .bind(timestamp(before))
+        .execute(self)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait]
+impl Repository for PgPool {
+    async fn optimize(&self) -> Result<(), sqlx::Error> {
+        // Autovacuum does the rest
+        sqlx::query("ANALYZE").execute(self).await?;
+        Ok(())
+    }
+
+    async fn backup(&self, _path: &Path) -> Result<(), sqlx::Error> {
+        Err(sqlx::Error::Configuration(
+            "online backups are only supported for SQLite, use pg_dump instead".into(),
+        ))
+    }
+
+    async fn close(&self) {
+        PgPool::close(self).await;
+    }
+
+    fn pool_stats(&self) -> Vec<PoolStats> {
+        vec![PoolStats::of("main", self)]
+    }
+}
@@ -0,0 +1,233 @@
+//! The storage operations the app needs, one trait per area. Each backend
+//! implements them all; see [`super::sqlite`] and [`super::postgres`].
+//!
+//! Operations that change todos also publish their [`crate::events`] and
+//! queue their webhook deliveries, so every backend behaves the same to
+//! the rest of the app.
+
+use super::{DavResource, DueDelivery, PoolStats};
+use crate::models::{
+    ApiToken, CalendarFeed, CreateTodo, ImportedTodo, LoginUser, RegisterUser, Session, Todo, TokenScope, UpdateTodo,
+    User, Webhook, WebhookDelivery, WebhookEvent,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+#[async_trait]
+pub trait UserRepository {
+    /// Creates an account without administrator rights.
+    async fn create_user(&self, user_data: RegisterUser, bcrypt_cost: u32) -> Result<User, sqlx::Error>;
+
+    /// The user with these credentials, if they are right.
+    async fn authenticate_user(&self, login_data: LoginUser) -> Result<Option<User>, sqlx::Error>;
+
+    async fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, sqlx::Error>;
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error>;
+
+    /// All accounts, oldest first, including disabled ones.
+    async fn list_users(&self) -> Result<Vec<User>, sqlx::Error>;
+
+    /// Disables or re-enables an account. Disabled accounts can't sign in,
+    /// and their sessions, API tokens and calendar feed stop working.
+    async fn set_user_disabled(&self, user_id: i64, disabled: bool) -> Result<bool, sqlx::Error>;
+
+    /// Grants or revokes administrator rights. New accounts have none.
+    async fn set_user_admin(&self, user_id: i64, admin: bool) -> Result<bool, sqlx::Error>;
+
+    async fn set_user_password(&self, user_id: i64, password: &str, bcrypt_cost: u32) -> Result<bool, sqlx::Error>;
+
+    /// Whether an account already has this username or email.
+    async fn user_exists(&self, username: &str, email: &str) -> Result<bool, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait SessionRepository {
+    /// Starts a session, returning the token for the cookie with it.
+    async fn create_session(
+        &self,
+        user_id: i64,
+        idle_timeout: Duration,
+        absolute_timeout: Duration,
+    ) -> Result<(String, Session), sqlx::Error>;
+
+    /// Looks up a live session by the token from the session cookie.
+    async fn get_session(&self, token: &str) -> Result<Option<(Session, User)>, sqlx::Error>;
+
+    /// Pushes the sliding expiry of a session forward, capped at its absolute
+    /// expiry. Only writes when at least a tenth of the idle timeout has
+    /// passed since the last refresh, so busy sessions don't write on every
+    /// request.
+    async fn refresh_session(&self, session_id: &str, idle_timeout: Duration) -> Result<bool, sqlx::Error>;
+
+    async fn delete_session(&self, token: &str) -> Result<bool, sqlx::Error>;
+
+    /// Deletes sessions past their idle or absolute expiry.
+    async fn purge_expired_sessions(&self) -> Result<u64, sqlx::Error>;
+
+    /// Signs a user out everywhere, e.g. after a password change.
+    async fn delete_user_sessions(&self, user_id: i64) -> Result<u64, sqlx::Error>;
+
+    /// Sessions that haven't expired yet, across all users.
+    async fn count_active_sessions(&self) -> Result<i64, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ApiTokenRepository {
+    /// Creates a token, returning its secret along with it.
+    async fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        scope: TokenScope,
+        expires_in: Option<Duration>,
+    ) -> Result<(String, ApiToken), sqlx::Error>;
+
+    async fn list_api_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>, sqlx::Error>;
+
+    async fn delete_api_token(&self, user_id: i64, token_id: i64) -> Result<bool, sqlx::Error>;
+
+    /// Looks up an unexpired API token and its owner, recording the use.
+    async fn authenticate_api_token(&self, token: &str) -> Result<Option<(TokenScope, User)>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait CalendarRepository {
+    /// Creates the user's calendar feed token, replacing any previous one.
+    async fn reset_calendar_feed(&self, user_id: i64) -> Result<String, sqlx::Error>;
+
+    async fn get_calendar_feed(&self, user_id: i64) -> Result<Option<CalendarFeed>, sqlx::Error>;
+
+    async fn delete_calendar_feed(&self, user_id: i64) -> Result<bool, sqlx::Error>;
+
+    /// The user a calendar feed token belongs to.
+    async fn calendar_feed_user(&self, token: &str) -> Result<Option<i64>, sqlx::Error>;
+
+    /// Client-chosen names of the user's todos created over CalDAV.
+    async fn list_dav_resources(&self, user_id: i64) -> Result<Vec<DavResource>, sqlx::Error>;
+
+    /// Creates a todo a CalDAV client PUT to `name`, remembering the name
+    /// and the client's UID (or [`ics::uid`](crate::calendar::ics::uid)
+    /// without one). Creates both or, if either insert fails, neither.
+    async fn create_dav_todo(
+        &self,
+        user_id: i64,
+        name: &str,
+        uid: Option<&str>,
+        title: &str,
+        completed: bool,
+    ) -> Result<Todo, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait TodoRepository {
+    /// The user's todos, newest first.
+    async fn get_user_todos(&self, user_id: i64) -> Result<Vec<Todo>, sqlx::Error>;
+
+    /// Sends the user's todos, oldest first and optionally only those with
+    /// the given completion state, to `sender` as they are read. Reads them
+    /// a page at a time, so a slow receiver doesn't keep a connection or a
+    /// read transaction open. Stops early once the receiving end is dropped.
+    async fn send_user_todos(
+        &self,
+        user_id: i64,
+        completed: Option<bool>,
+        sender: mpsc::Sender<Result<Todo, sqlx::Error>>,
+    );
+
+    async fn get_user_todo_by_id(&self, user_id: i64, todo_id: i64) -> Result<Option<Todo>, sqlx::Error>;
+
+    async fn create_user_todo(&self, user_id: i64, todo: CreateTodo) -> Result<Todo, sqlx::Error>;
+
+    /// Creates all of `todos` or, if any insert fails, none of them.
+    async fn import_user_todos(&self, user_id: i64, todos: &[ImportedTodo]) -> Result<Vec<Todo>, sqlx::Error>;
+
+    /// Applies `update` to a todo and bumps its version. When
+    /// `expected_version` is given the update only happens if the todo is
+    /// still at that version, so callers can detect concurrent edits; `None`
+    /// is returned in either case of no row being updated.
+    async fn update_user_todo(
+        &self,
+        user_id: i64,
+        todo_id: i64,
+        update: UpdateTodo,
+        expected_version: Option<i64>,
+    ) -> Result<Option<Todo>, sqlx::Error>;
+
+    /// Deletes a todo, guarded by `expected_version` like
+    /// [`TodoRepository::update_user_todo`].
+    async fn delete_user_todo(
+        &self,
+        user_id: i64,
+        todo_id: i64,
+        expected_version: Option<i64>,
+    ) -> Result<bool, sqlx::Error>;
+
+    /// Todos created at or after `since`, across all users.
+    async fn count_todos_created_since(&self, since: DateTime<Utc>) -> Result<i64, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait WebhookRepository {
+    /// Registers a webhook, returning its signing secret along with it.
+    async fn create_webhook(
+        &self,
+        user_id: i64,
+        url: &str,
+        events: &[WebhookEvent],
+    ) -> Result<(String, Webhook), sqlx::Error>;
+
+    async fn list_webhooks(&self, user_id: i64) -> Result<Vec<Webhook>, sqlx::Error>;
+
+    async fn delete_webhook(&self, user_id: i64, webhook_id: i64) -> Result<bool, sqlx::Error>;
+
+    /// The most recent deliveries to any of the user's webhooks.
+    async fn list_webhook_deliveries(&self, user_id: i64, limit: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error>;
+
+    /// Pending deliveries whose next attempt is due, oldest first.
+    async fn due_webhook_deliveries(&self, limit: i64) -> Result<Vec<DueDelivery>, sqlx::Error>;
+
+    async fn mark_webhook_delivered(&self, delivery_id: i64, status_code: i64) -> Result<(), sqlx::Error>;
+
+    /// Records a failed attempt. With a `retry_at` the delivery is tried again
+    /// then; without one it's given up on.
+    async fn mark_webhook_attempt_failed(
+        &self,
+        delivery_id: i64,
+        status_code: Option<i64>,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error>;
+
+    /// Deletes delivered and failed deliveries queued before `before`,
+    /// returning how many there were. Pending ones are kept.
+    async fn purge_webhook_deliveries(&self, before: DateTime<Utc>) -> Result<u64, sqlx::Error>;
+}
+
+/// Everything a backend provides.
+#[async_trait]
+pub trait Repository:
+    UserRepository
+    + SessionRepository
+    + ApiTokenRepository
+    + CalendarRepository
+    + TodoRepository
+    + WebhookRepository
+    + Send
+    + Sync
+{
+    /// Routine upkeep, run daily.
+    async fn optimize(&self) -> Result<(), sqlx::Error>;
+
+    /// Writes a consistent copy of the database to `path`, which must not
+    /// exist yet.
+    async fn backup(&self, path: &Path) -> Result<(), sqlx::Error>;
+
+    /// Closes the connections once in-flight queries finish.
+    async fn close(&self);
+
+    /// How many connections each pool holds and how many of them are idle.
+    fn pool_stats(&self) -> Vec<PoolStats>;
+}
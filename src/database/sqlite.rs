@@ -0,0 +1,1109 @@
+//! The SQLite backend, with queries checked at compile time against the
+//! database in `DATABASE_URL`.
+
+use super::repository::{
+    ApiTokenRepository, CalendarRepository, Repository, SessionRepository, TodoRepository, UserRepository,
+    WebhookRepository,
+};
+use super::{check_schema, format_timestamp, generate_token, hash_token, DavResource, DueDelivery, PoolStats, SEND_PAGE_SIZE};
+use crate::calendar::ics;
+use crate::config::DatabaseConfig;
+use crate::events;
+use crate::models::{
+    ApiToken, CalendarFeed, CreateTodo, ImportedTodo, LoginUser, RegisterUser, Session, Todo, TodoEvent, TokenScope,
+    UpdateTodo, User, Webhook, WebhookDelivery, WebhookEvent,
+};
+use crate::webhooks;
+use async_trait::async_trait;
+use bcrypt::{hash, verify};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::{SqliteConnection, SqlitePool};
+use sqlx::migrate::Migrator;
+use std::path::Path;
+use std::str::FromStr;
+use tokio::sync::mpsc;
+
+/// The SQLite schema migrations built into this binary.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// The SQLite backend. Reads go through a pool of read-only connections;
+/// writes share a single connection, so they queue up in the app instead of
+/// failing with `SQLITE_BUSY` while another write holds the lock.
+#[derive(Clone)]
+pub struct Sqlite {
+    reader: SqlitePool,
+    writer: SqlitePool,
+}
+
+impl Sqlite {
+    /// Opens the database at `config.url`. With `migrate` it's created if
+    /// it's missing and its schema brought up to date; without, it has to
+    /// exist and be up to date already. In-memory databases are always
+    /// migrated, as they start out empty.
+    pub async fn connect(config: &DatabaseConfig, migrate: bool) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::from_str(&config.url)?
+            .foreign_keys(true)
+            .busy_timeout(config.busy_timeout);
+
+        if is_in_memory(&config.url) {
+            // Every connection would get a database of its own
+            let pool = SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect_with(options)
+                .await?;
+            MIGRATOR.run(&pool).await?;
+            return Ok(Self::from_pool(pool));
+        }
+
+        let path = options.clone().get_filename();
+        if !migrate && !path.exists() {
+            return Err(sqlx::Error::Configuration(
+                format!("{} doesn't exist, run `todo-leptos migrate` to create it", path.display()).into(),
+            ));
+        }
+
+        // WAL lets readers carry on while a write is in progress, and with it
+        // NORMAL is durable against application crashes, only losing the
+        // last commits on power loss.
+        let writer = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(
+                options
+                    .clone()
+                    .create_if_missing(migrate)
+                    .journal_mode(SqliteJournalMode::Wal)
+                    .synchronous(SqliteSynchronous::Normal),
+            )
+            .await?;
+        if migrate {
+            MIGRATOR.run(&writer).await?;
+        } else {
+            check_schema(&writer, &MIGRATOR).await?;
+        }
+
+        let reader = SqlitePoolOptions::new()
+            .max_connections(config.max_connections)
+            .connect_with(options.read_only(true))
+            .await?;
+
+        Ok(Self { reader, writer })
+    }
+
+    /// Reads and writes through the same, already migrated pool.
+    pub fn from_pool(pool: SqlitePool) -> Self {
+        Self {
+            reader: pool.clone(),
+            writer: pool,
+        }
+    }
+}
+
+fn is_in_memory(url: &str) -> bool {
+    url.contains(":memory:") || url.contains("mode=memory")
+}
+
+#[async_trait]
+impl UserRepository for Sqlite {
+    async fn create_user(&self, user_data: RegisterUser, bcrypt_cost: u32) -> Result<User, sqlx::Error> {
+        let password_hash = hash(&user_data.password, bcrypt_cost)
+            .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+        let row = sqlx::query!(
+            "INSERT INTO users (username, email, password_hash) VALUES (?, ?, ?) RETURNING id, username, email, password_hash, is_admin, created_at, disabled_at",
+            user_data.username,
+            user_data.email,
+            password_hash
+        )
+        .fetch_one(&self.writer)
+        .await?;
+
+        Ok(User {
+            id: row.id,
+            username: row.username,
+            email: row.email,
+            password_hash: row.password_hash,
+            is_admin: row.is_admin,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            disabled_at: row.disabled_at.map(|dt| dt.to_string()),
+        })
+    }
+
+    async fn authenticate_user(&self, login_data: LoginUser) -> Result<Option<User>, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT id, username, email, password_hash, is_admin, created_at FROM users WHERE username = ? AND disabled_at IS NULL",
+            login_data.username
+        )
+        .fetch_optional(&self.reader)
+        .await?;
+
+        let user = row.map(|row| User {
+            id: row.id.unwrap_or(0),
+            username: row.username,
+            email: row.email,
+            password_hash: row.password_hash,
+            is_admin: row.is_admin,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            disabled_at: None,
+        });
+
+        if let Some(user) = user {
+            let is_valid = verify(&login_data.password, &user.password_hash)
+                .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+            if is_valid {
+                Ok(Some(user))
+            } else {
+                Ok(None)
+            }
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn get_user_by_id(&self, user_id: i64) -> Result<Option<User>, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT id, username, email, password_hash, is_admin, created_at, disabled_at FROM users WHERE id = ?",
+            user_id
+        )
+        .fetch_optional(&self.reader)
+        .await?;
+
+        Ok(row.map(|row| User {
+            id: row.id,
+            username: row.username,
+            email: row.email,
+            password_hash: row.password_hash,
+            is_admin: row.is_admin,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            disabled_at: row.disabled_at.map(|dt| dt.to_string()),
+        }))
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT id, username, email, password_hash, is_admin, created_at, disabled_at FROM users WHERE username = ?",
+            username
+        )
+        .fetch_optional(&self.reader)
+        .await?;
+
+        Ok(row.map(|row| User {
+            id: row.id.unwrap_or_default(),
+            username: row.username,
+            email: row.email,
+            password_hash: row.password_hash,
+            is_admin: row.is_admin,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            disabled_at: row.disabled_at.map(|dt| dt.to_string()),
+        }))
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT id, username, email, password_hash, is_admin, created_at, disabled_at FROM users ORDER BY id"
+        )
+        .fetch_all(&self.reader)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| User {
+                id: row.id,
+                username: row.username,
+                email: row.email,
+                password_hash: row.password_hash,
+                is_admin: row.is_admin,
+                created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+                disabled_at: row.disabled_at.map(|dt| dt.to_string()),
+            })
+            .collect())
+    }
+
+    async fn set_user_disabled(&self, user_id: i64, disabled: bool) -> Result<bool, sqlx::Error> {
+        // Disabling an already disabled account keeps the original time
+        let rows_affected = sqlx::query!(
+            "UPDATE users SET disabled_at = CASE WHEN ? THEN COALESCE(disabled_at, CURRENT_TIMESTAMP) END WHERE id = ?",
+            disabled,
+            user_id
+        )
+        .execute(&self.writer)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn set_user_admin(&self, user_id: i64, admin: bool) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query!("UPDATE users SET is_admin = ? WHERE id = ?", admin, user_id)
+            .execute(&self.writer)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn set_user_password(&self, user_id: i64, password: &str, bcrypt_cost: u32) -> Result<bool, sqlx::Error> {
+        let password_hash = hash(password, bcrypt_cost).map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+        let rows_affected = sqlx::query!(
+            "UPDATE users SET password_hash = ? WHERE id = ?",
+            password_hash,
+            user_id
+        )
+        .execute(&self.writer)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn user_exists(&self, username: &str, email: &str) -> Result<bool, sqlx::Error> {
+        let existing = sqlx::query!(
+            "SELECT id FROM users WHERE username = ? OR email = ?",
+            username,
+            email
+        )
+        .fetch_optional(&self.reader)
+        .await?;
+
+        Ok(existing.is_some())
+    }
+}
+
+#[async_trait]
+impl SessionRepository for Sqlite {
+    async fn create_session(
+        &self,
+        user_id: i64,
+        idle_timeout: Duration,
+        absolute_timeout: Duration,
+    ) -> Result<(String, Session), sqlx::Error> {
+        let token = generate_token();
+        let session_id = hash_token(&token);
+        let now = Utc::now();
+        let absolute_expires_at = format_timestamp(now + absolute_timeout);
+        let expires_at = format_timestamp(now + idle_timeout.min(absolute_timeout));
+
+        let row = sqlx::query!(
+            "INSERT INTO sessions (id, user_id, expires_at, absolute_expires_at) VALUES (?, ?, ?, ?) RETURNING id, user_id, created_at, expires_at, absolute_expires_at",
+            session_id,
+            user_id,
+            expires_at,
+            absolute_expires_at
+        )
+        .fetch_one(&self.writer)
+        .await?;
+
+        let session = Session {
+            id: row.id.unwrap_or_default(),
+            user_id: row.user_id,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            expires_at: row.expires_at.to_string(),
+            absolute_expires_at: row.absolute_expires_at.to_string(),
+        };
+
+        Ok((token, session))
+    }
+
+    async fn get_session(&self, token: &str) -> Result<Option<(Session, User)>, sqlx::Error> {
+        let session_id = hash_token(token);
+        let row = sqlx::query!(
+            "SELECT s.id, s.user_id, s.created_at, s.expires_at, s.absolute_expires_at, u.username, u.email, u.password_hash, u.is_admin, u.created_at as user_created_at
+             FROM sessions s
+             JOIN users u ON s.user_id = u.id
+             WHERE s.id = ? AND s.expires_at > datetime('now') AND s.absolute_expires_at > datetime('now') AND u.disabled_at IS NULL",
+            session_id
+        )
+        .fetch_optional(&self.reader)
+        .await?;
+
+        if let Some(row) = row {
+            let session = Session {
+                id: row.id.unwrap_or_default(),
+                user_id: row.user_id,
+                created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+                expires_at: row.expires_at.to_string(),
+                absolute_expires_at: row.absolute_expires_at.to_string(),
+            };
+
+            let user = User {
+                id: row.user_id,
+                username: row.username,
+                email: row.email,
+                password_hash: row.password_hash,
+                is_admin: row.is_admin,
+                created_at: row
+                    .user_created_at
+                    .map(|dt| dt.to_string())
+                    .unwrap_or_default(),
+                disabled_at: None,
+            };
+
+            Ok(Some((session, user)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    async fn refresh_session(&self, session_id: &str, idle_timeout: Duration) -> Result<bool, sqlx::Error> {
+        let now = Utc::now();
+        let expires_at = format_timestamp(now + idle_timeout);
+        let threshold = format_timestamp(now + idle_timeout - idle_timeout / 10);
+
+        let rows_affected = sqlx::query!(
+            "UPDATE sessions SET expires_at = MIN(?, absolute_expires_at) WHERE id = ? AND expires_at < ?",
+            expires_at,
+            session_id,
+            threshold
+        )
+        .execute(&self.writer)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn delete_session(&self, token: &str) -> Result<bool, sqlx::Error> {
+        let session_id = hash_token(token);
+        let rows_affected = sqlx::query!("DELETE FROM sessions WHERE id = ?", session_id)
+            .execute(&self.writer)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn purge_expired_sessions(&self) -> Result<u64, sqlx::Error> {
+        let rows_affected = sqlx::query!(
+            "DELETE FROM sessions WHERE expires_at <= datetime('now') OR absolute_expires_at <= datetime('now')"
+        )
+        .execute(&self.writer)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn delete_user_sessions(&self, user_id: i64) -> Result<u64, sqlx::Error> {
+        let rows_affected = sqlx::query!("DELETE FROM sessions WHERE user_id = ?", user_id)
+            .execute(&self.writer)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected)
+    }
+
+    async fn count_active_sessions(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count: i64" FROM sessions
+             WHERE expires_at > datetime('now') AND absolute_expires_at > datetime('now')"#
+        )
+        .fetch_one(&self.reader)
+        .await
+    }
+}
+
+#[async_trait]
+impl ApiTokenRepository for Sqlite {
+    async fn create_api_token(
+        &self,
+        user_id: i64,
+        name: &str,
+        scope: TokenScope,
+        expires_in: Option<Duration>,
+    ) -> Result<(String, ApiToken), sqlx::Error> {
+        let token = format!("tdl_{}", generate_token());
+        let token_hash = hash_token(&token);
+        let scope_str = scope.as_str();
+        let expires_at = expires_in.map(|ttl| format_timestamp(Utc::now() + ttl));
+
+        let row = sqlx::query!(
+            "INSERT INTO api_tokens (user_id, name, token_hash, scope, expires_at) VALUES (?, ?, ?, ?, ?) RETURNING id, name, created_at, expires_at, last_used_at",
+            user_id,
+            name,
+            token_hash,
+            scope_str,
+            expires_at
+        )
+        .fetch_one(&self.writer)
+        .await?;
+
+        let api_token = ApiToken {
+            id: row.id,
+            name: row.name,
+            scope,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            expires_at: row.expires_at.map(|dt| dt.to_string()),
+            last_used_at: row.last_used_at.map(|dt| dt.to_string()),
+        };
+
+        Ok((token, api_token))
+    }
+
+    async fn list_api_tokens(&self, user_id: i64) -> Result<Vec<ApiToken>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT id, name, scope, created_at, expires_at, last_used_at FROM api_tokens WHERE user_id = ? ORDER BY created_at DESC, id DESC",
+            user_id
+        )
+        .fetch_all(&self.reader)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| ApiToken {
+                id: row.id.unwrap_or_default(),
+                name: row.name,
+                scope: TokenScope::parse(&row.scope).unwrap_or(TokenScope::Read),
+                created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+                expires_at: row.expires_at.map(|dt| dt.to_string()),
+                last_used_at: row.last_used_at.map(|dt| dt.to_string()),
+            })
+            .collect())
+    }
+
+    async fn delete_api_token(&self, user_id: i64, token_id: i64) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query!(
+            "DELETE FROM api_tokens WHERE id = ? AND user_id = ?",
+            token_id,
+            user_id
+        )
+        .execute(&self.writer)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn authenticate_api_token(&self, token: &str) -> Result<Option<(TokenScope, User)>, sqlx::Error> {
+        let token_hash = hash_token(token);
+        let row = sqlx::query!(
+            "SELECT t.id as token_id, t.scope, u.id, u.username, u.email, u.password_hash, u.is_admin, u.created_at
+             FROM api_tokens t
+             JOIN users u ON t.user_id = u.id
+             WHERE t.token_hash = ? AND (t.expires_at IS NULL OR t.expires_at > datetime('now')) AND u.disabled_at IS NULL",
+            token_hash
+        )
+        .fetch_optional(&self.reader)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        // Only record usage once a minute to keep reads cheap
+        sqlx::query!(
+            "UPDATE api_tokens SET last_used_at = CURRENT_TIMESTAMP WHERE id = ? AND (last_used_at IS NULL OR last_used_at < datetime('now', '-1 minute'))",
+            row.token_id
+        )
+        .execute(&self.writer)
+        .await?;
+
+        let scope = TokenScope::parse(&row.scope).unwrap_or(TokenScope::Read);
+        let user = User {
+            id: row.id,
+            username: row.username,
+            email: row.email,
+            password_hash: row.password_hash,
+            is_admin: row.is_admin,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            disabled_at: None,
+        };
+
+        Ok(Some((scope, user)))
+    }
+}
+
+#[async_trait]
+impl CalendarRepository for Sqlite {
+    async fn reset_calendar_feed(&self, user_id: i64) -> Result<String, sqlx::Error> {
+        let token = format!("cal_{}", generate_token());
+        let token_hash = hash_token(&token);
+
+        sqlx::query!(
+            "INSERT INTO calendar_feeds (user_id, token_hash) VALUES (?, ?)
+             ON CONFLICT (user_id) DO UPDATE SET token_hash = excluded.token_hash, created_at = CURRENT_TIMESTAMP",
+            user_id,
+            token_hash
+        )
+        .execute(&self.writer)
+        .await?;
+
+        Ok(token)
+    }
+
+    async fn get_calendar_feed(&self, user_id: i64) -> Result<Option<CalendarFeed>, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT created_at FROM calendar_feeds WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.reader)
+        .await?;
+
+        Ok(row.map(|row| CalendarFeed {
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+        }))
+    }
+
+    async fn delete_calendar_feed(&self, user_id: i64) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query!("DELETE FROM calendar_feeds WHERE user_id = ?", user_id)
+            .execute(&self.writer)
+            .await?
+            .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn calendar_feed_user(&self, token: &str) -> Result<Option<i64>, sqlx::Error> {
+        let token_hash = hash_token(token);
+
+        sqlx::query_scalar!(
+            "SELECT f.user_id AS \"user_id!\" FROM calendar_feeds f
+             JOIN users u ON f.user_id = u.id
+             WHERE f.token_hash = ? AND u.disabled_at IS NULL",
+            token_hash
+        )
+        .fetch_optional(&self.reader)
+        .await
+    }
+
+    async fn list_dav_resources(&self, user_id: i64) -> Result<Vec<DavResource>, sqlx::Error> {
+        sqlx::query_as!(
+            DavResource,
+            "SELECT todo_id AS \"todo_id!\", name, uid FROM caldav_resources WHERE user_id = ?",
+            user_id
+        )
+        .fetch_all(&self.reader)
+        .await
+    }
+
+    async fn create_dav_todo(
+        &self,
+        user_id: i64,
+        name: &str,
+        uid: Option<&str>,
+        title: &str,
+        completed: bool,
+    ) -> Result<Todo, sqlx::Error> {
+        let mut tx = self.writer.begin().await?;
+        let todo = insert_user_todo(&mut tx, user_id, title, completed).await?;
+        let uid = uid.map_or_else(|| ics::uid(&todo), str::to_string);
+        sqlx::query!(
+            "INSERT INTO caldav_resources (todo_id, user_id, name, uid) VALUES (?, ?, ?, ?)",
+            todo.id,
+            user_id,
+            name,
+            uid
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        events::publish(user_id, TodoEvent::Created { todo: todo.clone() });
+        Ok(todo)
+    }
+}
+
+/// Queues a delivery of `event` for `todo` to each of the user's webhooks
+/// subscribed to it. Runs inside the transaction that made the change, so
+/// a committed change always has its deliveries queued.
+async fn enqueue_webhook_deliveries(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    event: WebhookEvent,
+    todo: &Todo,
+) -> Result<(), sqlx::Error> {
+    let event_name = event.as_str();
+    let payload = webhooks::delivery::payload(event, todo);
+
+    sqlx::query!(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload)
+         SELECT id, ?, ? FROM webhooks
+         WHERE user_id = ? AND instr(',' || events || ',', ',' || ? || ',') > 0",
+        event_name,
+        payload,
+        user_id,
+        event_name
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Inserts a todo and queues its `todo.created` webhooks, as part of a
+/// caller's transaction.
+async fn insert_user_todo(
+    tx: &mut SqliteConnection,
+    user_id: i64,
+    title: &str,
+    completed: bool,
+) -> Result<Todo, sqlx::Error> {
+    let row = sqlx::query!(
+        "INSERT INTO todos (title, completed, user_id) VALUES (?, ?, ?) RETURNING id, title, completed, created_at, updated_at, version, user_id",
+        title,
+        completed,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let todo = Todo {
+        id: row.id,
+        title: row.title,
+        completed: row.completed,
+        created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+        updated_at: row.updated_at.map(|dt| dt.to_string()).unwrap_or_default(),
+        version: row.version,
+        user_id: row.user_id,
+    };
+    enqueue_webhook_deliveries(tx, user_id, WebhookEvent::TodoCreated, &todo).await?;
+    Ok(todo)
+}
+
+#[async_trait]
+impl TodoRepository for Sqlite {
+    async fn get_user_todos(&self, user_id: i64) -> Result<Vec<Todo>, sqlx::Error> {
+        // With the created_at index, sqlx can't tell that id isn't null
+        let rows = sqlx::query!(
+            r#"SELECT id as "id!", title, completed, created_at, updated_at, version, user_id FROM todos WHERE user_id = ? ORDER BY created_at DESC, id DESC"#,
+            user_id
+        )
+        .fetch_all(&self.reader)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Todo {
+                id: row.id,
+                title: row.title,
+                completed: row.completed,
+                created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+                updated_at: row.updated_at.map(|dt| dt.to_string()).unwrap_or_default(),
+                version: row.version,
+                user_id: row.user_id,
+            })
+            .collect())
+    }
+
+    async fn send_user_todos(
+        &self,
+        user_id: i64,
+        completed: Option<bool>,
+        sender: mpsc::Sender<Result<Todo, sqlx::Error>>,
+    ) {
+        // The last todo sent, to read the next page after
+        let mut after: Option<(Option<chrono::NaiveDateTime>, i64)> = None;
+        loop {
+            let (after_created_at, after_id) = after.unzip();
+            let after_created_at = after_created_at.flatten();
+            // With the created_at index, sqlx can't tell that id isn't null
+            let page = sqlx::query!(
+                r#"SELECT id as "id!", title, completed, created_at, updated_at, version, user_id FROM todos
+                 WHERE user_id = ? AND (? IS NULL OR completed = ?)
+                   AND (? IS NULL OR created_at > ? OR (created_at = ? AND id > ?))
+                 ORDER BY created_at, id
+                 LIMIT ?"#,
+                user_id,
+                completed,
+                completed,
+                after_id,
+                after_created_at,
+                after_created_at,
+                after_id,
+                SEND_PAGE_SIZE
+            )
+            .fetch_all(&self.reader)
+            .await;
+            let page = match page {
+                Ok(page) => page,
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            };
+
+            let last_page = (page.len() as i64) < SEND_PAGE_SIZE;
+            for row in page {
+                after = Some((row.created_at, row.id));
+                let todo = Todo {
+                    id: row.id,
+                    title: row.title,
+                    completed: row.completed,
+                    created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+                    updated_at: row.updated_at.map(|dt| dt.to_string()).unwrap_or_default(),
+                    version: row.version,
+                    user_id: row.user_id,
+                };
+                if sender.send(Ok(todo)).await.is_err() {
+                    return;
+                }
+            }
+            if last_page {
+                return;
+            }
+        }
+    }
+
+    async fn get_user_todo_by_id(&self, user_id: i64, todo_id: i64) -> Result<Option<Todo>, sqlx::Error> {
+        let row = sqlx::query!(
+            "SELECT id, title, completed, created_at, updated_at, version, user_id FROM todos WHERE user_id = ? AND id = ?",
+            user_id,
+            todo_id
+        )
+        .fetch_optional(&self.reader)
+        .await?;
+
+        Ok(row.map(|row| Todo {
+            id: row.id,
+            title: row.title,
+            completed: row.completed,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            updated_at: row.updated_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            version: row.version,
+            user_id: row.user_id,
+        }))
+    }
+
+    async fn create_user_todo(&self, user_id: i64, todo: CreateTodo) -> Result<Todo, sqlx::Error> {
+        let mut tx = self.writer.begin().await?;
+        let todo = insert_user_todo(&mut tx, user_id, &todo.title, false).await?;
+        tx.commit().await?;
+
+        events::publish(user_id, TodoEvent::Created { todo: todo.clone() });
+        Ok(todo)
+    }
+
+    async fn import_user_todos(&self, user_id: i64, todos: &[ImportedTodo]) -> Result<Vec<Todo>, sqlx::Error> {
+        let mut tx = self.writer.begin().await?;
+        let mut created = Vec::with_capacity(todos.len());
+        for todo in todos {
+            created.push(insert_user_todo(&mut tx, user_id, &todo.title, todo.completed).await?);
+        }
+        tx.commit().await?;
+
+        for todo in &created {
+            events::publish(user_id, TodoEvent::Created { todo: todo.clone() });
+        }
+        Ok(created)
+    }
+
+    async fn update_user_todo(
+        &self,
+        user_id: i64,
+        todo_id: i64,
+        update: UpdateTodo,
+        expected_version: Option<i64>,
+    ) -> Result<Option<Todo>, sqlx::Error> {
+        let mut tx = self.writer.begin().await?;
+
+        let was_completed = sqlx::query_scalar!(
+            "SELECT completed FROM todos WHERE id = ? AND user_id = ?",
+            todo_id,
+            user_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let rows_affected = sqlx::query!(
+            "UPDATE todos SET title = COALESCE(?, title), completed = COALESCE(?, completed), updated_at = CURRENT_TIMESTAMP, version = version + 1
+             WHERE id = ? AND user_id = ? AND (? IS NULL OR version = ?)",
+            update.title,
+            update.completed,
+            todo_id,
+            user_id,
+            expected_version,
+            expected_version
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        if rows_affected == 0 {
+            return Ok(None);
+        }
+
+        let row = sqlx::query!(
+            "SELECT id, title, completed, created_at, updated_at, version, user_id FROM todos WHERE id = ? AND user_id = ?",
+            todo_id,
+            user_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let todo = Todo {
+            id: row.id,
+            title: row.title,
+            completed: row.completed,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            updated_at: row.updated_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            version: row.version,
+            user_id: row.user_id,
+        };
+        if todo.completed && was_completed == Some(false) {
+            enqueue_webhook_deliveries(&mut tx, user_id, WebhookEvent::TodoCompleted, &todo)
+                .await?;
+        }
+        tx.commit().await?;
+
+        events::publish(user_id, TodoEvent::Updated { todo: todo.clone() });
+        Ok(Some(todo))
+    }
+
+    async fn delete_user_todo(
+        &self,
+        user_id: i64,
+        todo_id: i64,
+        expected_version: Option<i64>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut tx = self.writer.begin().await?;
+
+        let row = sqlx::query!(
+            "DELETE FROM todos WHERE id = ? AND user_id = ? AND (? IS NULL OR version = ?)
+             RETURNING id, title, completed, created_at, updated_at, version, user_id",
+            todo_id,
+            user_id,
+            expected_version,
+            expected_version
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let Some(row) = row else {
+            return Ok(false);
+        };
+        let todo = Todo {
+            id: row.id,
+            title: row.title,
+            completed: row.completed,
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            updated_at: row.updated_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            version: row.version,
+            user_id: row.user_id,
+        };
+        enqueue_webhook_deliveries(&mut tx, user_id, WebhookEvent::TodoDeleted, &todo).await?;
+        tx.commit().await?;
+
+        events::publish(user_id, TodoEvent::Deleted { id: todo_id });
+        Ok(true)
+    }
+
+    async fn count_todos_created_since(&self, since: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+        let since = format_timestamp(since);
+        sqlx::query_scalar!(r#"SELECT COUNT(*) as "count: i64" FROM todos WHERE created_at >= ?"#, since)
+            .fetch_one(&self.reader)
+            .await
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for Sqlite {
+    async fn create_webhook(
+        &self,
+        user_id: i64,
+        url: &str,
+        events: &[WebhookEvent],
+    ) -> Result<(String, Webhook), sqlx::Error> {
+        let secret = format!("whsec_{}", generate_token());
+        let events_str = events
+            .iter()
+            .map(WebhookEvent::as_str)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let row = sqlx::query!(
+            "INSERT INTO webhooks (user_id, url, secret, events) VALUES (?, ?, ?, ?) RETURNING id, url, created_at",
+            user_id,
+            url,
+            secret,
+            events_str
+        )
+        .fetch_one(&self.writer)
+        .await?;
+
+        let webhook = Webhook {
+            id: row.id,
+            url: row.url,
+            events: events.to_vec(),
+            created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+        };
+
+        Ok((secret, webhook))
+    }
+
+    async fn list_webhooks(&self, user_id: i64) -> Result<Vec<Webhook>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT id, url, events, created_at FROM webhooks WHERE user_id = ? ORDER BY created_at DESC, id DESC",
+            user_id
+        )
+        .fetch_all(&self.reader)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Webhook {
+                id: row.id.unwrap_or_default(),
+                url: row.url,
+                events: row.events.split(',').filter_map(WebhookEvent::parse).collect(),
+                created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    async fn delete_webhook(&self, user_id: i64, webhook_id: i64) -> Result<bool, sqlx::Error> {
+        let rows_affected = sqlx::query!(
+            "DELETE FROM webhooks WHERE id = ? AND user_id = ?",
+            webhook_id,
+            user_id
+        )
+        .execute(&self.writer)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn list_webhook_deliveries(&self, user_id: i64, limit: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT d.id, d.webhook_id, w.url, d.event, d.status, d.attempts, d.last_status_code, d.last_error, d.created_at, d.delivered_at
+             FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+             WHERE w.user_id = ? ORDER BY d.id DESC LIMIT ?",
+            user_id,
+            limit
+        )
+        .fetch_all(&self.reader)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| WebhookDelivery {
+                id: row.id.unwrap_or_default(),
+                webhook_id: row.webhook_id,
+                url: row.url,
+                event: row.event,
+                status: row.status,
+                attempts: row.attempts,
+                last_status_code: row.last_status_code,
+                last_error: row.last_error,
+                created_at: row.created_at.map(|dt| dt.to_string()).unwrap_or_default(),
+                delivered_at: row.delivered_at.map(|dt| dt.to_string()),
+            })
+            .collect())
+    }
+
+    async fn due_webhook_deliveries(&self, limit: i64) -> Result<Vec<DueDelivery>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT d.id, w.url, w.secret, d.event, d.payload, d.attempts
+             FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+             WHERE d.status = 'pending' AND d.next_attempt_at <= datetime('now')
+             ORDER BY d.id LIMIT ?",
+            limit
+        )
+        .fetch_all(&self.reader)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| DueDelivery {
+                id: row.id.unwrap_or_default(),
+                url: row.url,
+                secret: row.secret,
+                event: row.event,
+                payload: row.payload,
+                attempts: row.attempts,
+            })
+            .collect())
+    }
+
+    async fn mark_webhook_delivered(&self, delivery_id: i64, status_code: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET status = 'delivered', attempts = attempts + 1, last_status_code = ?, last_error = NULL, delivered_at = datetime('now') WHERE id = ?",
+            status_code,
+            delivery_id
+        )
+        .execute(&self.writer)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_webhook_attempt_failed(
+        &self,
+        delivery_id: i64,
+        status_code: Option<i64>,
+        error: &str,
+        retry_at: Option<chrono::DateTime<Utc>>,
+    ) -> Result<(), sqlx::Error> {
+        let status = if retry_at.is_some() { "pending" } else { "failed" };
+        let next_attempt_at = retry_at.map(format_timestamp);
+
+        sqlx::query!(
+            "UPDATE webhook_deliveries SET status = ?, attempts = attempts + 1, last_status_code = ?, last_error = ?, next_attempt_at = COALESCE(?, next_attempt_at) WHERE id = ?",
+            status,
+            status_code,
+            error,
+            next_attempt_at,
+            delivery_id
+        )
+        .execute(&self.writer)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn purge_webhook_deliveries(&self, before: chrono::DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let before = format_timestamp(before);
+        let rows_affected = sqlx::query!(
+            "DELETE FROM webhook_deliveries WHERE status IN ('delivered', 'failed') AND created_at < ?",
+            before
+        )
+        .execute(&self.writer)
+        .await?
+        .rows_affected();
+
+        Ok(rows_affected)
+    }
+}
+
+#[async_trait]
+impl Repository for Sqlite {
+    async fn optimize(&self) -> Result<(), sqlx::Error> {
+        sqlx::query("PRAGMA optimize").execute(&self.writer).await?;
+        // No-op unless the database was created with auto_vacuum = INCREMENTAL
+        sqlx::query("PRAGMA incremental_vacuum").execute(&self.writer).await?;
+        Ok(())
+    }
+
+    async fn backup(&self, path: &Path) -> Result<(), sqlx::Error> {
+        // `VACUUM INTO` opens its target the way the source was opened, so
+        // an in-memory database would only be copied into memory
+        let file: String = sqlx::query_scalar("SELECT file FROM pragma_database_list WHERE name = 'main'")
+            .fetch_one(&self.writer)
+            .await?;
+        if file.is_empty() {
+            return Err(sqlx::Error::Configuration("in-memory databases can't be backed up".into()));
+        }
+
+        // A consistent snapshot, taken without holding up writers
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy())
+            .execute(&self.reader)
+            .await?;
+        Ok(())
+    }
+
+    async fn close(&self) {
+        // The last connection to close checkpoints the WAL into the main
+        // file, which the read-only ones can't do. A connection still being
+        // handed back when a pool closes can end up idle in it instead of
+        // closed; closing again closes those.
+        for pool in [&self.reader, &self.writer] {
+            pool.close().await;
+            pool.close().await;
+        }
+    }
+
+    fn pool_stats(&self) -> Vec<PoolStats> {
+        vec![PoolStats::of("reader", &self.reader), PoolStats::of("writer", &self.writer)]
+    }
+}
@@ -0,0 +1,113 @@
+use leptos::server_fn::codec::JsonEncoding;
+use leptos::server_fn::error::{FromServerFnError, ServerFnErrorErr};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Error type returned by every server function.
+///
+/// Only the variant and a user-facing message cross the wire; the underlying
+/// cause of an `Internal` error is logged on the server and never sent to the
+/// browser.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, thiserror::Error)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub enum AppError {
+    #[error("Not authenticated")]
+    Unauthorized,
+    #[error("You are not allowed to do that")]
+    Forbidden,
+    #[error("Not found")]
+    NotFound,
+    #[error("{0}")]
+    Conflict(String),
+    #[error("The resource was changed by someone else, reload and try again")]
+    PreconditionFailed,
+    #[error("Validation failed")]
+    Validation { fields: BTreeMap<String, String> },
+    #[error("Too many requests, please try again later")]
+    RateLimited,
+    #[error("Something went wrong, please try again")]
+    Internal,
+    /// The call never reached the server function body (network failure,
+    /// encoding error, ...).
+    #[error("Request failed: {0}")]
+    Request(String),
+}
+
+impl AppError {
+    pub fn validation(field: &str, message: &str) -> Self {
+        let mut fields = BTreeMap::new();
+        fields.insert(field.to_string(), message.to_string());
+        AppError::Validation { fields }
+    }
+
+    /// Logs `cause` server-side and returns an opaque `Internal` error.
+    pub fn internal(cause: impl std::fmt::Display) -> Self {
+        #[cfg(feature = "ssr")]
+        tracing::error!(%cause, "internal error");
+        #[cfg(not(feature = "ssr"))]
+        leptos::logging::error!("internal error: {cause}");
+        AppError::Internal
+    }
+
+    /// HTTP status code this error maps to.
+    pub fn status_code(&self) -> u16 {
+        match self {
+            AppError::Unauthorized => 401,
+            AppError::Forbidden => 403,
+            AppError::NotFound => 404,
+            AppError::Conflict(_) => 409,
+            AppError::PreconditionFailed => 412,
+            AppError::Validation { .. } => 422,
+            AppError::RateLimited => 429,
+            AppError::Internal => 500,
+            AppError::Request(_) => 400,
+        }
+    }
+
+    /// Message suitable for showing to the user, including per-field
+    /// validation messages.
+    pub fn user_message(&self) -> String {
+        match self {
+            AppError::Validation { fields } if !fields.is_empty() => fields
+                .values()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" "),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl FromServerFnError for AppError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        match value {
+            ServerFnErrorErr::ServerError(message)
+            | ServerFnErrorErr::MiddlewareError(message) => AppError::internal(message),
+            other => AppError::Request(other.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::RowNotFound => AppError::NotFound,
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                AppError::Conflict("Resource already exists".to_string())
+            }
+            _ => AppError::internal(error),
+        }
+    }
+}
+
+#[cfg(feature = "ssr")]
+impl axum::response::IntoResponse for AppError {
+    fn into_response(self) -> axum::response::Response {
+        let status = axum::http::StatusCode::from_u16(self.status_code())
+            .unwrap_or(axum::http::StatusCode::INTERNAL_SERVER_ERROR);
+        (status, axum::Json(self)).into_response()
+    }
+}
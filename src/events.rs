@@ -0,0 +1,139 @@
+//! Live todo updates, so every open tab of a user sees changes made
+//! elsewhere without reloading.
+//!
+//! The mutating queries in `database` publish a [`TodoEvent`] for
+//! each change; `/api/events` streams the current user's events as
+//! server-sent events and [`subscribe`] consumes them in the browser.
+
+use crate::models::TodoEvent;
+
+pub const EVENTS_PATH: &str = "/api/events";
+
+#[cfg(feature = "ssr")]
+mod hub {
+    use crate::models::TodoEvent;
+    use std::collections::HashMap;
+    use std::sync::{LazyLock, Mutex};
+    use tokio::sync::broadcast;
+
+    /// How many events a slow subscriber may fall behind before it is told
+    /// to resync. Each user has their own channel, so only their own
+    /// changes count towards it.
+    const CAPACITY: usize = 64;
+
+    /// A channel per user with open event streams.
+    static CHANNELS: LazyLock<Mutex<HashMap<i64, broadcast::Sender<TodoEvent>>>> =
+        LazyLock::new(Default::default);
+
+    pub(super) fn subscribe(user_id: i64) -> broadcast::Receiver<TodoEvent> {
+        let mut channels = CHANNELS.lock().unwrap();
+        channels.retain(|_, sender| sender.receiver_count() > 0);
+        channels
+            .entry(user_id)
+            .or_insert_with(|| broadcast::channel(CAPACITY).0)
+            .subscribe()
+    }
+
+    pub(super) fn send(user_id: i64, event: TodoEvent) {
+        let mut channels = CHANNELS.lock().unwrap();
+        let Some(sender) = channels.get(&user_id) else {
+            return;
+        };
+        // Sending only fails once the user's last stream has closed
+        if sender.send(event).is_err() {
+            channels.remove(&user_id);
+        }
+    }
+}
+
+/// Broadcasts `event` to every open event stream of `user_id`.
+#[cfg(feature = "ssr")]
+pub fn publish(user_id: i64, event: TodoEvent) {
+    hub::send(user_id, event);
+}
+
+/// `GET /api/events`: the signed-in user's todo events as `todo` SSE
+/// messages carrying JSON-encoded [`TodoEvent`]s.
+///
+/// Streams end once `shutdown` flips to `true`, so open tabs don't hold up
+/// a graceful shutdown; browsers reconnect to the next server on their own.
+#[cfg(feature = "ssr")]
+pub async fn event_stream(
+    axum::extract::State(mut shutdown): axum::extract::State<tokio::sync::watch::Receiver<bool>>,
+    axum::Extension(current): axum::Extension<crate::auth::CurrentUser>,
+) -> Result<axum::response::Response, crate::error::AppError> {
+    use crate::models::TokenScope;
+    use axum::response::sse::{Event, KeepAlive, Sse};
+    use axum::response::IntoResponse;
+    use futures::StreamExt;
+    use tokio::sync::broadcast::error::RecvError;
+
+    let user_id = current.require(TokenScope::Read)?.id;
+    let receiver = hub::subscribe(user_id);
+
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        let event = match receiver.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(_)) => TodoEvent::Resync,
+            Err(RecvError::Closed) => return None,
+        };
+        let message = Event::default().event("todo").json_data(&event);
+        Some((message, receiver))
+    })
+    .take_until(async move {
+        let _ = shutdown.wait_for(|stop| *stop).await;
+    });
+
+    Ok(Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+/// Calls `on_event` for every todo event pushed to the current user until
+/// the calling reactive owner is cleaned up.
+///
+/// The browser reconnects on its own after a dropped connection; since
+/// events may have been missed in the meantime, each reconnect is reported
+/// as [`TodoEvent::Resync`].
+#[cfg(feature = "hydrate")]
+pub fn subscribe(on_event: impl Fn(TodoEvent) + 'static) {
+    use leptos::prelude::on_cleanup;
+    use send_wrapper::SendWrapper;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{EventSource, MessageEvent};
+
+    let Ok(source) = EventSource::new(EVENTS_PATH) else {
+        return;
+    };
+
+    let on_event = Rc::new(on_event);
+    let on_message = Closure::<dyn Fn(MessageEvent)>::new({
+        let on_event = on_event.clone();
+        move |message: MessageEvent| {
+            let event = message
+                .data()
+                .as_string()
+                .and_then(|data| serde_json::from_str(&data).ok());
+            if let Some(event) = event {
+                on_event(event);
+            }
+        }
+    });
+    let connected_before = Cell::new(false);
+    let on_open = Closure::<dyn Fn()>::new(move || {
+        if connected_before.replace(true) {
+            on_event(TodoEvent::Resync);
+        }
+    });
+
+    let _ = source.add_event_listener_with_callback("todo", on_message.as_ref().unchecked_ref());
+    let _ = source.add_event_listener_with_callback("open", on_open.as_ref().unchecked_ref());
+
+    let subscription = SendWrapper::new((source, on_message, on_open));
+    on_cleanup(move || subscription.0.close());
+}
+
+#[cfg(not(feature = "hydrate"))]
+pub fn subscribe(_on_event: impl Fn(TodoEvent) + 'static) {}
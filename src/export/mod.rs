@@ -0,0 +1,79 @@
+//! Downloading the signed-in user's todos as JSON, CSV, a Markdown checklist
+//! or todo.txt, each readable again by the importer.
+//!
+//! `/api/export` streams the file as the todos are read, so large lists
+//! aren't built up in memory first.
+
+use crate::models::TodoFormat;
+
+#[cfg(feature = "ssr")]
+pub mod write;
+
+pub const EXPORT_PATH: &str = "/api/export";
+
+/// Link to an export in `format`, of only the todos whose completion state
+/// is `completed` if given.
+pub fn export_url(format: TodoFormat, completed: Option<bool>) -> String {
+    match completed {
+        Some(completed) => format!("{EXPORT_PATH}?format={}&completed={completed}", format.as_str()),
+        None => format!("{EXPORT_PATH}?format={}", format.as_str()),
+    }
+}
+
+#[cfg(feature = "ssr")]
+#[derive(Debug, serde::Deserialize)]
+pub struct ExportQuery {
+    /// Defaults to JSON.
+    pub format: Option<TodoFormat>,
+    /// Only export todos with this completion state.
+    pub completed: Option<bool>,
+}
+
+/// `GET /api/export?format=…&completed=…`: the current user's todos as a
+/// file attachment.
+#[cfg(feature = "ssr")]
+pub async fn export(
+    axum::extract::State(db): axum::extract::State<crate::database::Database>,
+    axum::Extension(current): axum::Extension<crate::auth::CurrentUser>,
+    axum::extract::Query(query): axum::extract::Query<ExportQuery>,
+) -> Result<axum::response::Response, crate::error::AppError> {
+    use crate::models::TokenScope;
+    use axum::body::Body;
+    use axum::http::header;
+    use axum::response::IntoResponse;
+    use futures::StreamExt;
+
+    let user_id = current.require(TokenScope::Read)?.id;
+    let format = query.format.unwrap_or(TodoFormat::Json);
+
+    let (sender, receiver) = tokio::sync::mpsc::channel(64);
+    tokio::spawn(async move {
+        db.send_user_todos(user_id, query.completed, sender).await;
+    });
+
+    let todos = futures::stream::unfold((receiver, 0), move |(mut receiver, index)| async move {
+        let chunk = receiver
+            .recv()
+            .await?
+            .map(|todo| write::todo(format, index, &todo));
+        Some((chunk, (receiver, index + 1)))
+    });
+    let body = futures::stream::once(async move { Ok(write::header(format)) })
+        .chain(todos)
+        .chain(futures::stream::once(async move { Ok(write::footer(format)) }));
+
+    let filename = format!(
+        "todos-{}.{}",
+        chrono::Utc::now().format("%Y-%m-%d"),
+        format.extension()
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{filename}\"")),
+            (header::CACHE_CONTROL, "private, no-cache".to_string()),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response())
+}
@@ -0,0 +1,90 @@
+//! Writers for each export format, one todo at a time: a file is its
+//! [`header`], then each [`todo`] in turn, then its [`footer`].
+
+use crate::models::{Todo, TodoFormat};
+
+/// Columns of the CSV export, which the importer recognises by name.
+const CSV_COLUMNS: [&str; 5] = ["id", "title", "completed", "created_at", "updated_at"];
+
+pub fn header(format: TodoFormat) -> String {
+    match format {
+        TodoFormat::Json => "[".to_string(),
+        TodoFormat::Csv => csv_row(&CSV_COLUMNS),
+        TodoFormat::Markdown => "# Todos\n\n".to_string(),
+        TodoFormat::TodoTxt => String::new(),
+    }
+}
+
+/// `todo`, the `index`th in the file counting from 0.
+pub fn todo(format: TodoFormat, index: usize, todo: &Todo) -> String {
+    match format {
+        TodoFormat::Json => {
+            let separator = if index == 0 { "\n  " } else { ",\n  " };
+            // Serializing a `Todo` can't fail
+            format!("{separator}{}", serde_json::to_string(todo).unwrap_or_default())
+        }
+        TodoFormat::Csv => csv_row(&[
+            &todo.id.to_string(),
+            &todo.title,
+            &todo.completed.to_string(),
+            &todo.created_at,
+            &todo.updated_at,
+        ]),
+        TodoFormat::Markdown => {
+            let mark = if todo.completed { 'x' } else { ' ' };
+            format!("- [{mark}] {}\n", one_line(&todo.title))
+        }
+        TodoFormat::TodoTxt => todo_txt_line(todo),
+    }
+}
+
+pub fn footer(format: TodoFormat) -> String {
+    match format {
+        TodoFormat::Json => "\n]\n".to_string(),
+        TodoFormat::Csv | TodoFormat::Markdown | TodoFormat::TodoTxt => String::new(),
+    }
+}
+
+fn csv_row(fields: &[&str]) -> String {
+    let mut writer = csv::WriterBuilder::new()
+        .terminator(csv::Terminator::CRLF)
+        .from_writer(Vec::new());
+    // Writing to a Vec can't fail
+    let _ = writer.write_record(fields);
+    let bytes = writer.into_inner().unwrap_or_default();
+    String::from_utf8(bytes).unwrap_or_default()
+}
+
+/// `x` and the completion date for done todos, then the creation date and
+/// the title, which keeps any `@context`s and `+project`s it was written with.
+fn todo_txt_line(todo: &Todo) -> String {
+    let mut line = String::new();
+    let created = date(&todo.created_at);
+    if todo.completed {
+        line.push_str("x ");
+        // A completion date must be followed by a creation date
+        if let (Some(completed), Some(_)) = (date(&todo.updated_at), created) {
+            line.push_str(completed);
+            line.push(' ');
+        }
+    }
+    if let Some(created) = created {
+        line.push_str(created);
+        line.push(' ');
+    }
+    line.push_str(&one_line(&todo.title));
+    line.push('\n');
+    line
+}
+
+/// The `YYYY-MM-DD` part of a stored timestamp.
+fn date(timestamp: &str) -> Option<&str> {
+    timestamp
+        .get(..10)
+        .filter(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok())
+}
+
+/// Line-based formats can't hold a line break inside a title.
+fn one_line(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,53 @@
+//! Bringing existing tasks in from todo.txt, CSV, Markdown checklists or the
+//! app's own JSON export.
+//!
+//! The same call previews an import (`dry_run`) and performs it; either all
+//! of a file's todos are created or none are.
+
+use crate::csrf::CsrfClient;
+use crate::error::AppError;
+use crate::models::{ImportPreview, ImportRequest};
+use leptos::prelude::*;
+
+#[cfg(feature = "ssr")]
+pub mod parse;
+
+#[cfg(feature = "ssr")]
+use crate::auth::require_scope;
+#[cfg(feature = "ssr")]
+use crate::models::TokenScope;
+#[cfg(feature = "ssr")]
+use crate::database::Database;
+
+/// Largest file accepted, in bytes.
+pub const MAX_IMPORT_BYTES: usize = 1024 * 1024;
+/// Most todos created by one import.
+pub const MAX_IMPORT_TODOS: usize = 5000;
+
+#[server(ImportTodos, "/api", client = CsrfClient)]
+pub async fn import_todos(request: ImportRequest) -> Result<ImportPreview, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_scope(TokenScope::Write).await?;
+
+    if request.content.len() > MAX_IMPORT_BYTES {
+        return Err(AppError::validation(
+            "content",
+            "That file is too large to import (1 MB at most).",
+        ));
+    }
+
+    let mut preview = parse::parse(&request)?;
+    if preview.todos.len() > MAX_IMPORT_TODOS {
+        return Err(AppError::validation(
+            "content",
+            &format!("That's more than {MAX_IMPORT_TODOS} todos; split the file and import it in parts."),
+        ));
+    }
+
+    if !request.dry_run && !preview.todos.is_empty() {
+        db.import_user_todos(user.id, &preview.todos).await?;
+        preview.imported = true;
+    }
+    Ok(preview)
+}
@@ -0,0 +1,238 @@
+//! Readers for each import format. Lines that can't be read are reported in
+//! [`ImportPreview::skipped`] rather than failing the whole file.
+
+use crate::error::AppError;
+use crate::models::{CsvColumns, TodoFormat, ImportPreview, ImportRequest, ImportedTodo};
+use crate::server_functions::validate_title;
+use serde_json::Value;
+
+/// Header names tried, in order, when no title column is chosen. Failing
+/// those the first column is used.
+const TITLE_COLUMNS: [&str; 6] = ["title", "task", "name", "summary", "subject", "description"];
+/// Header names tried when no completion column is chosen.
+const COMPLETED_COLUMNS: [&str; 4] = ["completed", "done", "complete", "status"];
+/// Cell values that mark a todo as done.
+const TRUTHY: [&str; 8] = ["1", "true", "yes", "y", "x", "done", "complete", "completed"];
+
+pub fn parse(request: &ImportRequest) -> Result<ImportPreview, AppError> {
+    match request.format {
+        TodoFormat::TodoTxt => Ok(todo_txt(&request.content)),
+        TodoFormat::Csv => csv(&request.content, &request.columns),
+        TodoFormat::Markdown => Ok(markdown(&request.content)),
+        TodoFormat::Json => json(&request.content),
+    }
+}
+
+/// One task per line: `x` for done, then optional dates and `(A)` priority,
+/// then the description with its `@context`s, `+project`s and `due:` date.
+/// The description becomes the title as written.
+pub fn todo_txt(content: &str) -> ImportPreview {
+    let mut preview = ImportPreview::default();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match todo_txt_line(line) {
+            Some(todo) => preview.todos.push(todo),
+            None => preview
+                .skipped
+                .push(format!("Line {}: no description", index + 1)),
+        }
+    }
+    preview
+}
+
+fn todo_txt_line(line: &str) -> Option<ImportedTodo> {
+    let mut todo = ImportedTodo::default();
+    let mut rest = line;
+
+    if let Some(after) = rest.strip_prefix("x ") {
+        todo.completed = true;
+        rest = strip_date(after.trim_start());
+    }
+    let bytes = rest.as_bytes();
+    if bytes.len() >= 4 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')' && bytes[3] == b' ' {
+        todo.priority = Some(bytes[1] as char);
+        rest = rest[4..].trim_start();
+    }
+    // Creation date
+    rest = strip_date(rest);
+
+    for word in rest.split_whitespace() {
+        if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            todo.contexts.push(context.to_string());
+        } else if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+            todo.projects.push(project.to_string());
+        } else if let Some(due) = word.strip_prefix("due:").filter(|d| !d.is_empty()) {
+            todo.due = Some(due.to_string());
+        } else if let Some(priority) = word.strip_prefix("pri:") {
+            // Completed tasks keep their priority as a tag
+            todo.priority = todo
+                .priority
+                .or(priority.chars().next().filter(char::is_ascii_uppercase));
+        }
+    }
+
+    todo.title = validate_title(rest).ok()?;
+    Some(todo)
+}
+
+/// Drops a leading `YYYY-MM-DD` date.
+fn strip_date(value: &str) -> &str {
+    match value.get(..10) {
+        Some(date)
+            if chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok()
+                && value[10..].chars().next().is_none_or(char::is_whitespace) =>
+        {
+            value[10..].trim_start()
+        }
+        _ => value,
+    }
+}
+
+/// `- [ ]` / `- [x]` items (any bullet or numbered list); every other line
+/// is ignored.
+pub fn markdown(content: &str) -> ImportPreview {
+    let mut preview = ImportPreview::default();
+    for (index, line) in content.lines().enumerate() {
+        let Some((completed, text)) = checklist_item(line) else {
+            continue;
+        };
+        match validate_title(text) {
+            Ok(title) => preview.todos.push(ImportedTodo {
+                title,
+                completed,
+                ..Default::default()
+            }),
+            Err(_) => preview.skipped.push(format!("Line {}: empty item", index + 1)),
+        }
+    }
+    preview
+}
+
+fn checklist_item(line: &str) -> Option<(bool, &str)> {
+    let line = line.trim_start();
+    let rest = match line.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let number = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if number == 0 {
+                return None;
+            }
+            line[number..].strip_prefix(['.', ')'])?
+        }
+    };
+
+    let rest = rest.strip_prefix(' ')?.trim_start();
+    let completed = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    Some((completed, &rest[3..]))
+}
+
+/// A CSV file with a header row. `columns` picks the title and completion
+/// columns by header name.
+pub fn csv(content: &str, columns: &CsvColumns) -> Result<ImportPreview, AppError> {
+    let unreadable = |e: csv::Error| AppError::validation("content", &format!("Couldn't read the CSV: {e}"));
+
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(unreadable)?
+        .iter()
+        .map(str::to_string)
+        .collect();
+    let mut preview = ImportPreview {
+        columns: headers.clone(),
+        ..Default::default()
+    };
+
+    let find = |name: &str| headers.iter().position(|header| header.eq_ignore_ascii_case(name));
+    let chosen = |field: &str, name: &str| {
+        find(name).ok_or_else(|| AppError::validation(field, &format!("There's no \"{name}\" column.")))
+    };
+
+    let guessed = |candidates: &[&str]| {
+        (!columns.chosen)
+            .then(|| candidates.iter().find_map(|name| find(name)))
+            .flatten()
+    };
+
+    let title = match columns.title.as_deref() {
+        Some(name) => chosen("columns.title", name)?,
+        None => match guessed(&TITLE_COLUMNS) {
+            Some(index) => index,
+            None if !headers.is_empty() => 0,
+            None => return Ok(preview),
+        },
+    };
+    let completed = match columns.completed.as_deref() {
+        Some(name) => Some(chosen("columns.completed", name)?),
+        None => guessed(&COMPLETED_COLUMNS),
+    };
+    preview.mapping = CsvColumns {
+        title: Some(headers[title].clone()),
+        completed: completed.map(|index| headers[index].clone()),
+        chosen: true,
+    };
+
+    for (index, record) in reader.records().enumerate() {
+        // The header is row 1
+        let row = index + 2;
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                preview.skipped.push(format!("Row {row}: {e}"));
+                continue;
+            }
+        };
+
+        match validate_title(record.get(title).unwrap_or_default()) {
+            Ok(title) => preview.todos.push(ImportedTodo {
+                title,
+                completed: completed
+                    .and_then(|column| record.get(column))
+                    .is_some_and(|value| TRUTHY.iter().any(|t| t.eq_ignore_ascii_case(value))),
+                ..Default::default()
+            }),
+            Err(_) => preview.skipped.push(format!("Row {row}: no title")),
+        }
+    }
+    Ok(preview)
+}
+
+/// The JSON export, an array of todos, or an object holding one under
+/// `todos`. Only `title` and `completed` are read.
+pub fn json(content: &str) -> Result<ImportPreview, AppError> {
+    let not_a_list = || AppError::validation("content", "Expected a JSON array of todos.");
+
+    let value: Value = serde_json::from_str(content)
+        .map_err(|e| AppError::validation("content", &format!("Not valid JSON: {e}")))?;
+    let items = match value {
+        Value::Array(items) => items,
+        Value::Object(mut object) => match object.remove("todos") {
+            Some(Value::Array(items)) => items,
+            _ => return Err(not_a_list()),
+        },
+        _ => return Err(not_a_list()),
+    };
+
+    let mut preview = ImportPreview::default();
+    for (index, item) in items.iter().enumerate() {
+        match item.get("title").and_then(Value::as_str).map(validate_title) {
+            Some(Ok(title)) => preview.todos.push(ImportedTodo {
+                title,
+                completed: item.get("completed").and_then(Value::as_bool).unwrap_or(false),
+                ..Default::default()
+            }),
+            _ => preview.skipped.push(format!("Item {}: no title", index + 1)),
+        }
+    }
+    Ok(preview)
+}
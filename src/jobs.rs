@@ -0,0 +1,187 @@
+//! In-process scheduler for periodic housekeeping.
+
+use crate::backup;
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::JobStatus;
+use crate::webhooks::delivery;
+use chrono::Utc;
+use futures::future::BoxFuture;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// Why a job run failed; shown on the admin job status.
+pub type JobError = Box<dyn std::error::Error + Send + Sync>;
+
+type JobFn = Box<dyn Fn(Database) -> BoxFuture<'static, Result<String, JobError>> + Send + Sync>;
+
+pub struct Job {
+    pub name: &'static str,
+    pub interval: Duration,
+    run: JobFn,
+}
+
+impl Job {
+    pub fn new<F, Fut>(name: &'static str, interval: Duration, run: F) -> Self
+    where
+        F: Fn(Database) -> Fut + Send + Sync + 'static,
+        Fut: std::future::Future<Output = Result<String, JobError>> + Send + 'static,
+    {
+        Self {
+            name,
+            interval,
+            run: Box::new(move |db| Box::pin(run(db))),
+        }
+    }
+}
+
+/// The housekeeping jobs the server runs with `config`.
+pub fn default_jobs(config: &Config) -> Vec<Job> {
+    let mut jobs = vec![
+        Job::new(
+            "purge_expired_sessions",
+            Duration::from_secs(60 * 60),
+            |db| async move {
+                let purged = db.purge_expired_sessions().await?;
+                Ok(format!("purged {purged} expired sessions"))
+            },
+        ),
+        Job::new(
+            "deliver_webhooks",
+            Duration::from_secs(5),
+            |db| async move { Ok(delivery::deliver_due(&db, delivery::Destinations::Public).await?) },
+        ),
+        Job::new(
+            "purge_webhook_deliveries",
+            Duration::from_secs(60 * 60),
+            |db| async move {
+                let before = Utc::now() - chrono::Duration::days(delivery::RETENTION_DAYS);
+                let purged = db.purge_webhook_deliveries(before).await?;
+                Ok(format!("purged {purged} finished webhook deliveries"))
+            },
+        ),
+        Job::new(
+            "optimize_database",
+            Duration::from_secs(24 * 60 * 60),
+            |db| async move {
+                db.optimize().await?;
+                Ok("optimized".to_string())
+            },
+        ),
+    ];
+
+    if config.backup.directory.is_some() {
+        let backup = config.backup.clone();
+        jobs.push(Job::new("backup_database", backup.interval, move |db| {
+            let backup = backup.clone();
+            async move {
+                let written = backup::create(&db, &backup).await?;
+                Ok(format!("wrote {}", written.file_name))
+            }
+        }));
+    }
+    jobs
+}
+
+/// Handle to the running jobs, provided as context so the admin endpoint can
+/// report on them.
+#[derive(Clone)]
+pub struct JobRunner {
+    statuses: Arc<Mutex<Vec<JobStatus>>>,
+}
+
+impl JobRunner {
+    /// Spawns one task per job. Each runs immediately and then on its
+    /// interval until `shutdown` flips to `true`; the returned handle
+    /// resolves once every job has stopped.
+    pub fn start(
+        db: Database,
+        jobs: Vec<Job>,
+        shutdown: watch::Receiver<bool>,
+    ) -> (Self, JoinHandle<()>) {
+        let statuses = Arc::new(Mutex::new(
+            jobs.iter()
+                .map(|job| JobStatus {
+                    name: job.name.to_string(),
+                    interval_secs: job.interval.as_secs(),
+                    runs: 0,
+                    failures: 0,
+                    last_run_at: None,
+                    last_duration_ms: None,
+                    last_result: None,
+                })
+                .collect(),
+        ));
+
+        let tasks: Vec<_> = jobs
+            .into_iter()
+            .enumerate()
+            .map(|(index, job)| {
+                tokio::spawn(run_job(
+                    index,
+                    job,
+                    db.clone(),
+                    statuses.clone(),
+                    shutdown.clone(),
+                ))
+            })
+            .collect();
+
+        let handle = tokio::spawn(async move {
+            for task in tasks {
+                let _ = task.await;
+            }
+        });
+
+        (Self { statuses }, handle)
+    }
+
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.statuses.lock().unwrap().clone()
+    }
+}
+
+async fn run_job(
+    index: usize,
+    job: Job,
+    db: Database,
+    statuses: Arc<Mutex<Vec<JobStatus>>>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let mut interval = tokio::time::interval(job.interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.changed() => break,
+        }
+        if *shutdown.borrow() {
+            break;
+        }
+
+        let started = Instant::now();
+        let result = (job.run)(db.clone()).await;
+        let elapsed = started.elapsed();
+
+        if let Err(e) = &result {
+            tracing::error!(job = job.name, error = %e, "job failed");
+        }
+
+        let mut statuses = statuses.lock().unwrap();
+        let status = &mut statuses[index];
+        status.runs += 1;
+        status.last_run_at = Some(Utc::now().format("%Y-%m-%d %H:%M:%S").to_string());
+        status.last_duration_ms = Some(elapsed.as_millis() as u64);
+        status.last_result = Some(match result {
+            Ok(summary) => summary,
+            Err(e) => {
+                status.failures += 1;
+                format!("error: {e}")
+            }
+        });
+    }
+}
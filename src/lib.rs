@@ -1,10 +1,39 @@
+pub mod admin;
+pub mod api_tokens;
 pub mod app;
+pub mod calendar;
 pub mod models;
+pub mod offline;
 pub mod components;
+pub mod csrf;
+pub mod error;
+pub mod events;
+pub mod export;
+pub mod import;
 
+#[cfg(feature = "ssr")]
+pub mod backup;
+#[cfg(feature = "ssr")]
+pub mod cli;
+#[cfg(feature = "todo-cli")]
+pub mod client;
+#[cfg(feature = "ssr")]
+pub mod config;
 #[cfg(feature = "ssr")]
 pub mod database;
+#[cfg(feature = "ssr")]
+pub mod jobs;
+#[cfg(feature = "ssr")]
+pub mod metrics;
+#[cfg(feature = "ssr")]
+pub mod rest;
+#[cfg(feature = "ssr")]
+pub mod telemetry;
+#[cfg(feature = "test-util")]
+pub mod testing;
 pub mod server_functions;
+pub mod store;
+pub mod webhooks;
 pub mod auth;
 
 #[cfg(feature = "hydrate")]
@@ -12,5 +41,6 @@ pub mod auth;
 pub fn hydrate() {
     use crate::app::*;
     console_error_panic_hook::set_once();
+    offline::register_service_worker();
     leptos::mount::hydrate_body(App);
 }
@@ -2,49 +2,162 @@
 #[tokio::main]
 async fn main() {
     use axum::Router;
-    use leptos::logging::log;
+    use clap::Parser;
     use leptos::prelude::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
-    use sqlx::SqlitePool;
     use todo_leptos::app::*;
-    use todo_leptos::database::create_pool;
+    use todo_leptos::auth::middleware::resolve_session;
+    use todo_leptos::calendar;
+    use todo_leptos::cli::{self, Command};
+    use todo_leptos::config::{Args, Config};
+    use todo_leptos::csrf::middleware::csrf_protection;
+    use todo_leptos::database::Database;
+    use todo_leptos::events;
+    use todo_leptos::export;
+    use todo_leptos::jobs::{self, JobRunner};
+    use todo_leptos::metrics::Metrics;
+    use todo_leptos::rest;
+    use todo_leptos::telemetry;
+
+    let mut args = Args::parse();
+    let command = args.command.take().unwrap_or(Command::Serve);
 
     // Load environment variables from .env file
     dotenvy::dotenv().ok();
 
     let conf = get_configuration(None).unwrap();
-    let addr = conf.leptos_options.site_addr;
-    let leptos_options = conf.leptos_options;
+    let mut leptos_options = conf.leptos_options;
+
+    let config = match Config::from_process(&args, leptos_options.env == leptos::config::Env::PROD) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(2);
+        }
+    };
+
+    telemetry::init(&config.log);
+
+    let result = match command {
+        Command::Serve => None,
+        Command::Restore { backup } => Some(cli::restore(&config, &backup, &mut std::io::stdout()).await),
+        Command::Admin(command) => {
+            Some(cli::run(command, &config, &mut std::io::stdin().lock(), &mut std::io::stdout()).await)
+        }
+    };
+    if let Some(result) = result {
+        if let Err(e) = result {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let pool = create_pool().await.expect("Failed to create database pool");
+    if let Some(address) = config.address {
+        leptos_options.site_addr = address;
+    }
+    let addr = leptos_options.site_addr;
+
+    let metrics = Metrics::new();
+    let db = Database::connect(&config.database).await.expect("Failed to connect to the database");
+    let db = metrics.meter(db);
+    let session_config = config.session.clone();
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let (job_runner, jobs_handle) = JobRunner::start(db.clone(), jobs::default_jobs(&config), shutdown_rx.clone());
 
     
     let routes = generate_route_list(App);
 
     
 
-    let app = Router::new()
+    let mut app = Router::new()
+        .nest("/api/v1", rest::router(db.clone(), config.auth.clone()))
+        .merge(rest::docs())
+        .merge(calendar::feed::router(db.clone()))
+        .merge(calendar::caldav::router(db.clone()))
+        .route(
+            events::EVENTS_PATH,
+            axum::routing::get(events::event_stream).with_state(shutdown_rx.clone()),
+        )
+        .route(export::EXPORT_PATH, axum::routing::get(export::export).with_state(db.clone()))
         .leptos_routes_with_context(
             &leptos_options,
             routes,
             {
-                let pool = pool.clone();
-                move || provide_context(pool.clone())
+                let db = db.clone();
+                let config = config.clone();
+                let session_config = session_config.clone();
+                let job_runner = job_runner.clone();
+                move || {
+                    provide_context(db.clone());
+                    provide_context(config.clone());
+                    provide_context(session_config.clone());
+                    provide_context(job_runner.clone());
+                }
             },
             {
                 let leptos_options = leptos_options.clone();
                 move || shell(leptos_options.clone())
             },
         )
+        .route_layer(axum::middleware::from_fn(telemetry::server_fn_span))
+        .route_layer(axum::middleware::from_fn_with_state(
+            (db.clone(), session_config.clone()),
+            resolve_session,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(
+            session_config.clone(),
+            csrf_protection,
+        ))
         .fallback(leptos_axum::file_and_error_handler(shell))
         .with_state(leptos_options);
+    if let Some(token) = config.metrics.token.clone() {
+        app = app.merge(metrics.router(db.clone(), token));
+    }
+    let app = telemetry::trace_requests(metrics.track_requests(app));
 
     // run our app with hyper
-    log!("listening on http://{}", &addr);
+    tracing::info!("listening on http://{addr}");
     let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
     axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            // Ends event streams, which would otherwise keep the server
+            // waiting, and stops jobs from starting new runs
+            let _ = shutdown_tx.send(true);
+        })
         .await
         .unwrap();
+
+    // Let running jobs finish before the database goes away
+    let _ = jobs_handle.await;
+    db.close().await;
+}
+
+#[cfg(feature = "ssr")]
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
 
 #[cfg(not(feature = "ssr"))]
@@ -0,0 +1,236 @@
+//! Prometheus metrics, served at [`METRICS_PATH`] in the text format to
+//! scrapers that send the configured bearer token.
+//!
+//! Requests and server function calls are counted as they are answered,
+//! and database operations timed by [`Database::metered`]. The connection
+//! pool and business gauges are read from the database when `/metrics` is
+//! scraped, so they're never stale.
+
+use crate::csrf::middleware::constant_time_eq;
+use crate::database::Database;
+use crate::telemetry::ServerFnCall;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use chrono::Utc;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Every metric name starts with this.
+const NAMESPACE: &str = "todo_leptos";
+
+/// The app's metrics, cheap to clone. Each instance has its own registry.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    server_fn_calls: IntCounterVec,
+    server_fn_errors: IntCounterVec,
+    db_operation_duration: HistogramVec,
+    db_connections: IntGaugeVec,
+    active_sessions: IntGauge,
+    todos_created_today: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        fn register<M: prometheus::core::Collector + Clone + 'static>(registry: &Registry, metric: M) -> M {
+            registry
+                .register(Box::new(metric.clone()))
+                .expect("metric names are unique");
+            metric
+        }
+        let opts = |name: &str, help: &str| Opts::new(name, help).namespace(NAMESPACE);
+        let histogram = |name: &str, help: &str| HistogramOpts::new(name, help).namespace(NAMESPACE);
+
+        Self {
+            http_requests: register(
+                &registry,
+                IntCounterVec::new(
+                    opts("http_requests_total", "HTTP requests answered"),
+                    &["method", "route", "status"],
+                )
+                .unwrap(),
+            ),
+            http_request_duration: register(
+                &registry,
+                HistogramVec::new(
+                    histogram("http_request_duration_seconds", "Time to answer HTTP requests"),
+                    &["method", "route"],
+                )
+                .unwrap(),
+            ),
+            server_fn_calls: register(
+                &registry,
+                IntCounterVec::new(opts("server_fn_calls_total", "Server function calls"), &["name"]).unwrap(),
+            ),
+            server_fn_errors: register(
+                &registry,
+                IntCounterVec::new(
+                    opts(
+                        "server_fn_errors_total",
+                        "Server function calls that returned an error, by its status code",
+                    ),
+                    &["name", "status"],
+                )
+                .unwrap(),
+            ),
+            db_operation_duration: register(
+                &registry,
+                HistogramVec::new(
+                    histogram("db_operation_duration_seconds", "Time taken by database operations"),
+                    &["operation"],
+                )
+                .unwrap(),
+            ),
+            db_connections: register(
+                &registry,
+                IntGaugeVec::new(
+                    opts(
+                        "db_connections",
+                        "Database connections by pool and state: idle, in_use or max",
+                    ),
+                    &["pool", "state"],
+                )
+                .unwrap(),
+            ),
+            active_sessions: register(
+                &registry,
+                IntGauge::with_opts(opts("active_sessions", "Sign-in sessions that haven't expired")).unwrap(),
+            ),
+            todos_created_today: register(
+                &registry,
+                IntGauge::with_opts(opts("todos_created_today", "Todos created since midnight UTC")).unwrap(),
+            ),
+            registry,
+        }
+    }
+
+    /// `db`, with every operation timed into these metrics.
+    pub fn meter(&self, db: Database) -> Database {
+        db.metered(self.db_operation_duration.clone())
+    }
+
+    /// Counts and times every request of `router` by its route, and the
+    /// server function calls among them by name.
+    pub fn track_requests<S>(&self, router: Router<S>) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        router.layer(axum::middleware::from_fn_with_state(self.clone(), track))
+    }
+
+    /// A router serving these metrics at [`METRICS_PATH`] to requests
+    /// carrying `Authorization: Bearer <token>`.
+    pub fn router<S>(&self, db: Database, token: String) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        Router::new()
+            .route(METRICS_PATH, get(serve).with_state((self.clone(), db)))
+            .route_layer(axum::middleware::from_fn_with_state(Arc::<str>::from(token), require_token))
+    }
+
+    /// Reads the gauges that come from the database.
+    pub async fn refresh(&self, db: &Database) -> Result<(), sqlx::Error> {
+        for pool in db.pool_stats() {
+            let in_use = i64::from(pool.size) - pool.idle as i64;
+            for (state, value) in [
+                ("idle", pool.idle as i64),
+                ("in_use", in_use),
+                ("max", i64::from(pool.max_connections)),
+            ] {
+                self.db_connections.with_label_values(&[pool.name, state]).set(value);
+            }
+        }
+
+        self.active_sessions.set(db.count_active_sessions().await?);
+        let midnight = Utc::now().date_naive().and_time(chrono::NaiveTime::MIN).and_utc();
+        self.todos_created_today
+            .set(db.count_todos_created_since(midnight).await?);
+        Ok(())
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("writing to a Vec doesn't fail");
+        String::from_utf8(buffer).expect("the text format is UTF-8")
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+async fn track(State(metrics): State<Metrics>, request: Request, next: Next) -> Response {
+    let method = request.method().clone();
+    // Unrouted requests, mostly static files, would each get their own
+    // series if labelled by path
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("fallback".to_string(), |path| path.as_str().to_string());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    metrics
+        .http_request_duration
+        .with_label_values(&[method.as_str(), &route])
+        .observe(started.elapsed().as_secs_f64());
+    metrics
+        .http_requests
+        .with_label_values(&[method.as_str(), &route, response.status().as_str()])
+        .inc();
+    if let Some(call) = response.extensions().get::<ServerFnCall>() {
+        metrics.server_fn_calls.with_label_values(&[call.name]).inc();
+        if let Some(status) = call.error {
+            metrics
+                .server_fn_errors
+                .with_label_values(&[call.name, &status.to_string()])
+                .inc();
+        }
+    }
+    response
+}
+
+/// Turns away scrapes without the token before they reach the database.
+async fn require_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let sent = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !sent.is_some_and(|sent| constant_time_eq(sent.trim(), &token)) {
+        return (StatusCode::UNAUTHORIZED, [(header::WWW_AUTHENTICATE, "Bearer")]).into_response();
+    }
+    next.run(request).await
+}
+
+/// `GET /metrics`: everything in [`Metrics`], for Prometheus to scrape.
+pub async fn serve(State((metrics, db)): State<(Metrics, Database)>) -> Response {
+    if let Err(e) = metrics.refresh(&db).await {
+        tracing::error!(error = %e, "failed to read metrics from the database");
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        metrics.render(),
+    )
+        .into_response()
+}
@@ -3,47 +3,61 @@ use sqlx::FromRow;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[cfg_attr(feature = "ssr", derive(FromRow))]
+#[cfg_attr(feature = "ssr", derive(FromRow, utoipa::ToSchema))]
 pub struct User {
     pub id: i64,
     pub username: String,
     pub email: String,
     #[serde(skip)]
     pub password_hash: String,
+    pub is_admin: bool,
     pub created_at: String,
+    /// When an operator disabled the account, if they did.
+    pub disabled_at: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(feature = "ssr", derive(FromRow))]
 pub struct Session {
+    /// SHA-256 of the session token; the token itself only lives in the cookie.
     pub id: String,
     pub user_id: i64,
     pub created_at: String,
     pub expires_at: String,
+    pub absolute_expires_at: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[cfg_attr(feature = "ssr", derive(FromRow))]
+#[cfg_attr(feature = "ssr", derive(FromRow, utoipa::ToSchema))]
 pub struct Todo {
     pub id: i64,
     pub title: String,
     pub completed: bool,
     pub created_at: String,
     pub updated_at: String,
+    /// Bumped on every change; ETags and conditional writes are based on it.
+    pub version: i64,
     pub user_id: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct CreateTodo {
     pub title: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A partial update; fields left as `None` keep their current value.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct UpdateTodo {
-    pub completed: bool,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub completed: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
 pub struct RegisterUser {
     pub username: String,
     pub email: String,
@@ -55,3 +69,324 @@ pub struct LoginUser {
     pub username: String,
     pub password: String,
 }
+
+/// What a personal access token may do. Browser sessions can do everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    Read,
+    Write,
+}
+
+impl TokenScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(TokenScope::Read),
+            "write" => Some(TokenScope::Write),
+            _ => None,
+        }
+    }
+
+    /// Whether a token with this scope may perform an action needing `required`.
+    pub fn allows(&self, required: TokenScope) -> bool {
+        *self == TokenScope::Write || required == TokenScope::Read
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct ApiToken {
+    pub id: i64,
+    pub name: String,
+    pub scope: TokenScope,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct CreateApiToken {
+    pub name: String,
+    pub scope: TokenScope,
+    /// `None` for a token that never expires.
+    pub expires_in_days: Option<i64>,
+}
+
+/// A freshly created token. `token` is the only time the secret is available.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct NewApiToken {
+    pub token: String,
+    pub api_token: ApiToken,
+}
+
+/// A username and password exchanged for a new token, by clients that
+/// can't hold a session such as `todo-cli`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[cfg_attr(feature = "ssr", derive(utoipa::ToSchema))]
+pub struct PasswordTokenRequest {
+    pub username: String,
+    pub password: String,
+    #[serde(flatten)]
+    pub token: CreateApiToken,
+}
+
+/// Todo changes a webhook can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    #[serde(rename = "todo.created")]
+    TodoCreated,
+    #[serde(rename = "todo.completed")]
+    TodoCompleted,
+    #[serde(rename = "todo.deleted")]
+    TodoDeleted,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 3] = [
+        WebhookEvent::TodoCreated,
+        WebhookEvent::TodoCompleted,
+        WebhookEvent::TodoDeleted,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::TodoCreated => "todo.created",
+            WebhookEvent::TodoCompleted => "todo.completed",
+            WebhookEvent::TodoDeleted => "todo.deleted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.as_str() == value)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CreateWebhook {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+/// A freshly created webhook. `secret` is the only time the signing key is
+/// shown.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewWebhook {
+    pub secret: String,
+    pub webhook: Webhook,
+}
+
+/// One attempt-tracked delivery of an event to a webhook.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub url: String,
+    pub event: String,
+    /// `pending`, `delivered` or `failed` (retries exhausted).
+    pub status: String,
+    pub attempts: i64,
+    pub last_status_code: Option<i64>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+}
+
+/// A user's secret iCalendar feed. The feed URL is only shown when created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarFeed {
+    pub created_at: String,
+}
+
+/// File formats todos can be imported from and exported to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TodoFormat {
+    #[default]
+    TodoTxt,
+    Csv,
+    Markdown,
+    /// The app's own JSON export: an array of todos.
+    Json,
+}
+
+impl TodoFormat {
+    pub const ALL: [TodoFormat; 4] = [
+        TodoFormat::TodoTxt,
+        TodoFormat::Csv,
+        TodoFormat::Markdown,
+        TodoFormat::Json,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TodoFormat::TodoTxt => "todo_txt",
+            TodoFormat::Csv => "csv",
+            TodoFormat::Markdown => "markdown",
+            TodoFormat::Json => "json",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.as_str() == value)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TodoFormat::TodoTxt => "todo.txt",
+            TodoFormat::Csv => "CSV",
+            TodoFormat::Markdown => "Markdown checklist",
+            TodoFormat::Json => "JSON",
+        }
+    }
+
+    /// File name extension of an export in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TodoFormat::TodoTxt => "txt",
+            TodoFormat::Csv => "csv",
+            TodoFormat::Markdown => "md",
+            TodoFormat::Json => "json",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            TodoFormat::TodoTxt => "text/plain; charset=utf-8",
+            TodoFormat::Csv => "text/csv; charset=utf-8",
+            TodoFormat::Markdown => "text/markdown; charset=utf-8",
+            TodoFormat::Json => "application/json",
+        }
+    }
+
+    /// Guesses the format from a file name's extension.
+    pub fn from_filename(name: &str) -> Option<Self> {
+        let extension = name.rsplit_once('.')?.1.to_ascii_lowercase();
+        match extension.as_str() {
+            "txt" => Some(TodoFormat::TodoTxt),
+            "csv" => Some(TodoFormat::Csv),
+            "md" | "markdown" => Some(TodoFormat::Markdown),
+            "json" => Some(TodoFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Which CSV columns, by header, hold a todo's title and completion state.
+/// Unless `chosen`, columns left unset are guessed from the header names.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CsvColumns {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub completed: Option<String>,
+    #[serde(default)]
+    pub chosen: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRequest {
+    pub format: TodoFormat,
+    pub content: String,
+    #[serde(default)]
+    pub columns: CsvColumns,
+    /// Only parse and preview, without creating anything.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A todo read from an import file. Priority, due date, contexts and
+/// projects come from todo.txt; they're shown in the preview but only the
+/// title (which keeps them as written) and completion state are stored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportedTodo {
+    pub title: String,
+    pub completed: bool,
+    pub priority: Option<char>,
+    pub due: Option<String>,
+    pub contexts: Vec<String>,
+    pub projects: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportPreview {
+    pub todos: Vec<ImportedTodo>,
+    /// Lines or rows that couldn't be read, with the reason.
+    pub skipped: Vec<String>,
+    /// The CSV header, for choosing the column mapping.
+    pub columns: Vec<String>,
+    /// The CSV columns that were read, after guessing any not chosen.
+    pub mapping: CsvColumns,
+    /// Whether the todos were created, i.e. this wasn't a dry run.
+    pub imported: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobStatus {
+    pub name: String,
+    pub interval_secs: u64,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_run_at: Option<String>,
+    pub last_duration_ms: Option<u64>,
+    /// Summary of the last run, or the error it failed with.
+    pub last_result: Option<String>,
+}
+
+/// A backup file written to the server's backup directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Backup {
+    pub file_name: String,
+    pub size_bytes: u64,
+    pub created_at: String,
+}
+
+/// A change to one of a user's todos, pushed to their open tabs over
+/// `/api/events`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TodoEvent {
+    Created { todo: Todo },
+    Updated { todo: Todo },
+    Deleted { id: i64 },
+    /// Events were missed (the connection dropped or fell behind); the
+    /// client should refetch its list.
+    Resync,
+}
+
+impl TodoEvent {
+    /// Patches a newest-first todo list in place.
+    pub fn apply(&self, todos: &mut Vec<Todo>) {
+        match self {
+            TodoEvent::Created { todo } => {
+                if !todos.iter().any(|t| t.id == todo.id) {
+                    todos.insert(0, todo.clone());
+                }
+            }
+            TodoEvent::Updated { todo } => {
+                if let Some(existing) = todos.iter_mut().find(|t| t.id == todo.id) {
+                    *existing = todo.clone();
+                }
+            }
+            TodoEvent::Deleted { id } => todos.retain(|t| t.id != *id),
+            TodoEvent::Resync => {}
+        }
+    }
+}
@@ -0,0 +1,51 @@
+// Minimal promise wrapper around IndexedDB, imported by `offline.rs`.
+// Values are stored as JSON strings under string keys in a single store.
+// Also clears the pages `sw.js` cached, which can hold a user's todos.
+
+const DB_NAME = "todo-leptos";
+const STORE = "kv";
+
+function openDb() {
+    return new Promise((resolve, reject) => {
+        const request = indexedDB.open(DB_NAME, 1);
+        request.onupgradeneeded = () => request.result.createObjectStore(STORE);
+        request.onsuccess = () => resolve(request.result);
+        request.onerror = () => reject(request.error);
+    });
+}
+
+async function withStore(mode, run) {
+    const db = await openDb();
+    return new Promise((resolve, reject) => {
+        const tx = db.transaction(STORE, mode);
+        const request = run(tx.objectStore(STORE));
+        tx.oncomplete = () => {
+            db.close();
+            resolve(request.result ?? null);
+        };
+        tx.onerror = () => reject(tx.error);
+    });
+}
+
+export function idb_get(key) {
+    return withStore("readonly", (store) => store.get(key));
+}
+
+export function idb_set(key, value) {
+    return withStore("readwrite", (store) => store.put(value, key));
+}
+
+export function idb_clear() {
+    return withStore("readwrite", (store) => store.clear());
+}
+
+// Deletes every cache of this origin, the service worker's included.
+// `caches` is missing outside secure contexts, where there's no worker either.
+export async function caches_clear() {
+    if (typeof caches === "undefined") {
+        return null;
+    }
+    const keys = await caches.keys();
+    await Promise.all(keys.map((key) => caches.delete(key)));
+    return null;
+}
@@ -0,0 +1,140 @@
+//! Offline support for the hydrated client.
+//!
+//! The current user, the last-known todo list and any mutations made while
+//! offline are kept in IndexedDB (see `offline.js`), so the app keeps working
+//! on a flaky connection and catches up once it's back. On the server every
+//! function here is a no-op.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+pub const USER_KEY: &str = "user";
+pub const TODOS_KEY: &str = "todos";
+pub const QUEUE_KEY: &str = "queue";
+
+/// A todo change made while offline, waiting to be sent to the server.
+///
+/// Toggles and deletes remember the `updated_at` they were made against so
+/// replaying them can't silently overwrite a change made on another device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum QueuedMutation {
+    Add {
+        temp_id: i64,
+        title: String,
+    },
+    Toggle {
+        id: i64,
+        completed: bool,
+        expected_updated_at: String,
+    },
+    Delete {
+        id: i64,
+        expected_updated_at: String,
+    },
+}
+
+#[cfg(feature = "hydrate")]
+mod idb {
+    use wasm_bindgen::prelude::*;
+
+    #[wasm_bindgen(module = "/src/offline.js")]
+    extern "C" {
+        #[wasm_bindgen(catch)]
+        pub async fn idb_get(key: &str) -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(catch)]
+        pub async fn idb_set(key: &str, value: &str) -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(catch)]
+        pub async fn idb_clear() -> Result<JsValue, JsValue>;
+        #[wasm_bindgen(catch)]
+        pub async fn caches_clear() -> Result<JsValue, JsValue>;
+    }
+}
+
+/// Whether the browser believes it has a network connection.
+#[cfg(feature = "hydrate")]
+pub fn is_online() -> bool {
+    web_sys::window().is_none_or(|window| window.navigator().on_line())
+}
+
+#[cfg(not(feature = "hydrate"))]
+pub fn is_online() -> bool {
+    true
+}
+
+/// Reads a value saved with [`save`].
+#[cfg(feature = "hydrate")]
+pub async fn load<T: DeserializeOwned>(key: &str) -> Option<T> {
+    // JS futures aren't `Send`, but resources need theirs to be; in the
+    // browser everything runs on one thread anyway
+    let value = send_wrapper::SendWrapper::new(idb::idb_get(key)).await;
+    serde_json::from_str(&value.ok()?.as_string()?).ok()
+}
+
+#[cfg(not(feature = "hydrate"))]
+pub async fn load<T: DeserializeOwned>(_key: &str) -> Option<T> {
+    None
+}
+
+/// Saves `value` under `key` in the background.
+#[cfg(feature = "hydrate")]
+pub fn save<T: Serialize>(key: &'static str, value: &T) {
+    let Ok(value) = serde_json::to_string(value) else {
+        return;
+    };
+    leptos::task::spawn_local(async move {
+        if let Err(e) = idb::idb_set(key, &value).await {
+            leptos::logging::warn!("couldn't save {key} for offline use: {e:?}");
+        }
+    });
+}
+
+#[cfg(not(feature = "hydrate"))]
+pub fn save<T: Serialize>(_key: &'static str, _value: &T) {}
+
+/// Forgets everything stored for offline use, e.g. on logout: the data in
+/// IndexedDB and the pages the service worker cached, which were rendered
+/// for the signed-in user.
+#[cfg(feature = "hydrate")]
+pub fn clear() {
+    leptos::task::spawn_local(async {
+        let _ = idb::idb_clear().await;
+        let _ = idb::caches_clear().await;
+    });
+}
+
+#[cfg(not(feature = "hydrate"))]
+pub fn clear() {}
+
+/// Calls `f` whenever the browser regains its connection, until the calling
+/// reactive owner is cleaned up.
+#[cfg(feature = "hydrate")]
+pub fn on_reconnect(f: impl Fn() + 'static) {
+    use leptos::prelude::on_cleanup;
+    use send_wrapper::SendWrapper;
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    let Some(window) = web_sys::window() else {
+        return;
+    };
+    let listener = Closure::<dyn Fn()>::new(f);
+    let _ = window.add_event_listener_with_callback("online", listener.as_ref().unchecked_ref());
+
+    let subscription = SendWrapper::new((window, listener));
+    on_cleanup(move || {
+        let (window, listener) = subscription.take();
+        let _ = window
+            .remove_event_listener_with_callback("online", listener.as_ref().unchecked_ref());
+    });
+}
+
+#[cfg(not(feature = "hydrate"))]
+pub fn on_reconnect(_f: impl Fn() + 'static) {}
+
+/// Registers `public/sw.js`, which serves the app shell while offline.
+#[cfg(feature = "hydrate")]
+pub fn register_service_worker() {
+    if let Some(window) = web_sys::window() {
+        let container = window.navigator().service_worker();
+        let _ = container.register("/sw.js");
+    }
+}
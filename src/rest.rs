@@ -0,0 +1,397 @@
+//! Versioned JSON API under `/api/v1`, for clients that can't speak the
+//! server function encodings.
+//!
+//! State-changing requests go through the CSRF check like the rest of
+//! `/api`, which passes those with a bearer token. `/tokens` and `/users`
+//! also work without one, as long as no session cookie is sent.
+
+use crate::api_tokens::issue_api_token;
+use crate::auth::{register_user, CurrentUser};
+use crate::config::AuthConfig;
+use crate::database::{hash_token, Database};
+use crate::error::AppError;
+use crate::models::{
+    ApiToken, CreateApiToken, CreateTodo, LoginUser, NewApiToken, PasswordTokenRequest, RegisterUser, Todo, TokenScope,
+    UpdateTodo, User,
+};
+use crate::server_functions::validate_title;
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{IntoParams, Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+/// Where the generated OpenAPI document is served.
+pub const OPENAPI_PATH: &str = "/api/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Todo Leptos API", description = "Versioned JSON API for todos and users."),
+    servers((url = "/api/v1")),
+    paths(
+        list_todos,
+        create_todo,
+        get_todo,
+        update_todo,
+        delete_todo,
+        get_user,
+        create_user,
+        create_token
+    ),
+    components(schemas(
+        Todo,
+        CreateTodo,
+        UpdateTodo,
+        User,
+        RegisterUser,
+        ApiToken,
+        CreateApiToken,
+        NewApiToken,
+        PasswordTokenRequest,
+        TokenScope,
+        AppError
+    )),
+    modifiers(&BearerAuth),
+    tags((name = "todos"), (name = "users"))
+)]
+pub struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("A personal API token (`tdl_...`)"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::ApiKey(utoipa::openapi::security::ApiKey::Cookie(
+                utoipa::openapi::security::ApiKeyValue::new("session_id"),
+            )),
+        );
+    }
+}
+
+/// Serves the OpenAPI document and a bundled Swagger UI at `/api/docs`.
+pub fn docs<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    SwaggerUi::new("/api/docs")
+        .url(OPENAPI_PATH, ApiDoc::openapi())
+        .into()
+}
+
+pub fn router<S>(db: Database, auth: AuthConfig) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/todos", get(list_todos).post(create_todo))
+        .route(
+            "/todos/{id}",
+            get(get_todo).patch(update_todo).delete(delete_todo),
+        )
+        .route("/user", get(get_user))
+        .route("/users", post(create_user))
+        .route("/tokens", post(create_token))
+        .layer(Extension(auth))
+        .with_state(db)
+}
+
+/// Strong validator for a todo's current representation. It changes with
+/// every write, as the version does.
+pub fn todo_etag(todo: &Todo) -> String {
+    let digest = hash_token(&format!("{}:{}", todo.id, todo.version));
+    format!("\"{}\"", &digest[..16])
+}
+
+/// Whether an `If-Match` header (if any) allows modifying a resource whose
+/// current ETag is `etag`.
+pub(crate) fn if_match_satisfied(headers: &HeaderMap, etag: &str) -> bool {
+    match headers.get(header::IF_MATCH).and_then(|v| v.to_str().ok()) {
+        None => true,
+        Some(value) => value
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate == etag),
+    }
+}
+
+pub(crate) fn if_none_match_satisfied(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|value| {
+            value
+                .split(',')
+                .map(|candidate| candidate.trim().trim_start_matches("W/"))
+                .any(|candidate| candidate == "*" || candidate == etag)
+        })
+}
+
+fn with_etag(status: StatusCode, todo: Todo) -> Response {
+    let etag = todo_etag(&todo);
+    (status, [(header::ETAG, etag)], Json(todo)).into_response()
+}
+
+async fn find_todo(db: &Database, user_id: i64, id: i64) -> Result<Todo, AppError> {
+    db.get_user_todo_by_id(user_id, id)
+        .await?
+        .ok_or(AppError::NotFound)
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct TodoFilter {
+    /// Only return todos with this completion state.
+    pub completed: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos",
+    tag = "todos",
+    params(TodoFilter),
+    responses(
+        (status = 200, description = "The caller's todos", body = [Todo]),
+        (status = 401, description = "Not authenticated", body = AppError),
+    ),
+    security(("bearer" = []), ("session" = []))
+)]
+async fn list_todos(
+    State(db): State<Database>,
+    Extension(current): Extension<CurrentUser>,
+    Query(filter): Query<TodoFilter>,
+) -> Result<Json<Vec<Todo>>, AppError> {
+    let user = current.require(TokenScope::Read)?;
+
+    let todos = db.get_user_todos(user.id).await?;
+    Ok(Json(
+        todos
+            .into_iter()
+            .filter(|todo| filter.completed.is_none_or(|completed| todo.completed == completed))
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/todos",
+    tag = "todos",
+    request_body = CreateTodo,
+    responses(
+        (status = 201, description = "Todo created", body = Todo,
+            headers(("ETag" = String), ("Location" = String))),
+        (status = 401, description = "Not authenticated", body = AppError),
+        (status = 403, description = "Token lacks the write scope", body = AppError),
+        (status = 422, description = "Invalid title", body = AppError),
+    ),
+    security(("bearer" = []), ("session" = []))
+)]
+async fn create_todo(
+    State(db): State<Database>,
+    Extension(current): Extension<CurrentUser>,
+    Json(todo): Json<CreateTodo>,
+) -> Result<Response, AppError> {
+    let user = current.require(TokenScope::Write)?;
+
+    let todo = CreateTodo {
+        title: validate_title(&todo.title)?,
+    };
+    let todo = db.create_user_todo(user.id, todo).await?;
+
+    let location = format!("/api/v1/todos/{}", todo.id);
+    let mut response = with_etag(StatusCode::CREATED, todo);
+    if let Ok(location) = location.parse() {
+        response.headers_mut().insert(header::LOCATION, location);
+    }
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(
+        ("id" = i64, Path, description = "Todo id"),
+        ("If-None-Match" = Option<String>, Header, description = "Return 304 if the ETag matches"),
+    ),
+    responses(
+        (status = 200, description = "The todo", body = Todo, headers(("ETag" = String))),
+        (status = 304, description = "Not modified"),
+        (status = 401, description = "Not authenticated", body = AppError),
+        (status = 404, description = "No such todo", body = AppError),
+    ),
+    security(("bearer" = []), ("session" = []))
+)]
+async fn get_todo(
+    State(db): State<Database>,
+    Extension(current): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let user = current.require(TokenScope::Read)?;
+
+    let todo = find_todo(&db, user.id, id).await?;
+    let etag = todo_etag(&todo);
+    if if_none_match_satisfied(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
+
+    Ok(with_etag(StatusCode::OK, todo))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(
+        ("id" = i64, Path, description = "Todo id"),
+        ("If-Match" = Option<String>, Header, description = "Only update if the ETag matches"),
+    ),
+    request_body = UpdateTodo,
+    responses(
+        (status = 200, description = "The updated todo", body = Todo, headers(("ETag" = String))),
+        (status = 401, description = "Not authenticated", body = AppError),
+        (status = 403, description = "Token lacks the write scope", body = AppError),
+        (status = 404, description = "No such todo", body = AppError),
+        (status = 412, description = "The todo changed since it was read", body = AppError),
+        (status = 422, description = "Invalid title", body = AppError),
+    ),
+    security(("bearer" = []), ("session" = []))
+)]
+async fn update_todo(
+    State(db): State<Database>,
+    Extension(current): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Json(mut update): Json<UpdateTodo>,
+) -> Result<Response, AppError> {
+    let user = current.require(TokenScope::Write)?;
+
+    if let Some(title) = &update.title {
+        update.title = Some(validate_title(title)?);
+    }
+
+    let todo = find_todo(&db, user.id, id).await?;
+    if !if_match_satisfied(&headers, &todo_etag(&todo)) {
+        return Err(AppError::PreconditionFailed);
+    }
+
+    // Only apply the update if nobody changed the todo since we read it
+    let updated = db.update_user_todo(user.id, id, update, Some(todo.version))
+        .await?
+        .ok_or(AppError::PreconditionFailed)?;
+
+    Ok(with_etag(StatusCode::OK, updated))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/todos/{id}",
+    tag = "todos",
+    params(
+        ("id" = i64, Path, description = "Todo id"),
+        ("If-Match" = Option<String>, Header, description = "Only delete if the ETag matches"),
+    ),
+    responses(
+        (status = 204, description = "Todo deleted"),
+        (status = 401, description = "Not authenticated", body = AppError),
+        (status = 403, description = "Token lacks the write scope", body = AppError),
+        (status = 404, description = "No such todo", body = AppError),
+        (status = 412, description = "The todo changed since it was read", body = AppError),
+    ),
+    security(("bearer" = []), ("session" = []))
+)]
+async fn delete_todo(
+    State(db): State<Database>,
+    Extension(current): Extension<CurrentUser>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<StatusCode, AppError> {
+    let user = current.require(TokenScope::Write)?;
+
+    let todo = find_todo(&db, user.id, id).await?;
+    if !if_match_satisfied(&headers, &todo_etag(&todo)) {
+        return Err(AppError::PreconditionFailed);
+    }
+
+    // Likewise, only delete the todo we checked
+    if db.delete_user_todo(user.id, id, Some(todo.version)).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(AppError::PreconditionFailed)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/user",
+    tag = "users",
+    responses(
+        (status = 200, description = "The authenticated user", body = User),
+        (status = 401, description = "Not authenticated", body = AppError),
+    ),
+    security(("bearer" = []), ("session" = []))
+)]
+async fn get_user(Extension(current): Extension<CurrentUser>) -> Result<Json<User>, AppError> {
+    Ok(Json(current.require(TokenScope::Read)?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/users",
+    tag = "users",
+    request_body = RegisterUser,
+    responses(
+        (status = 201, description = "User registered", body = User),
+        (status = 409, description = "Username or email already taken", body = AppError),
+        (status = 422, description = "Invalid registration details", body = AppError),
+    )
+)]
+async fn create_user(
+    State(db): State<Database>,
+    Extension(auth): Extension<AuthConfig>,
+    Json(user_data): Json<RegisterUser>,
+) -> Result<(StatusCode, Json<User>), AppError> {
+    let user = register_user(&db, user_data, auth.bcrypt_cost).await?;
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/tokens",
+    tag = "users",
+    request_body = PasswordTokenRequest,
+    responses(
+        (status = 201, description = "Token created; the secret is only shown now", body = NewApiToken),
+        (status = 401, description = "Wrong username or password", body = AppError),
+        (status = 422, description = "Invalid token name or expiry", body = AppError),
+    )
+)]
+async fn create_token(
+    State(db): State<Database>,
+    Json(request): Json<PasswordTokenRequest>,
+) -> Result<(StatusCode, Json<NewApiToken>), AppError> {
+    let login = LoginUser {
+        username: request.username,
+        password: request.password,
+    };
+    let user = db.authenticate_user(login).await?.ok_or(AppError::Unauthorized)?;
+
+    let token = issue_api_token(&db, user.id, request.token).await?;
+    Ok((StatusCode::CREATED, Json(token)))
+}
@@ -1,85 +1,135 @@
-use crate::models::{CreateTodo, Todo, UpdateTodo};
+use crate::csrf::CsrfClient;
+use crate::error::AppError;
+use crate::models::{CreateTodo, Todo};
 use leptos::prelude::*;
 
 #[cfg(feature = "ssr")]
-use crate::auth::get_current_user;
+use crate::auth::{require_scope, require_user};
 #[cfg(feature = "ssr")]
-use crate::database::queries;
+use crate::models::{TokenScope, UpdateTodo};
 #[cfg(feature = "ssr")]
-use sqlx::SqlitePool;
+use crate::database::Database;
 
-#[server(GetTodos, "/api")]
-pub async fn get_todos() -> Result<Vec<Todo>, ServerFnError> {
-    let pool = expect_context::<SqlitePool>();
+#[server(GetTodos, "/api", client = CsrfClient)]
+pub async fn get_todos() -> Result<Vec<Todo>, AppError> {
+    let db = expect_context::<Database>();
 
-    let current_user = get_current_user().await?;
+    let user = require_user().await?;
 
-    if let Some(user) = current_user {
-        queries::get_user_todos(&pool, user.id)
-            .await
-            .map_err(|e| ServerFnError::ServerError(e.to_string()))
-    } else {
-        Err(ServerFnError::ServerError("Not authenticated".to_string()))
-    }
+    Ok(db.get_user_todos(user.id).await?)
 }
-#[server(GetTodoById,"/api")]
-pub async fn get_todo_by_id(id:i64)->Result<Option<Todo>,ServerFnError>{
-    let pool = expect_context::<SqlitePool>();
-    
-    let current_user = get_current_user().await?;
-    
-    if let Some(user) = current_user {
-        queries::get_user_todo_by_id(&pool, user.id, id)
-            .await
-            .map_err(|e| ServerFnError::ServerError(e.to_string()))
-    } else {
-        Err(ServerFnError::ServerError("Not authenticated".to_string()))
-    }
-    
+
+#[server(GetTodoById, "/api", client = CsrfClient)]
+pub async fn get_todo_by_id(id: i64) -> Result<Todo, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_user().await?;
+
+    db.get_user_todo_by_id(user.id, id)
+        .await?
+        .ok_or(AppError::NotFound)
 }
 
-#[server(AddTodo, "/api")]
-pub async fn add_todo(todo: CreateTodo) -> Result<Todo, ServerFnError> {
-    let pool = expect_context::<SqlitePool>();
+#[server(AddTodo, "/api", client = CsrfClient)]
+pub async fn add_todo(todo: CreateTodo) -> Result<Todo, AppError> {
+    let db = expect_context::<Database>();
 
-    let current_user = get_current_user().await?;
+    let user = require_scope(TokenScope::Write).await?;
 
-    if let Some(user) = current_user {
-        queries::create_user_todo(&pool, user.id, todo)
-            .await
-            .map_err(|e| ServerFnError::ServerError(e.to_string()))
-    } else {
-        Err(ServerFnError::ServerError("Not authenticated".to_string()))
+    let todo = CreateTodo {
+        title: validate_title(&todo.title)?,
+    };
+
+    Ok(db.create_user_todo(user.id, todo).await?)
+}
+
+/// Sets a todo's completion state. With `expected_updated_at` the change is
+/// only made if the todo hasn't been modified since, failing with
+/// `PreconditionFailed` otherwise.
+#[server(ToggleTodo, "/api", client = CsrfClient)]
+pub async fn toggle_todo(
+    id: i64,
+    completed: bool,
+    expected_updated_at: Option<String>,
+) -> Result<Todo, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_scope(TokenScope::Write).await?;
+
+    let expected_version = match &expected_updated_at {
+        Some(expected) => Some(unchanged_version(&db, user.id, id, expected).await?),
+        None => None,
+    };
+    let update = UpdateTodo {
+        completed: Some(completed),
+        ..Default::default()
+    };
+    match db.update_user_todo(user.id, id, update, expected_version).await? {
+        Some(todo) => Ok(todo),
+        // It changed between the check and the update
+        None if expected_version.is_some() => Err(AppError::PreconditionFailed),
+        None => Err(AppError::NotFound),
     }
 }
 
-#[server(ToggleTodo, "/api")]
-pub async fn toggle_todo(id: i64, completed: bool) -> Result<Option<Todo>, ServerFnError> {
-    let pool = expect_context::<SqlitePool>();
+/// Deletes a todo, guarded by `expected_updated_at` like [`toggle_todo`].
+#[server(DeleteTodo, "/api", client = CsrfClient)]
+pub async fn delete_todo(id: i64, expected_updated_at: Option<String>) -> Result<(), AppError> {
+    let db = expect_context::<Database>();
 
-    let current_user = get_current_user().await?;
+    let user = require_scope(TokenScope::Write).await?;
 
-    if let Some(user) = current_user {
-        let update = UpdateTodo { completed };
-        queries::update_user_todo(&pool, user.id, id, update)
-            .await
-            .map_err(|e| ServerFnError::ServerError(e.to_string()))
+    let expected_version = match &expected_updated_at {
+        Some(expected) => Some(unchanged_version(&db, user.id, id, expected).await?),
+        None => None,
+    };
+
+    if db.delete_user_todo(user.id, id, expected_version).await? {
+        Ok(())
+    } else if expected_version.is_some() {
+        Err(AppError::PreconditionFailed)
     } else {
-        Err(ServerFnError::ServerError("Not authenticated".to_string()))
+        Err(AppError::NotFound)
     }
 }
 
-#[server(DeleteTodo, "/api")]
-pub async fn delete_todo(id: i64) -> Result<bool, ServerFnError> {
-    let pool = expect_context::<SqlitePool>();
+/// The version of a todo that is still as the client last saw it, at
+/// `expected_updated_at`. Writes guarded by it fail if the todo changes
+/// after this check. A timestamp that can't be read can't match, so it
+/// fails the same way.
+#[cfg(feature = "ssr")]
+async fn unchanged_version(
+    db: &Database,
+    user_id: i64,
+    id: i64,
+    expected_updated_at: &str,
+) -> Result<i64, AppError> {
+    let expected = parse_timestamp(expected_updated_at).ok_or(AppError::PreconditionFailed)?;
+    let todo = db.get_user_todo_by_id(user_id, id)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    if parse_timestamp(&todo.updated_at) != Some(expected) {
+        return Err(AppError::PreconditionFailed);
+    }
+    Ok(todo.version)
+}
 
-    let current_user = get_current_user().await?;
+/// Reads a timestamp as the backends format them, `2024-05-01 09:30:00`,
+/// or with a `T` between date and time.
+#[cfg(feature = "ssr")]
+fn parse_timestamp(value: &str) -> Option<chrono::NaiveDateTime> {
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"]
+        .iter()
+        .find_map(|format| chrono::NaiveDateTime::parse_from_str(value.trim(), format).ok())
+}
 
-    if let Some(user) = current_user {
-        queries::delete_user_todo(&pool, user.id, id)
-            .await
-            .map_err(|e| ServerFnError::ServerError(e.to_string()))
+/// Trims a todo title, rejecting blank ones.
+#[cfg(feature = "ssr")]
+pub(crate) fn validate_title(title: &str) -> Result<String, AppError> {
+    let title = title.trim();
+    if title.is_empty() {
+        Err(AppError::validation("title", "Title cannot be empty."))
     } else {
-        Err(ServerFnError::ServerError("Not authenticated".to_string()))
+        Ok(title.to_string())
     }
 }
@@ -0,0 +1,336 @@
+//! Client-side view of the current user's todos.
+//!
+//! Mutations are applied to the list straight away and then sent to the
+//! server; the server's answer replaces the optimistic copy, and a failure
+//! puts the previous state back and surfaces the error.
+//!
+//! While offline, mutations are queued instead (see [`crate::offline`]) and
+//! replayed in order once the connection is back.
+
+use crate::error::AppError;
+use crate::models::{CreateTodo, Todo, TodoEvent};
+use crate::offline::{self, QueuedMutation};
+use crate::server_functions::{add_todo, delete_todo, get_todos, toggle_todo};
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How long a failed mutation's error stays on screen.
+const ERROR_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Copy)]
+pub struct TodoStore {
+    /// The list as fetched from the server, patched in place by optimistic
+    /// updates and live events.
+    pub todos: Resource<Result<Vec<Todo>, AppError>>,
+    /// The most recent failed mutation, if it hasn't been dismissed yet.
+    pub error: RwSignal<Option<AppError>>,
+    /// Mutations waiting for the connection to come back.
+    pub queue: RwSignal<Vec<QueuedMutation>>,
+    /// Optimistically added todos get negative ids until the server assigns
+    /// the real one.
+    next_temp_id: StoredValue<i64>,
+    replaying: StoredValue<bool>,
+}
+
+impl TodoStore {
+    pub fn new() -> Self {
+        let store = Self {
+            todos: Resource::new(|| (), |_| get_todos()),
+            error: RwSignal::new(None),
+            queue: RwSignal::new(Vec::new()),
+            next_temp_id: StoredValue::new(-1),
+            replaying: StoredValue::new(false),
+        };
+
+        // Effects only run in the browser, which is the only place there's
+        // anything to persist or restore
+        Effect::new(move |_| match store.todos.get() {
+            Some(Ok(todos)) => offline::save(offline::TODOS_KEY, &todos),
+            Some(Err(AppError::Request(_))) => spawn_local(store.restore_cached_todos()),
+            _ => {}
+        });
+        Effect::new(move |first_run: Option<()>| {
+            let queue = store.queue.get();
+            if first_run.is_some() {
+                offline::save(offline::QUEUE_KEY, &queue);
+            }
+        });
+        Effect::new(move |_| {
+            spawn_local(async move {
+                let queued: Vec<QueuedMutation> =
+                    offline::load(offline::QUEUE_KEY).await.unwrap_or_default();
+                if !queued.is_empty() {
+                    // Anything queued since loading started goes after these
+                    store.queue.update(|queue| {
+                        let newer = std::mem::replace(queue, queued);
+                        queue.extend(newer);
+                    });
+                    store.replay();
+                }
+            });
+        });
+        offline::on_reconnect(move || store.replay());
+
+        store
+    }
+
+    /// Whether `todo` only exists locally so far.
+    pub fn is_pending(todo: &Todo) -> bool {
+        todo.id < 0
+    }
+
+    fn modify(&self, f: impl FnOnce(&mut Vec<Todo>)) {
+        self.todos.update(|todos| {
+            if let Some(Ok(todos)) = todos {
+                f(todos);
+            }
+        });
+    }
+
+    fn find(&self, id: i64) -> Option<(usize, Todo)> {
+        self.todos.with_untracked(|todos| match todos {
+            Some(Ok(todos)) => todos
+                .iter()
+                .position(|todo| todo.id == id)
+                .map(|index| (index, todos[index].clone())),
+            _ => None,
+        })
+    }
+
+    fn fail(&self, error: AppError) {
+        self.error.set(Some(error.clone()));
+        let store = *self;
+        set_timeout(
+            move || {
+                if store.error.get_untracked().as_ref() == Some(&error) {
+                    store.error.set(None);
+                }
+            },
+            ERROR_TIMEOUT,
+        );
+    }
+
+    fn enqueue(&self, mutation: QueuedMutation) {
+        self.queue.update(|queue| queue.push(mutation));
+    }
+
+    /// Shows the last list saved for offline use when the server can't be
+    /// reached.
+    async fn restore_cached_todos(self) {
+        if let Some(todos) = offline::load::<Vec<Todo>>(offline::TODOS_KEY).await {
+            let lowest_id = todos.iter().map(|todo| todo.id).min().unwrap_or(0);
+            self.next_temp_id.set_value(lowest_id.min(0) - 1);
+            self.todos.set(Some(Ok(todos)));
+        }
+    }
+
+    /// Applies a change pushed from the server.
+    pub fn apply(&self, event: TodoEvent) {
+        match event {
+            TodoEvent::Resync => self.todos.refetch(),
+            event => self.modify(|todos| event.apply(todos)),
+        }
+    }
+
+    pub fn add(&self, title: String, user_id: i64) {
+        let temp_id = self.next_temp_id.get_value();
+        self.next_temp_id.set_value(temp_id - 1);
+        self.modify(|todos| {
+            todos.insert(
+                0,
+                Todo {
+                    id: temp_id,
+                    title: title.clone(),
+                    completed: false,
+                    created_at: String::new(),
+                    updated_at: String::new(),
+                    version: 0,
+                    user_id,
+                },
+            )
+        });
+
+        // A failed add removes its placeholder itself, nothing to roll back
+        self.submit(QueuedMutation::Add { temp_id, title }, || {});
+    }
+
+    pub fn toggle(&self, id: i64, completed: bool) {
+        let Some((_, previous)) = self.find(id) else {
+            return;
+        };
+        self.modify(|todos| {
+            if let Some(todo) = todos.iter_mut().find(|todo| todo.id == id) {
+                todo.completed = completed;
+            }
+        });
+
+        let mutation = QueuedMutation::Toggle {
+            id,
+            completed,
+            expected_updated_at: previous.updated_at.clone(),
+        };
+        let store = *self;
+        self.submit(mutation, move || {
+            store.apply(TodoEvent::Updated { todo: previous })
+        });
+    }
+
+    pub fn delete(&self, id: i64) {
+        let Some((index, previous)) = self.find(id) else {
+            return;
+        };
+        self.apply(TodoEvent::Deleted { id });
+
+        let mutation = QueuedMutation::Delete {
+            id,
+            expected_updated_at: previous.updated_at.clone(),
+        };
+        let store = *self;
+        self.submit(mutation, move || {
+            store.modify(|todos| {
+                if !todos.iter().any(|todo| todo.id == id) {
+                    todos.insert(index.min(todos.len()), previous);
+                }
+            })
+        });
+    }
+
+    /// Sends a mutation that has already been applied locally, calling
+    /// `rollback` if the server rejects it.
+    ///
+    /// While offline, or while earlier mutations are still queued, it joins
+    /// the queue instead so changes reach the server in the order they were
+    /// made.
+    fn submit(&self, mutation: QueuedMutation, rollback: impl FnOnce() + 'static) {
+        if !offline::is_online() || !self.queue.with_untracked(Vec::is_empty) {
+            self.enqueue(mutation);
+            self.replay();
+            return;
+        }
+
+        let store = *self;
+        spawn_local(async move {
+            match store.send(&mutation, &mut HashMap::new()).await {
+                Outcome::Sent => {}
+                Outcome::Offline => store.enqueue(mutation),
+                Outcome::Rejected => rollback(),
+            }
+        });
+    }
+
+    /// Sends one mutation and reconciles the list with the server's answer.
+    ///
+    /// `updated` maps todo ids to the `updated_at` the server gave them
+    /// earlier in the same replay, so a todo changed twice while offline
+    /// doesn't conflict with itself.
+    async fn send(&self, mutation: &QueuedMutation, updated: &mut HashMap<i64, String>) -> Outcome {
+        let result = match mutation {
+            QueuedMutation::Add { temp_id, title } => {
+                let temp_id = *temp_id;
+                let result = add_todo(CreateTodo {
+                    title: title.clone(),
+                })
+                .await;
+                if !matches!(result, Err(AppError::Request(_))) {
+                    self.modify(|todos| {
+                        let Some(index) = todos.iter().position(|todo| todo.id == temp_id) else {
+                            return;
+                        };
+                        match &result {
+                            // The live event for this todo may have beaten the response
+                            Ok(todo) if !todos.iter().any(|t| t.id == todo.id) => {
+                                todos[index] = todo.clone()
+                            }
+                            _ => {
+                                todos.remove(index);
+                            }
+                        }
+                    });
+                }
+                result.map(|todo| {
+                    updated.insert(todo.id, todo.updated_at);
+                })
+            }
+            QueuedMutation::Toggle {
+                id,
+                completed,
+                expected_updated_at,
+            } => {
+                let expected = updated.get(id).unwrap_or(expected_updated_at).clone();
+                toggle_todo(*id, *completed, Some(expected))
+                    .await
+                    .map(|todo| {
+                        updated.insert(todo.id, todo.updated_at.clone());
+                        self.apply(TodoEvent::Updated { todo });
+                    })
+            }
+            QueuedMutation::Delete {
+                id,
+                expected_updated_at,
+            } => {
+                let expected = updated.get(id).unwrap_or(expected_updated_at).clone();
+                match delete_todo(*id, Some(expected)).await {
+                    // Already gone, which is what we wanted
+                    Err(AppError::NotFound) => Ok(()),
+                    result => result,
+                }
+            }
+        };
+
+        match result {
+            Ok(()) => Outcome::Sent,
+            Err(AppError::Request(_)) => Outcome::Offline,
+            Err(e) => {
+                self.fail(e);
+                Outcome::Rejected
+            }
+        }
+    }
+
+    /// Sends queued mutations in the order they were made, stopping at the
+    /// first one that can't reach the server. Rejected ones (e.g. a todo
+    /// changed on another device in the meantime) are dropped with an error,
+    /// and the list is refetched afterwards to show the server's version.
+    pub fn replay(&self) {
+        if !offline::is_online() || self.replaying.get_value() {
+            return;
+        }
+        self.replaying.set_value(true);
+
+        let store = *self;
+        spawn_local(async move {
+            let mut updated = HashMap::new();
+            let mut rejected = false;
+            while let Some(mutation) = store.queue.with_untracked(|queue| queue.first().cloned()) {
+                match store.send(&mutation, &mut updated).await {
+                    Outcome::Offline => break,
+                    Outcome::Rejected => rejected = true,
+                    Outcome::Sent => {}
+                }
+                store.queue.update(|queue| {
+                    queue.remove(0);
+                });
+            }
+            store.replaying.set_value(false);
+            if rejected {
+                store.todos.refetch();
+            }
+        });
+    }
+}
+
+enum Outcome {
+    Sent,
+    /// The server couldn't be reached.
+    Offline,
+    /// The server refused the change; the error has been shown.
+    Rejected,
+}
+
+impl Default for TodoStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,161 @@
+//! Logging and request tracing.
+//!
+//! Every request is handled inside a `request` span carrying its
+//! `x-request-id`, kept when a proxy already set one and echoed in the
+//! response. The session middleware records the `user_id` on it once it
+//! knows who is asking, and server function calls open a `server_fn` span
+//! inside it, so whatever a handler logs can be traced back to the request.
+
+use crate::config::{LogConfig, LogFormat};
+use crate::error::AppError;
+use axum::body::Body;
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use axum::Router;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+use tower_http::LatencyUnit;
+use tracing::{Instrument, Level, Span};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+/// The header a request id is read from and returned in.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Error responses are a serialized [`AppError`]; anything bigger isn't one.
+const ERROR_BODY_LIMIT: usize = 64 * 1024;
+
+/// Installs the global subscriber that writes events to standard error.
+/// Also picks up the `log` records of dependencies such as sqlx.
+pub fn init(config: &LogConfig) {
+    // Validated when the config was loaded
+    let filter = EnvFilter::try_new(&config.filter).unwrap_or_else(|_| EnvFilter::new("info"));
+    let registry = tracing_subscriber::registry().with(filter);
+    let fmt = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+    match config.format {
+        LogFormat::Text => registry.with(fmt).init(),
+        LogFormat::Json => registry
+            .with(fmt.json().flatten_event(true).with_current_span(false).with_span_list(true))
+            .init(),
+    }
+}
+
+/// Gives every request of `router` an id and a `request` span, and logs
+/// each response with its status and latency.
+///
+/// A 5xx isn't logged as a failure on top of that: internal errors are
+/// logged with their cause by [`AppError::internal`], and failed server
+/// function calls by [`server_fn_span`].
+pub fn trace_requests<S>(router: Router<S>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    // Applied bottom to top, so the id is set before the span reads it
+    router
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER.parse().unwrap()))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_span)
+                .on_response(
+                    DefaultOnResponse::new()
+                        .level(Level::INFO)
+                        .latency_unit(LatencyUnit::Millis),
+                )
+                .on_failure(()),
+        )
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER.parse().unwrap(), MakeRequestUuid))
+}
+
+fn request_span(request: &Request) -> Span {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    // The route rather than the path, which may carry a calendar feed token
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(request.uri().path(), MatchedPath::as_str);
+
+    tracing::info_span!(
+        "request",
+        request_id,
+        method = %request.method(),
+        route,
+        user_id = tracing::field::Empty,
+    )
+}
+
+/// Records the signed-in user on the current request's span.
+pub fn record_user(user_id: i64) {
+    Span::current().record("user_id", user_id);
+}
+
+/// Which server function a response came from, and the status code of
+/// the [`AppError`] it returned if it failed. Left in the response's
+/// extensions by [`server_fn_span`] for [`crate::metrics`].
+#[derive(Debug, Clone, Copy)]
+pub struct ServerFnCall {
+    pub name: &'static str,
+    pub error: Option<u16>,
+}
+
+/// Runs server function calls in a `server_fn` span named after the
+/// function, and logs the [`AppError`] a failed call returned.
+///
+/// Leptos answers every failed call with a 500, so the status alone can't
+/// tell a wrong password from a bug.
+pub async fn server_fn_span(request: Request, next: Next) -> Response {
+    let Some(name) = server_fn_name(request.uri().path()) else {
+        return next.run(request).await;
+    };
+
+    let span = tracing::info_span!("server_fn", name);
+    let mut response = next.run(request).instrument(span.clone()).await;
+    if response.status().is_success() || response.status().is_redirection() {
+        response.extensions_mut().insert(ServerFnCall { name, error: None });
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, ERROR_BODY_LIMIT).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            tracing::warn!(parent: &span, "server function error too large to log");
+            Default::default()
+        }
+    };
+    let error = match serde_json::from_slice::<AppError>(&bytes) {
+        Ok(error) => {
+            // Internal errors were logged with their cause where they happened
+            if error != AppError::Internal {
+                tracing::info!(parent: &span, error = %error.user_message(), "server function returned an error");
+            }
+            error.status_code()
+        }
+        Err(_) => {
+            tracing::warn!(parent: &span, status = parts.status.as_u16(), "server function failed");
+            parts.status.as_u16()
+        }
+    };
+    parts.extensions.insert(ServerFnCall {
+        name,
+        error: Some(error),
+    });
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// The name of the server function served at `path`, whose URL is the name
+/// followed by a hash of where it is defined.
+fn server_fn_name(path: &str) -> Option<&'static str> {
+    static PATHS: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    let paths = PATHS.get_or_init(|| leptos::server_fn::axum::server_fn_paths().map(|(path, _)| path).collect());
+    let path: &'static str = paths.get(path)?;
+    let name = path.rsplit('/').next().unwrap_or(path);
+    Some(name.trim_end_matches(|c: char| c.is_ascii_digit()))
+}
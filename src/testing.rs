@@ -0,0 +1,200 @@
+//! Test support: an app backed by an in-memory database, fixtures for the
+//! usual rows, and a way to call server functions as if a request had come
+//! in.
+//!
+//! Only built with the `test-util` feature, which the crate's own tests
+//! enable through its dev-dependency on itself.
+//!
+//! ```ignore
+//! let app = TestApp::new().await;
+//! let alice = app.user("alice").await;
+//! let mut client = app.signed_in(&alice).await;
+//! let todo = app.call(&mut client, add_todo(CreateTodo { title: "Milk".into() })).await?;
+//! ```
+
+use crate::auth::SessionConfig;
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{CreateTodo, RegisterUser, Todo, User};
+use axum::http::header::{AUTHORIZATION, COOKIE, SET_COOKIE};
+use axum::http::{HeaderMap, HeaderValue, Request};
+use leptos::prelude::provide_context;
+use leptos::reactive::{computed::ScopedFuture, owner::Owner};
+use leptos_axum::ResponseOptions;
+use std::collections::BTreeMap;
+use std::future::Future;
+
+/// The password of every user created by [`TestApp::user`].
+pub const PASSWORD: &str = "secret1";
+
+/// The server's shared state, as `main` sets it up, around a fresh
+/// in-memory database.
+#[derive(Clone)]
+pub struct TestApp {
+    pub db: Database,
+    pub config: Config,
+}
+
+impl TestApp {
+    pub async fn new() -> Self {
+        // Not `Config::load`, which would read a `todo-leptos.toml` in the
+        // working directory
+        let mut config = Config::defaults(false);
+        config.database.url = "sqlite::memory:".to_string();
+        // The cheapest cost bcrypt allows, to keep the tests quick
+        config.auth.bcrypt_cost = 4;
+
+        Self {
+            db: Database::memory().await.expect("in-memory database"),
+            config,
+        }
+    }
+
+    pub fn session_config(&self) -> &SessionConfig {
+        &self.config.session
+    }
+
+    /// Creates a user named `username` with the email
+    /// `{username}@example.com` and password [`PASSWORD`].
+    pub async fn user(&self, username: &str) -> User {
+        self.db
+            .create_user(
+                RegisterUser {
+                    username: username.to_string(),
+                    email: format!("{username}@example.com"),
+                    password: PASSWORD.to_string(),
+                },
+                self.config.auth.bcrypt_cost,
+            )
+            .await
+            .expect("create user")
+    }
+
+    /// Creates a user like [`TestApp::user`] and makes it an administrator.
+    pub async fn admin(&self, username: &str) -> User {
+        let mut user = self.user(username).await;
+        self.db
+            .set_user_admin(user.id, true)
+            .await
+            .expect("promote user");
+        user.is_admin = true;
+        user
+    }
+
+    pub async fn todo(&self, user: &User, title: &str) -> Todo {
+        self.db
+            .create_user_todo(
+                user.id,
+                CreateTodo {
+                    title: title.to_string(),
+                },
+            )
+            .await
+            .expect("create todo")
+    }
+
+    /// Starts a session for `user`, returning its token.
+    pub async fn session(&self, user: &User) -> String {
+        let config = self.session_config();
+        let (token, _) = self
+            .db
+            .create_session(user.id, config.idle_timeout, config.absolute_timeout)
+            .await
+            .expect("create session");
+        token
+    }
+
+    /// A client without credentials.
+    pub fn client(&self) -> TestClient {
+        TestClient::default()
+    }
+
+    /// A client holding the session cookie of a new session for `user`.
+    pub async fn signed_in(&self, user: &User) -> TestClient {
+        let mut client = self.client();
+        let token = self.session(user).await;
+        client
+            .cookies
+            .insert(self.session_config().cookie_name.clone(), token);
+        client
+    }
+
+    /// Runs a server function as a request from `client` would: with the
+    /// contexts `main` provides, the client's cookies and headers as the
+    /// request, and any cookies the function sets stored back on the client.
+    ///
+    /// The session middleware doesn't run, so the credentials are resolved
+    /// the way a server function called during rendering resolves them.
+    pub async fn call<F: Future>(&self, client: &mut TestClient, server_fn: F) -> F::Output {
+        let (parts, ()) = client.request().into_parts();
+        let response = ResponseOptions::default();
+
+        let output = Owner::new()
+            .with(|| {
+                let response = response.clone();
+                let app = self.clone();
+                ScopedFuture::new(async move {
+                    provide_context(parts);
+                    provide_context(response);
+                    provide_context(app.db);
+                    provide_context(app.config.session.clone());
+                    provide_context(app.config);
+                    server_fn.await
+                })
+            })
+            .await;
+
+        client.store_cookies(&response.0.read().headers);
+        output
+    }
+}
+
+/// The caller of a server function: a cookie jar plus extra request
+/// headers.
+#[derive(Debug, Clone, Default)]
+pub struct TestClient {
+    pub cookies: BTreeMap<String, String>,
+    pub headers: HeaderMap,
+}
+
+impl TestClient {
+    /// Authenticates with an API token instead of the session cookie.
+    pub fn bearer(mut self, token: &str) -> Self {
+        let value = HeaderValue::from_str(&format!("Bearer {token}")).expect("token is a valid header");
+        self.headers.insert(AUTHORIZATION, value);
+        self
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.get(name).map(String::as_str)
+    }
+
+    fn request(&self) -> Request<()> {
+        let mut request = Request::post("/api/test").body(()).unwrap();
+        *request.headers_mut() = self.headers.clone();
+        if !self.cookies.is_empty() {
+            let cookies: Vec<_> = self
+                .cookies
+                .iter()
+                .map(|(name, value)| format!("{name}={value}"))
+                .collect();
+            let value = HeaderValue::from_str(&cookies.join("; ")).expect("cookies are valid headers");
+            request.headers_mut().insert(COOKIE, value);
+        }
+        request
+    }
+
+    fn store_cookies(&mut self, headers: &HeaderMap) {
+        for value in headers.get_all(SET_COOKIE) {
+            let Some((name, rest)) = value.to_str().ok().and_then(|value| value.split_once('=')) else {
+                continue;
+            };
+            let (value, attributes) = rest.split_once(';').unwrap_or((rest, ""));
+            if value.is_empty() || attributes.contains("Max-Age=0") {
+                self.cookies.remove(name);
+            } else {
+                self.cookies.insert(name.to_string(), value.to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Sending queued webhook deliveries.
+//!
+//! Every request carries the event name, the delivery id and a signature
+//! header of the form `t=<unix timestamp>,v1=<hex HMAC-SHA256>`, where the
+//! HMAC is keyed with the webhook's secret and computed over
+//! `"<timestamp>.<body>"`. Receivers should recompute it (see [`verify`]) and
+//! reject stale timestamps.
+//!
+//! Webhook URLs come from users, so the server only sends to hosts that
+//! resolve to public addresses, checked when the webhook is added and again
+//! for every delivery, and doesn't follow redirects. Otherwise anyone with
+//! an account could make it post to itself, its private network or a cloud
+//! metadata endpoint.
+
+use crate::database::{Database, DueDelivery};
+use crate::models::{Todo, WebhookEvent};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{redirect, Url};
+use sha2::Sha256;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+pub const EVENT_HEADER: &str = "x-todo-event";
+pub const DELIVERY_HEADER: &str = "x-todo-delivery";
+pub const SIGNATURE_HEADER: &str = "x-todo-signature";
+
+/// Attempts before a delivery is marked failed.
+pub const MAX_ATTEMPTS: i64 = 8;
+/// Deliveries sent per job run.
+const BATCH_SIZE: i64 = 20;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long delivered and failed deliveries stay in the log.
+pub const RETENTION_DAYS: i64 = 14;
+
+/// Where deliveries may be sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destinations {
+    /// Only hosts whose addresses are all public. What the server uses.
+    Public,
+    /// Anywhere, loopback included; for tests against a local receiver.
+    Any,
+}
+
+/// Whether `ip` is reachable on the public internet, as opposed to this
+/// machine, a private, link-local or reserved network, or no address at
+/// all.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, third, _] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // "This network" and carrier-grade NAT
+                || first == 0
+                || (first == 100 && second & 0xc0 == 64)
+                // IETF protocol assignments and benchmarking
+                || (first, second, third) == (192, 0, 0)
+                || (first == 198 && second & 0xfe == 18)
+                // Reserved, broadcast included
+                || first >= 240)
+        }
+        IpAddr::V6(ip) => match embedded_ipv4(ip) {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local and link-local
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// The IPv4 address that traffic to `ip` ends up at, for the IPv6 ranges
+/// that carry one: IPv4-mapped, NAT64, 6to4 and Teredo.
+fn embedded_ipv4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return Some(ip);
+    }
+    let segments = ip.segments();
+    let [.., high, low] = segments;
+    let from = |high: u16, low: u16| Ipv4Addr::from((u32::from(high) << 16) | u32::from(low));
+    match segments {
+        [0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(from(high, low)),
+        [0x2002, high, low, ..] => Some(from(high, low)),
+        // Teredo stores the client's address with its bits flipped
+        [0x2001, 0, ..] => Some(from(!high, !low)),
+        _ => None,
+    }
+}
+
+/// Resolves the host of an http(s) `url`, failing unless every address it
+/// resolves to is public. Returns the host and its addresses.
+pub async fn resolve_public(url: &str) -> Result<(String, Vec<SocketAddr>), String> {
+    let url = Url::parse(url).map_err(|e| e.to_string())?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err("only http and https URLs can be used".to_string());
+    }
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Err("the URL has no host".to_string());
+    };
+
+    // IPv6 literals come bracketed
+    let name = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((name, port))
+        .await
+        .map_err(|e| format!("can't resolve {host}: {e}"))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(format!("{host} has no addresses"));
+    }
+    if let Some(address) = addresses.iter().find(|address| !is_public(address.ip())) {
+        return Err(format!("{host} resolves to {}, which isn't a public address", address.ip()));
+    }
+
+    Ok((host.to_string(), addresses))
+}
+
+/// The client for one delivery. With `pinned`, the host only connects to
+/// the addresses that were checked, so a DNS answer that changes in the
+/// meantime can't send the request elsewhere.
+fn client(pinned: Option<(&str, &[SocketAddr])>) -> reqwest::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("todo-leptos-webhooks/", env!("CARGO_PKG_VERSION")))
+        .redirect(redirect::Policy::none());
+    if let Some((host, addresses)) = pinned {
+        builder = builder.resolve_to_addrs(host, addresses);
+    }
+    builder.build()
+}
+
+/// The JSON body sent for `event`.
+pub fn payload(event: WebhookEvent, todo: &Todo) -> String {
+    serde_json::json!({
+        "event": event.as_str(),
+        "occurred_at": Utc::now().to_rfc3339(),
+        "todo": todo,
+    })
+    .to_string()
+}
+
+fn mac(secret: &str, timestamp: i64, body: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{timestamp}.{body}").as_bytes());
+    mac
+}
+
+/// The signature header value for `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let digest: String = mac(secret, timestamp, body)
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("t={timestamp},v1={digest}")
+}
+
+/// Checks a signature header against `body`, returning the signed timestamp
+/// if it matches so the caller can enforce its own freshness window.
+pub fn verify(secret: &str, signature: &str, body: &str) -> Option<i64> {
+    let mut timestamp = None;
+    let mut digest = None;
+    for part in signature.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => digest = Some(value),
+            _ => {}
+        }
+    }
+    let (timestamp, digest) = (timestamp?, digest?);
+
+    let bytes = (0..digest.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digest.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    // `verify_slice` compares in constant time
+    mac(secret, timestamp, body).verify_slice(&bytes).ok()?;
+
+    Some(timestamp)
+}
+
+/// Delay before retrying after `attempts` failed attempts: 30s, doubling each
+/// time, capped at six hours.
+pub fn backoff(attempts: i64) -> chrono::Duration {
+    let seconds = 30i64.saturating_mul(1 << (attempts - 1).clamp(0, 20));
+    chrono::Duration::seconds(seconds.min(6 * 60 * 60))
+}
+
+/// Sends every delivery that is due to `destinations`, recording the
+/// outcome of each.
+pub async fn deliver_due(db: &Database, destinations: Destinations) -> Result<String, sqlx::Error> {
+    let due = db.due_webhook_deliveries(BATCH_SIZE).await?;
+
+    let (mut delivered, mut failed) = (0, 0);
+    for delivery in due {
+        match send(&delivery, destinations).await {
+            Ok(status) => {
+                db.mark_webhook_delivered(delivery.id, status).await?;
+                delivered += 1;
+            }
+            Err((status, error)) => {
+                let attempts = delivery.attempts + 1;
+                let retry_at = (attempts < MAX_ATTEMPTS).then(|| Utc::now() + backoff(attempts));
+                db.mark_webhook_attempt_failed(delivery.id, status, &error, retry_at)
+                    .await?;
+                failed += 1;
+            }
+        }
+    }
+
+    Ok(format!("delivered {delivered} webhooks, {failed} failed"))
+}
+
+/// Posts one delivery, returning the response status on success or the
+/// status (if any) and an error description on failure.
+async fn send(delivery: &DueDelivery, destinations: Destinations) -> Result<i64, (Option<i64>, String)> {
+    // Checked again on every attempt, as what the host resolves to may have
+    // changed since the webhook was added
+    let resolved = match destinations {
+        Destinations::Public => Some(resolve_public(&delivery.url).await.map_err(|e| (None, e))?),
+        Destinations::Any => None,
+    };
+    let pinned = resolved
+        .as_ref()
+        .map(|(host, addresses)| (host.as_str(), addresses.as_slice()));
+    let client = client(pinned).map_err(|e| (None, e.to_string()))?;
+
+    let timestamp = Utc::now().timestamp();
+    let response = client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, &delivery.event)
+        .header(DELIVERY_HEADER, delivery.id.to_string())
+        .header(
+            SIGNATURE_HEADER,
+            sign(&delivery.secret, timestamp, &delivery.payload),
+        )
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|e| (None, e.to_string()))?;
+
+    // Redirects aren't followed, so they fail here like any other non-2xx
+    let status = i64::from(response.status().as_u16());
+    if response.status().is_success() {
+        Ok(status)
+    } else {
+        Err((Some(status), format!("receiver responded with {status}")))
+    }
+}
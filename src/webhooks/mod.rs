@@ -0,0 +1,78 @@
+//! Outgoing webhooks: user-registered URLs that are sent a signed JSON
+//! payload whenever one of the user's todos is created, completed or
+//! deleted.
+//!
+//! Deliveries are queued in the database alongside the change that caused
+//! them and sent by the `deliver_webhooks` job, see [`delivery`].
+
+use crate::csrf::CsrfClient;
+use crate::error::AppError;
+use crate::models::{CreateWebhook, NewWebhook, Webhook, WebhookDelivery};
+use leptos::prelude::*;
+
+#[cfg(feature = "ssr")]
+pub mod delivery;
+#[cfg(feature = "test-util")]
+pub mod receiver;
+
+#[cfg(feature = "ssr")]
+use crate::auth::require_session_user;
+#[cfg(feature = "ssr")]
+use crate::database::Database;
+
+/// How many recent deliveries the settings page shows.
+#[cfg(feature = "ssr")]
+const DELIVERY_LOG_LIMIT: i64 = 50;
+
+#[server(ListWebhooks, "/api", client = CsrfClient)]
+pub async fn list_webhooks() -> Result<Vec<Webhook>, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    Ok(db.list_webhooks(user.id).await?)
+}
+
+#[server(AddWebhook, "/api", client = CsrfClient)]
+pub async fn add_webhook(request: CreateWebhook) -> Result<NewWebhook, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    let url = request.url.trim();
+    if !(url.starts_with("http://") || url.starts_with("https://")) || url.contains(char::is_whitespace) {
+        return Err(AppError::validation("url", "Enter an http:// or https:// URL."));
+    }
+    if let Err(reason) = delivery::resolve_public(url).await {
+        return Err(AppError::validation("url", &format!("Enter a URL on the public internet; {reason}.")));
+    }
+    if request.events.is_empty() {
+        return Err(AppError::validation("events", "Pick at least one event."));
+    }
+
+    let (secret, webhook) = db.create_webhook(user.id, url, &request.events).await?;
+
+    Ok(NewWebhook { secret, webhook })
+}
+
+#[server(RemoveWebhook, "/api", client = CsrfClient)]
+pub async fn remove_webhook(id: i64) -> Result<(), AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    if db.delete_webhook(user.id, id).await? {
+        Ok(())
+    } else {
+        Err(AppError::NotFound)
+    }
+}
+
+#[server(ListWebhookDeliveries, "/api", client = CsrfClient)]
+pub async fn list_webhook_deliveries() -> Result<Vec<WebhookDelivery>, AppError> {
+    let db = expect_context::<Database>();
+
+    let user = require_session_user().await?;
+
+    Ok(db.list_webhook_deliveries(user.id, DELIVERY_LOG_LIMIT).await?)
+}
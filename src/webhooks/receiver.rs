@@ -0,0 +1,141 @@
+//! A throwaway local webhook endpoint for tests and manual debugging.
+//!
+//! ```ignore
+//! let mut receiver = TestReceiver::start().await?;
+//! // register `receiver.url()` as a webhook, trigger some events, run
+//! // `delivery::deliver_due`, then:
+//! let hook = receiver.recv().await.expect("webhook delivered");
+//! assert!(hook.verify(&secret));
+//! ```
+
+use super::delivery::{self, DELIVERY_HEADER, EVENT_HEADER, SIGNATURE_HEADER};
+use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// How long [`TestReceiver::recv`] waits for a request.
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request received by a [`TestReceiver`].
+#[derive(Debug, Clone)]
+pub struct ReceivedWebhook {
+    pub event: Option<String>,
+    pub delivery_id: Option<String>,
+    pub signature: Option<String>,
+    pub body: String,
+    /// The status the receiver answered with.
+    pub responded_with: StatusCode,
+}
+
+impl ReceivedWebhook {
+    /// Whether the request was signed with `secret`.
+    pub fn verify(&self, secret: &str) -> bool {
+        self.signature
+            .as_deref()
+            .and_then(|signature| delivery::verify(secret, signature, &self.body))
+            .is_some()
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or_default()
+    }
+}
+
+#[derive(Clone)]
+struct ReceiverState {
+    sender: mpsc::UnboundedSender<ReceivedWebhook>,
+    fail_next: Arc<AtomicUsize>,
+}
+
+/// An HTTP server on a random local port that records every POST to `/`.
+pub struct TestReceiver {
+    addr: SocketAddr,
+    received: mpsc::UnboundedReceiver<ReceivedWebhook>,
+    fail_next: Arc<AtomicUsize>,
+    server: JoinHandle<()>,
+}
+
+impl TestReceiver {
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let (sender, received) = mpsc::unbounded_channel();
+        let fail_next = Arc::new(AtomicUsize::new(0));
+
+        let app = Router::new().route("/", post(receive)).with_state(ReceiverState {
+            sender,
+            fail_next: fail_next.clone(),
+        });
+        let server = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        Ok(Self {
+            addr,
+            received,
+            fail_next,
+            server,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}/", self.addr)
+    }
+
+    /// Answers the next `count` requests with `500 Internal Server Error`.
+    pub fn fail_next(&self, count: usize) {
+        self.fail_next.store(count, Ordering::SeqCst);
+    }
+
+    /// The next received request, or `None` if none arrives within a few
+    /// seconds.
+    pub async fn recv(&mut self) -> Option<ReceivedWebhook> {
+        tokio::time::timeout(RECV_TIMEOUT, self.received.recv())
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// A request that has already arrived, without waiting.
+    pub fn try_recv(&mut self) -> Option<ReceivedWebhook> {
+        self.received.try_recv().ok()
+    }
+}
+
+impl Drop for TestReceiver {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+async fn receive(State(state): State<ReceiverState>, headers: HeaderMap, body: String) -> StatusCode {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string)
+    };
+
+    let should_fail = state
+        .fail_next
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+        .is_ok();
+    let status = if should_fail {
+        StatusCode::INTERNAL_SERVER_ERROR
+    } else {
+        StatusCode::NO_CONTENT
+    };
+
+    let _ = state.sender.send(ReceivedWebhook {
+        event: header(EVENT_HEADER),
+        delivery_id: header(DELIVERY_HEADER),
+        signature: header(SIGNATURE_HEADER),
+        body,
+        responded_with: status,
+    });
+    status
+}
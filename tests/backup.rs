@@ -0,0 +1,213 @@
+//! Online backups, the admin endpoint that makes one, and restoring.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use sqlx::{Connection, SqliteConnection};
+use std::path::{Path, PathBuf};
+use todo_leptos::admin::create_backup;
+use todo_leptos::backup::{self, BackupError};
+use todo_leptos::config::{BackupConfig, DatabaseConfig};
+use todo_leptos::database::Database;
+use todo_leptos::error::AppError;
+use todo_leptos::models::TokenScope;
+use todo_leptos::testing::TestApp;
+
+fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("todo-leptos-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir(&dir).unwrap();
+    dir
+}
+
+fn backup_config(directory: &Path, keep: usize) -> BackupConfig {
+    BackupConfig {
+        directory: Some(directory.to_path_buf()),
+        keep,
+        ..BackupConfig::default()
+    }
+}
+
+fn file_names(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+async fn file_database(path: &Path) -> (Database, DatabaseConfig) {
+    let config = DatabaseConfig {
+        url: format!("sqlite:{}", path.display()),
+        ..DatabaseConfig::default()
+    };
+    (Database::connect(&config).await.unwrap(), config)
+}
+
+#[tokio::test]
+async fn backups_are_rotated() {
+    let dir = temp_dir();
+    let (db, _) = file_database(&dir.join("todos.db")).await;
+    let backups = dir.join("backups");
+    std::fs::create_dir(&backups).unwrap();
+    let old = ["todos-20200101T000000Z.db", "todos-20210101T000000Z.db"];
+    for name in old {
+        std::fs::write(backups.join(name), "").unwrap();
+    }
+    std::fs::write(backups.join("notes.txt"), "not a backup").unwrap();
+
+    let written = backup::create(&db, &backup_config(&backups, 2)).await.unwrap();
+
+    assert!(written.size_bytes > 0);
+    assert_eq!(file_names(&backups), ["notes.txt", old[1], &written.file_name]);
+    db.close().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn only_admins_can_ask_for_a_backup() {
+    let dir = temp_dir();
+    let (db, _) = file_database(&dir.join("todos.db")).await;
+    let mut app = TestApp {
+        db,
+        ..TestApp::new().await
+    };
+    let alice = app.admin("alice").await;
+    let bob = app.user("bob").await;
+    let mut admin = app.signed_in(&alice).await;
+    let mut user = app.signed_in(&bob).await;
+
+    let refused = app.call(&mut admin, create_backup()).await;
+    assert!(matches!(refused, Err(AppError::Conflict(_))));
+
+    app.config.backup = backup_config(&dir.join("backups"), 7);
+    assert_eq!(
+        app.call(&mut user, create_backup()).await.unwrap_err(),
+        AppError::Forbidden
+    );
+    // Writing a backup needs more than a read-only token, even an admin's
+    let (token, _) = app
+        .db
+        .create_api_token(alice.id, "dashboard", TokenScope::Read, None)
+        .await
+        .unwrap();
+    let mut read_only = app.client().bearer(&token);
+    assert_eq!(
+        app.call(&mut read_only, create_backup()).await.unwrap_err(),
+        AppError::Forbidden
+    );
+    let written = app.call(&mut admin, create_backup()).await.unwrap();
+    assert_eq!(file_names(&dir.join("backups")), [written.file_name]);
+
+    // In-memory databases have no file to back up
+    let mut memory = TestApp::new().await;
+    memory.config.backup = backup_config(&dir.join("memory"), 7);
+    let alice = memory.admin("alice").await;
+    let mut admin = memory.signed_in(&alice).await;
+    let refused = memory.call(&mut admin, create_backup()).await;
+    assert!(matches!(refused, Err(AppError::Conflict(_))));
+
+    app.db.close().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn restore_swaps_in_the_backup_and_keeps_the_old_file() {
+    let dir = temp_dir();
+    let (db, config) = file_database(&dir.join("todos.db")).await;
+    let app = TestApp {
+        db,
+        ..TestApp::new().await
+    };
+    let alice = app.user("alice").await;
+    app.todo(&alice, "Before the backup").await;
+    let written = backup::create(&app.db, &backup_config(&dir.join("backups"), 7)).await.unwrap();
+    app.todo(&alice, "After the backup").await;
+    app.db.close().await;
+
+    let previous = backup::restore(&config, &dir.join("backups").join(&written.file_name))
+        .await
+        .unwrap()
+        .expect("the replaced database was kept");
+    assert!(previous.is_file());
+
+    let (db, _) = file_database(&dir.join("todos.db")).await;
+    let titles: Vec<String> = db
+        .get_user_todos(alice.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect();
+    assert_eq!(titles, ["Before the backup"]);
+    db.close().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn restore_refuses_while_the_database_is_open() {
+    let dir = temp_dir();
+    let (db, config) = file_database(&dir.join("todos.db")).await;
+    let app = TestApp {
+        db,
+        ..TestApp::new().await
+    };
+    let alice = app.user("alice").await;
+    let written = backup::create(&app.db, &backup_config(&dir.join("backups"), 7)).await.unwrap();
+    app.todo(&alice, "After the backup").await;
+    let before = file_names(&dir);
+
+    let error = backup::restore(&config, &dir.join("backups").join(&written.file_name))
+        .await
+        .unwrap_err();
+    assert!(matches!(error, BackupError::InUse { .. }), "{error}");
+
+    // The running server's database is untouched and still works
+    assert_eq!(file_names(&dir), before);
+    assert_eq!(app.db.get_user_todos(alice.id).await.unwrap().len(), 1);
+    app.todo(&alice, "Still writable").await;
+    app.db.close().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn restore_refuses_files_it_cannot_run_against() {
+    let dir = temp_dir();
+    let (db, config) = file_database(&dir.join("todos.db")).await;
+    let written = backup::create(&db, &backup_config(&dir, 7)).await.unwrap();
+    db.close().await;
+    let backup = dir.join(&written.file_name);
+
+    let not_a_database = dir.join("notes.txt");
+    std::fs::write(&not_a_database, "not a database").unwrap();
+    let error = backup::restore(&config, &not_a_database).await.unwrap_err();
+    assert!(matches!(error, BackupError::Invalid { .. } | BackupError::Database(_)));
+
+    // A backup from a newer server
+    let mut connection = SqliteConnection::connect(&format!("sqlite:{}", backup.display()))
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+         VALUES (99990101000000, 'from the future', TRUE, x'00', 0)",
+    )
+    .execute(&mut connection)
+    .await
+    .unwrap();
+    connection.close().await.unwrap();
+    let error = backup::restore(&config, &backup).await.unwrap_err();
+    assert!(matches!(error, BackupError::Invalid { .. }), "{error}");
+
+    let memory = DatabaseConfig {
+        url: "sqlite::memory:".to_string(),
+        ..DatabaseConfig::default()
+    };
+    assert!(matches!(
+        backup::restore(&memory, &backup).await.unwrap_err(),
+        BackupError::Unsupported
+    ));
+
+    // Nothing was touched
+    assert_eq!(file_names(&dir), ["notes.txt", &written.file_name, "todos.db"]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,549 @@
+//! The CalDAV server driven by request bodies modelled on what task clients
+//! (DAVx⁵/Tasks.org, Thunderbird, Apple Reminders) send while syncing; see
+//! `tests/fixtures/caldav/`.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use axum::body::Body;
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::Router;
+use base64::Engine;
+use todo_leptos::auth::middleware::resolve_session;
+use todo_leptos::calendar::caldav;
+use todo_leptos::database::Database;
+use todo_leptos::models::{CreateTodo, TokenScope, UpdateTodo, User};
+use todo_leptos::rest::todo_etag;
+use todo_leptos::testing::{TestApp, PASSWORD};
+use tower::ServiceExt;
+
+const NEW_TASK: &str = "/dav/calendars/todos/3f1c9b52-0c55-4d9a-9d1c-2f6e0b1f7a11.ics";
+
+fn fixture(name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/caldav/{name}",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{path}: {e}"))
+}
+
+/// The CalDAV routes, plus Basic credentials for a write token of alice's
+/// as a task app would send them.
+struct Dav {
+    router: Router,
+    alice: String,
+}
+
+async fn setup() -> (Database, User, Dav) {
+    let app = TestApp::new().await;
+    let user = app.user("alice").await;
+    let (token, _) = app
+        .db
+        .create_api_token(user.id, "tasks", TokenScope::Write, None)
+        .await
+        .unwrap();
+
+    let router = caldav::router(app.db.clone()).layer(axum::middleware::from_fn_with_state(
+        (app.db.clone(), app.session_config().clone()),
+        resolve_session,
+    ));
+    let dav = Dav {
+        router,
+        alice: basic("alice", &token),
+    };
+    (app.db, user, dav)
+}
+
+fn basic(username: &str, password: &str) -> String {
+    let credentials =
+        base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+    format!("Basic {credentials}")
+}
+
+struct Reply {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: String,
+}
+
+impl Reply {
+    fn header(&self, name: &str) -> &str {
+        self.headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+    }
+}
+
+async fn send(
+    app: &Dav,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: String,
+) -> Reply {
+    let mut request = Request::builder().method(method).uri(path);
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let response = app
+        .router
+        .clone()
+        .oneshot(request.body(Body::from(body)).unwrap())
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    Reply {
+        status,
+        headers,
+        body: String::from_utf8(body.to_vec()).unwrap(),
+    }
+}
+
+/// Sends a request as alice, authenticated with her token.
+async fn send_as_alice(
+    app: &Dav,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: String,
+) -> Reply {
+    let mut headers = headers.to_vec();
+    headers.push(("authorization", &app.alice));
+    send(app, method, path, &headers, body).await
+}
+
+async fn create_todo(db: &Database, user: &User, title: &str) -> i64 {
+    db.create_user_todo(
+        user.id,
+        CreateTodo {
+            title: title.to_string(),
+        },
+    )
+    .await
+    .unwrap()
+    .id
+}
+
+#[tokio::test]
+async fn discovery_leads_to_the_todo_calendar() {
+    let (_pool, _user, app) = setup().await;
+
+    let reply = send(&app, "GET", "/.well-known/caldav", &[], String::new()).await;
+    assert_eq!(reply.status, StatusCode::MOVED_PERMANENTLY);
+    assert_eq!(reply.header("location"), "/dav/");
+
+    let reply = send(
+        &app,
+        "PROPFIND",
+        "/dav/",
+        &[("depth", "0")],
+        fixture("propfind-principal.xml"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+    assert!(reply.header("www-authenticate").starts_with("Basic"));
+
+    let reply = send(&app, "OPTIONS", "/dav/calendars/todos/", &[], String::new()).await;
+    assert!(reply.header("dav").contains("calendar-access"));
+
+    let reply = send_as_alice(
+        &app,
+        "PROPFIND",
+        "/dav",
+        &[("depth", "0")],
+        fixture("propfind-principal.xml"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::MULTI_STATUS);
+    assert!(reply.body.contains(
+        "<d:current-user-principal><d:href>/dav/principal/</d:href></d:current-user-principal>"
+    ));
+
+    let reply = send_as_alice(
+        &app,
+        "PROPFIND",
+        "/dav/principal/",
+        &[("depth", "0")],
+        fixture("propfind-home-set.xml"),
+    )
+    .await;
+    assert!(reply
+        .body
+        .contains("<c:calendar-home-set><d:href>/dav/calendars/</d:href></c:calendar-home-set>"));
+    assert!(reply.body.contains("mailto:alice@example.com"));
+    assert!(reply.body.contains("<d:displayname>alice</d:displayname>"));
+
+    let reply = send_as_alice(
+        &app,
+        "PROPFIND",
+        "/dav/calendars/",
+        &[("depth", "1")],
+        fixture("propfind-calendars.xml"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::MULTI_STATUS);
+    assert!(reply
+        .body
+        .contains("<d:href>/dav/calendars/todos/</d:href>"));
+    assert!(reply.body.contains("<d:collection/><c:calendar/>"));
+    assert!(reply.body.contains("<c:comp name=\"VTODO\"/>"));
+    assert!(reply.body.contains("<d:privilege><d:write/></d:privilege>"));
+    assert!(reply.body.contains("<cs:getctag>"));
+    // Properties we don't have are reported as such, in their own namespace
+    assert!(reply
+        .body
+        .contains("<x:calendar-color xmlns:x=\"http://apple.com/ns/ical/\"/>"));
+    assert!(reply.body.contains("HTTP/1.1 404 Not Found"));
+}
+
+#[tokio::test]
+async fn lists_queries_and_fetches_todos() {
+    let (db, user, app) = setup().await;
+    let open = create_todo(&db, &user, "Water the plants").await;
+    let done = create_todo(&db, &user, "File taxes; early").await;
+    let update = UpdateTodo {
+        completed: Some(true),
+        ..Default::default()
+    };
+    db.update_user_todo(user.id, done, update, None)
+        .await
+        .unwrap();
+    let todo = db.get_user_todo_by_id(user.id, open)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let reply = send_as_alice(
+        &app,
+        "PROPFIND",
+        "/dav/calendars/todos/",
+        &[("depth", "1")],
+        fixture("propfind-etags.xml"),
+    )
+    .await;
+    assert!(reply
+        .body
+        .contains(&format!("<d:href>/dav/calendars/todos/todo-{open}.ics</d:href>")));
+    assert!(reply
+        .body
+        .contains(&format!("<d:href>/dav/calendars/todos/todo-{done}.ics</d:href>")));
+    assert!(reply
+        .body
+        .contains(&format!("<d:getetag>{}</d:getetag>", todo_etag(&todo))));
+
+    let reply = send_as_alice(
+        &app,
+        "REPORT",
+        "/dav/calendars/todos/",
+        &[("depth", "1")],
+        fixture("report-calendar-query.xml"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::MULTI_STATUS);
+    assert!(reply.body.contains("SUMMARY:Water the plants"));
+    assert!(reply.body.contains("SUMMARY:File taxes\\; early"));
+    assert!(reply.body.contains("STATUS:COMPLETED"));
+
+    let reply = send_as_alice(
+        &app,
+        "REPORT",
+        "/dav/calendars/todos/",
+        &[("depth", "1")],
+        fixture("report-open-tasks.xml"),
+    )
+    .await;
+    assert!(reply
+        .body
+        .contains(&format!("/dav/calendars/todos/todo-{open}.ics")));
+    assert!(!reply
+        .body
+        .contains(&format!("/dav/calendars/todos/todo-{done}.ics")));
+
+    let reply = send_as_alice(
+        &app,
+        "REPORT",
+        "/dav/calendars/todos/",
+        &[("depth", "1")],
+        fixture("report-events.xml"),
+    )
+    .await;
+    assert!(!reply.body.contains("<d:response>"));
+
+    let reply = send_as_alice(
+        &app,
+        "REPORT",
+        "/dav/calendars/todos/",
+        &[("depth", "1")],
+        fixture("report-multiget.xml"),
+    )
+    .await;
+    assert!(reply.body.contains("UID:todo-1@todo-leptos"));
+    assert!(reply.body.contains(
+        "<d:response><d:href>https://todos.example.com/dav/calendars/todos/does-not-exist.ics</d:href>\
+         <d:status>HTTP/1.1 404 Not Found</d:status></d:response>"
+    ));
+
+    let path = format!("/dav/calendars/todos/todo-{open}.ics");
+    let reply = send_as_alice(&app, "GET", &path, &[], String::new()).await;
+    assert_eq!(reply.status, StatusCode::OK);
+    assert!(reply.header("content-type").starts_with("text/calendar"));
+    assert_eq!(reply.header("etag"), todo_etag(&todo));
+    assert!(reply.body.contains("BEGIN:VTODO\r\n"));
+
+    let etag = todo_etag(&todo);
+    let reply = send_as_alice(
+        &app,
+        "GET",
+        &path,
+        &[("if-none-match", &etag)],
+        String::new(),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::NOT_MODIFIED);
+
+    let reply = send_as_alice(
+        &app,
+        "GET",
+        "/dav/calendars/todos/missing.ics",
+        &[],
+        String::new(),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn client_created_tasks_round_trip() {
+    let (db, user, app) = setup().await;
+
+    let reply = send_as_alice(
+        &app,
+        "PUT",
+        NEW_TASK,
+        &[("if-none-match", "*")],
+        fixture("put-new-task.ics"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    let todos = db.get_user_todos(user.id).await.unwrap();
+    assert_eq!(todos.len(), 1);
+    assert_eq!(
+        todos[0].title,
+        "Buy milk, eggs and a very long list of other groceries that wraps past the fold"
+    );
+    assert!(!todos[0].completed);
+
+    // Same name and UID as the client chose, not ours
+    let reply = send_as_alice(&app, "GET", NEW_TASK, &[], String::new()).await;
+    assert!(reply
+        .body
+        .contains("UID:3f1c9b52-0c55-4d9a-9d1c-2f6e0b1f7a11\r\n"));
+    let etag = reply.header("etag").to_string();
+
+    let reply = send_as_alice(
+        &app,
+        "PUT",
+        NEW_TASK,
+        &[("if-none-match", "*")],
+        fixture("put-new-task.ics"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::PRECONDITION_FAILED);
+
+    let reply = send_as_alice(
+        &app,
+        "PUT",
+        NEW_TASK,
+        &[("if-match", "\"stale\"")],
+        fixture("put-completed-task.ics"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::PRECONDITION_FAILED);
+
+    let reply = send_as_alice(
+        &app,
+        "PUT",
+        NEW_TASK,
+        &[("if-match", &etag)],
+        fixture("put-completed-task.ics"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    let todos = db.get_user_todos(user.id).await.unwrap();
+    assert_eq!(todos[0].title, "Buy milk and eggs");
+    assert!(todos[0].completed);
+
+    let reply = send_as_alice(
+        &app,
+        "DELETE",
+        NEW_TASK,
+        &[("if-match", &etag)],
+        String::new(),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::PRECONDITION_FAILED);
+
+    let etag = send_as_alice(&app, "GET", NEW_TASK, &[], String::new())
+        .await
+        .header("etag")
+        .to_string();
+    let reply = send_as_alice(
+        &app,
+        "DELETE",
+        NEW_TASK,
+        &[("if-match", &etag)],
+        String::new(),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::NO_CONTENT);
+    assert!(db.get_user_todos(user.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let reply = send_as_alice(&app, "GET", NEW_TASK, &[], String::new()).await;
+    assert_eq!(reply.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn client_names_never_shadow_a_todos_own() {
+    let (db, user, app) = setup().await;
+    let first = create_todo(&db, &user, "Water the plants").await;
+
+    // Names like the ones todos get by default are reserved
+    let reserved = format!("/dav/calendars/todos/todo-{}.ics", first + 1);
+    let reply = send_as_alice(&app, "PUT", &reserved, &[], fixture("put-new-task.ics")).await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+    assert_eq!(db.get_user_todos(user.id).await.unwrap().len(), 1);
+
+    // A bare id is an ordinary name, and the todo it names later keeps its own
+    let claimed = format!("/dav/calendars/todos/{}.ics", first + 2);
+    let reply = send_as_alice(&app, "PUT", &claimed, &[], fixture("put-new-task.ics")).await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+    let later = create_todo(&db, &user, "File taxes").await;
+    assert_eq!(later, first + 2);
+
+    let reply = send_as_alice(
+        &app,
+        "PROPFIND",
+        "/dav/calendars/todos/",
+        &[("depth", "1")],
+        fixture("propfind-etags.xml"),
+    )
+    .await;
+    assert_eq!(reply.body.matches(&format!("<d:href>{claimed}</d:href>")).count(), 1);
+    let own = format!("/dav/calendars/todos/todo-{later}.ics");
+    assert_eq!(reply.body.matches(&format!("<d:href>{own}</d:href>")).count(), 1);
+
+    let reply = send_as_alice(&app, "GET", &claimed, &[], String::new()).await;
+    assert!(reply.body.contains("SUMMARY:Buy milk"), "{}", reply.body);
+    let reply = send_as_alice(&app, "GET", &own, &[], String::new()).await;
+    assert!(reply.body.contains("SUMMARY:File taxes"), "{}", reply.body);
+}
+
+#[tokio::test]
+async fn only_tasks_are_accepted() {
+    let (db, user, app) = setup().await;
+
+    let reply = send_as_alice(
+        &app,
+        "PUT",
+        "/dav/calendars/todos/standup.ics",
+        &[],
+        fixture("put-event.ics"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+    assert!(reply.body.contains("supported-calendar-component"));
+    assert!(db.get_user_todos(user.id)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn personal_tokens_authenticate_with_their_scope() {
+    let (db, user, app) = setup().await;
+    let (read_token, _) =
+        db.create_api_token(user.id, "phone", TokenScope::Read, None)
+            .await
+            .unwrap();
+    let (write_token, _) =
+        db.create_api_token(user.id, "laptop", TokenScope::Write, None)
+            .await
+            .unwrap();
+
+    let read = basic("alice", &read_token);
+    let reply = send(
+        &app,
+        "PROPFIND",
+        "/dav/calendars/todos/",
+        &[("authorization", &read)],
+        fixture("propfind-calendars.xml"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::MULTI_STATUS);
+    assert!(!reply.body.contains("<d:write/>"));
+
+    let reply = send(
+        &app,
+        "PUT",
+        NEW_TASK,
+        &[("authorization", &read)],
+        fixture("put-new-task.ics"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::FORBIDDEN);
+
+    let bearer = format!("Bearer {write_token}");
+    let reply = send(
+        &app,
+        "PUT",
+        NEW_TASK,
+        &[("authorization", &bearer)],
+        fixture("put-new-task.ics"),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::CREATED);
+
+    // A token only works with its owner's username
+    let wrong_user = basic("bob", &write_token);
+    let reply = send(
+        &app,
+        "PROPFIND",
+        "/dav/",
+        &[("authorization", &wrong_user)],
+        String::new(),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+
+    let wrong_password = basic("alice", "nope");
+    let reply = send(
+        &app,
+        "PROPFIND",
+        "/dav/",
+        &[("authorization", &wrong_password)],
+        String::new(),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+
+    // Only tokens: the account password would cost a bcrypt check per request
+    let password = basic("alice", PASSWORD);
+    let reply = send(
+        &app,
+        "PROPFIND",
+        "/dav/",
+        &[("authorization", &password)],
+        String::new(),
+    )
+    .await;
+    assert_eq!(reply.status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,105 @@
+//! iCalendar output and the secret feed URL.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use todo_leptos::calendar::{feed, ics};
+use todo_leptos::models::Todo;
+use todo_leptos::testing::TestApp;
+use tower::ServiceExt;
+
+#[test]
+fn text_values_are_escaped() {
+    let escaped = ics::escape("Milk; eggs, bread\\butter\r\nthen home");
+    assert_eq!(escaped, r"Milk\; eggs\, bread\\butter\nthen home");
+    assert_eq!(ics::unescape(&escaped), "Milk; eggs, bread\\butter\nthen home");
+}
+
+#[test]
+fn every_todo_has_a_dtstamp() {
+    let todo = |created_at: &str, updated_at: &str| Todo {
+        id: 1,
+        title: "Buy milk".to_string(),
+        completed: false,
+        created_at: created_at.to_string(),
+        updated_at: updated_at.to_string(),
+        version: 1,
+        user_id: 1,
+    };
+    let dtstamp = |todo: &Todo| {
+        let calendar = ics::single(todo, "milk@example.com");
+        let stamps: Vec<_> = calendar
+            .split_terminator("\r\n")
+            .filter_map(|line| line.strip_prefix("DTSTAMP:"))
+            .map(str::to_string)
+            .collect();
+        assert_eq!(stamps.len(), 1, "{calendar}");
+        stamps[0].clone()
+    };
+
+    assert_eq!(dtstamp(&todo("2024-05-01 09:30:00", "2024-05-02 18:00:00")), "20240502T180000Z");
+    assert_eq!(dtstamp(&todo("2024-05-01 09:30:00", "yesterday")), "20240501T093000Z");
+    let now = dtstamp(&todo("", "yesterday"));
+    assert!(chrono::NaiveDateTime::parse_from_str(&now, "%Y%m%dT%H%M%SZ").is_ok(), "{now}");
+}
+
+#[tokio::test]
+async fn long_lines_are_folded_at_75_octets() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    // Multi-byte characters must not be split across a fold
+    let title = "Ünïcödé shopping list, ".repeat(8);
+    let todo = app.todo(&alice, &title).await;
+
+    let calendar = ics::single(&todo, "long@example.com");
+    assert!(calendar.ends_with("END:VCALENDAR\r\n"));
+    let lines: Vec<_> = calendar.split_terminator("\r\n").collect();
+    assert!(lines.iter().all(|line| line.len() <= 75), "{calendar}");
+    assert!(lines.iter().any(|line| line.starts_with(' ')), "{calendar}");
+
+    let parsed = ics::parse_vtodo(&calendar).unwrap();
+    assert_eq!(parsed.summary, title);
+    assert_eq!(parsed.uid.as_deref(), Some("long@example.com"));
+}
+
+async fn get(router: &Router, path: &str) -> (StatusCode, String, String) {
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    let response = router.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|v| v.to_str().unwrap().to_string())
+        .unwrap_or_default();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn the_feed_is_only_served_for_the_current_token() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    app.todo(&alice, "Water plants").await;
+    let router: Router = feed::router(app.db.clone());
+
+    let token = app.db.reset_calendar_feed(alice.id).await.unwrap();
+    let (status, content_type, body) = get(&router, &format!("{}/{token}.ics", feed::FEED_PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(content_type.starts_with("text/calendar"), "{content_type}");
+    assert!(body.contains("X-WR-CALNAME:alice's todos\r\n"), "{body}");
+    assert!(body.contains("SUMMARY:Water plants\r\n"), "{body}");
+
+    let (status, ..) = get(&router, &format!("{}/not-the-token.ics", feed::FEED_PATH)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Resetting the feed retires the old URL
+    let reset = app.db.reset_calendar_feed(alice.id).await.unwrap();
+    assert_ne!(reset, token);
+    let (status, ..) = get(&router, &format!("{}/{token}.ics", feed::FEED_PATH)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, ..) = get(&router, &format!("{}/{reset}.ics", feed::FEED_PATH)).await;
+    assert_eq!(status, StatusCode::OK);
+}
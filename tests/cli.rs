@@ -0,0 +1,227 @@
+//! The operator commands of the server binary, run against a database file
+//! the way `todo-leptos <command>` would.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use clap::Parser;
+use sqlx::{Connection, SqliteConnection};
+use std::path::{Path, PathBuf};
+use todo_leptos::cli::{self, CliError, Command};
+use todo_leptos::config::{Args, Config};
+use todo_leptos::database::Database;
+use todo_leptos::models::LoginUser;
+
+struct Instance {
+    dir: PathBuf,
+    config: Config,
+}
+
+impl Instance {
+    /// An instance whose database doesn't exist yet.
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("todo-leptos-{}", uuid::Uuid::new_v4().simple()));
+        std::fs::create_dir(&dir).unwrap();
+        let mut config = Config::defaults(false);
+        config.database.url = format!("sqlite:{}", dir.join("todos.db").display());
+        config.auth.bcrypt_cost = 4;
+        Self { dir, config }
+    }
+
+    /// Runs `todo-leptos <args>` with `input` on standard input, returning
+    /// what it printed.
+    async fn run(&self, args: &[&str], input: &str) -> Result<String, CliError> {
+        let args = Args::try_parse_from(std::iter::once("todo-leptos").chain(args.iter().copied())).unwrap();
+        let Some(Command::Admin(command)) = args.command else {
+            panic!("not an admin command: {args:?}");
+        };
+        let mut output = Vec::new();
+        cli::run(command, &self.config, &mut input.as_bytes(), &mut output).await?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    async fn database(&self) -> Database {
+        Database::connect(&self.config.database).await.unwrap()
+    }
+
+    fn file(&self, name: &str, content: &str) -> PathBuf {
+        let path = self.dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn finish(self) {
+        std::fs::remove_dir_all(self.dir).unwrap();
+    }
+}
+
+fn login(username: &str, password: &str) -> LoginUser {
+    LoginUser {
+        username: username.to_string(),
+        password: password.to_string(),
+    }
+}
+
+fn path(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[tokio::test]
+async fn users_are_managed_from_the_command_line() {
+    let instance = Instance::new();
+    instance.run(&["migrate"], "").await.unwrap();
+
+    let created = instance
+        .run(&["user", "create", "alice", "--email", "alice@example.com", "--admin"], "secret1\n")
+        .await
+        .unwrap();
+    assert_eq!(created, "created administrator alice with id 1\n");
+    let created = instance
+        .run(&["user", "create", "bob", "--email", "bob@example.com"], "secret1\n")
+        .await
+        .unwrap();
+    assert_eq!(created, "created user bob with id 2\n");
+
+    let short = instance
+        .run(&["user", "create", "carol", "--email", "carol@example.com"], "short\n")
+        .await
+        .unwrap_err();
+    assert_eq!(short.to_string(), "Password must be at least 6 characters.");
+    let taken = instance
+        .run(&["user", "create", "bob", "--email", "robert@example.com"], "secret1\n")
+        .await;
+    assert!(matches!(taken, Err(CliError::Rejected(_))));
+
+    let db = instance.database().await;
+    let bob = db.get_user_by_username("bob").await.unwrap().unwrap();
+    let (session, _) = db
+        .create_session(bob.id, chrono::Duration::hours(1), chrono::Duration::days(1))
+        .await
+        .unwrap();
+
+    let disabled = instance.run(&["user", "disable", "bob"], "").await.unwrap();
+    assert_eq!(disabled, "disabled bob and ended 1 sessions\n");
+    assert!(db.get_session(&session).await.unwrap().is_none());
+    assert!(db.authenticate_user(login("bob", "secret1")).await.unwrap().is_none());
+
+    let list = instance.run(&["user", "list"], "").await.unwrap();
+    let lines: Vec<_> = list.lines().collect();
+    assert_eq!(lines.len(), 3, "{list}");
+    assert!(lines[0].starts_with("    ID  USERNAME  EMAIL"), "{list}");
+    assert!(lines[1].contains("alice@example.com") && lines[1].ends_with("admin"), "{list}");
+    assert!(lines[2].contains("bob@example.com") && lines[2].contains("disabled 20"), "{list}");
+
+    instance.run(&["user", "enable", "bob"], "").await.unwrap();
+    let new_session = || db.create_session(bob.id, chrono::Duration::hours(1), chrono::Duration::days(1));
+    let (session, _) = new_session().await.unwrap();
+    let promoted = instance.run(&["user", "promote", "bob"], "").await.unwrap();
+    assert_eq!(promoted, "made bob an administrator and ended 1 sessions\n");
+    assert!(db.get_user_by_id(bob.id).await.unwrap().unwrap().is_admin);
+    assert!(db.get_session(&session).await.unwrap().is_none());
+    let (session, _) = new_session().await.unwrap();
+    let demoted = instance.run(&["user", "demote", "bob"], "").await.unwrap();
+    assert_eq!(demoted, "bob is no longer an administrator, ended 1 sessions\n");
+    assert!(!db.get_user_by_id(bob.id).await.unwrap().unwrap().is_admin);
+    assert!(db.get_session(&session).await.unwrap().is_none());
+
+    let reset = instance
+        .run(&["user", "reset-password", "bob"], "hunter22\n")
+        .await
+        .unwrap();
+    assert_eq!(reset, "changed the password of bob and ended 0 sessions\n");
+    assert!(db.authenticate_user(login("bob", "secret1")).await.unwrap().is_none());
+    assert!(db.authenticate_user(login("bob", "hunter22")).await.unwrap().is_some());
+
+    let missing = instance.run(&["user", "disable", "mallory"], "").await.unwrap_err();
+    assert_eq!(missing.to_string(), "no user named \"mallory\"");
+
+    db.close().await;
+    instance.finish();
+}
+
+#[tokio::test]
+async fn only_migrate_changes_the_schema() {
+    let instance = Instance::new();
+
+    // A missing database isn't created
+    let error = instance.run(&["user", "list"], "").await.unwrap_err();
+    assert!(error.to_string().contains("todo-leptos migrate"), "{error}");
+    assert!(!instance.dir.join("todos.db").exists());
+
+    // Nor is one a newer server migrated further used as it is
+    instance.run(&["migrate"], "").await.unwrap();
+    let mut connection = SqliteConnection::connect(&instance.config.database.url).await.unwrap();
+    let newer = "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+                 VALUES (99990101000000, 'from the future', TRUE, x'00', 0)";
+    sqlx::query(newer).execute(&mut connection).await.unwrap();
+    let error = instance.run(&["user", "list"], "").await.unwrap_err();
+    assert!(error.to_string().contains("todo-leptos migrate"), "{error}");
+
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = 99990101000000")
+        .execute(&mut connection)
+        .await
+        .unwrap();
+    connection.close().await.unwrap();
+    assert!(instance.run(&["user", "list"], "").await.unwrap().starts_with("    ID  USERNAME"));
+    instance.finish();
+}
+
+#[tokio::test]
+async fn sessions_are_purged_for_everyone_or_one_user() {
+    let instance = Instance::new();
+    instance.run(&["migrate"], "").await.unwrap();
+    instance
+        .run(&["user", "create", "alice", "--email", "alice@example.com"], "secret1\n")
+        .await
+        .unwrap();
+    let db = instance.database().await;
+    let alice = db.get_user_by_username("alice").await.unwrap().unwrap();
+    for _ in 0..2 {
+        db.create_session(alice.id, chrono::Duration::hours(1), chrono::Duration::days(1))
+            .await
+            .unwrap();
+    }
+
+    let purged = instance.run(&["sessions", "purge"], "").await.unwrap();
+    assert_eq!(purged, "purged 0 expired sessions\n");
+    let ended = instance.run(&["sessions", "purge", "--user", "alice"], "").await.unwrap();
+    assert_eq!(ended, "ended 2 sessions of alice\n");
+
+    db.close().await;
+    instance.finish();
+}
+
+#[tokio::test]
+async fn todos_are_imported_and_exported_for_a_user() {
+    let instance = Instance::new();
+    assert_eq!(instance.run(&["migrate"], "").await.unwrap(), "the database is up to date\n");
+    instance
+        .run(&["user", "create", "alice", "--email", "alice@example.com"], "secret1\n")
+        .await
+        .unwrap();
+    let list = instance.file("list.txt", "x Buy milk\n(A) Call mum +family\n");
+
+    let preview = instance
+        .run(&["import", "alice", path(&list), "--dry-run"], "")
+        .await
+        .unwrap();
+    assert_eq!(preview, "would import 2 todos for alice\n");
+    let imported = instance.run(&["import", "alice", path(&list)], "").await.unwrap();
+    assert_eq!(imported, "imported 2 todos for alice\n");
+
+    let exported = instance
+        .run(&["export-user", "alice", "--format", "markdown"], "")
+        .await
+        .unwrap();
+    assert_eq!(exported, "# Todos\n\n- [x] Buy milk\n- [ ] Call mum +family\n");
+
+    let json = instance.run(&["export-user", "alice"], "").await.unwrap();
+    let todos: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+    assert_eq!(todos.len(), 2);
+
+    let unknown = instance.file("list.docx", "");
+    let error = instance.run(&["import", "alice", path(&unknown)], "").await.unwrap_err();
+    assert!(error.to_string().contains("pass --format"), "{error}");
+
+    instance.finish();
+}
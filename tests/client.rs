@@ -0,0 +1,123 @@
+//! The `todo-cli` HTTP client against the JSON API served on a local port,
+//! behind the same session and CSRF layers as the real server.
+//!
+//! Run with `cargo test --features ssr,todo-cli`.
+#![cfg(all(feature = "ssr", feature = "todo-cli"))]
+
+use axum::Router;
+use todo_leptos::auth::middleware::resolve_session;
+use todo_leptos::client::{Client, ClientError};
+use todo_leptos::csrf::middleware::csrf_protection;
+use todo_leptos::error::AppError;
+use todo_leptos::models::UpdateTodo;
+use todo_leptos::rest;
+use todo_leptos::testing::{TestApp, PASSWORD};
+
+/// Serves the API of `app` on a free port, returning its URL.
+async fn serve(app: &TestApp) -> String {
+    let session_config = app.session_config().clone();
+    let router = Router::new()
+        .nest("/api/v1", rest::router(app.db.clone(), app.config.auth.clone()))
+        .route_layer(axum::middleware::from_fn_with_state(
+            (app.db.clone(), session_config.clone()),
+            resolve_session,
+        ))
+        .route_layer(axum::middleware::from_fn_with_state(session_config, csrf_protection));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    url
+}
+
+#[tokio::test]
+async fn a_password_is_exchanged_for_a_token_that_manages_todos() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    app.todo(&alice, "Buy milk").await;
+    let server = serve(&app).await;
+
+    let anonymous = Client::new(&server, None).unwrap();
+    let wrong = anonymous.create_token("alice", "wrong", "todo-cli").await;
+    assert!(matches!(wrong, Err(ClientError::Api(AppError::Unauthorized))), "{wrong:?}");
+    let issued = anonymous.create_token("alice", PASSWORD, "todo-cli").await.unwrap();
+    assert_eq!(issued.api_token.name, "todo-cli");
+
+    let client = Client::new(&server, Some(issued.token)).unwrap();
+    assert_eq!(client.current_user().await.unwrap().username, "alice");
+
+    let added = client.create_todo("Call mum").await.unwrap();
+    let update = UpdateTodo {
+        completed: Some(true),
+        ..UpdateTodo::default()
+    };
+    let done = client.update_todo(added.id, &update).await.unwrap();
+    assert!(done.completed);
+
+    let open: Vec<String> = client
+        .list_todos(Some(false))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect();
+    assert_eq!(open, ["Buy milk"]);
+    assert_eq!(client.list_todos(None).await.unwrap().len(), 2);
+
+    client.delete_todo(added.id).await.unwrap();
+    let gone = client.delete_todo(added.id).await;
+    assert!(matches!(gone, Err(ClientError::Api(AppError::NotFound))), "{gone:?}");
+
+    let empty = client.create_todo("  ").await.unwrap_err();
+    assert!(matches!(empty, ClientError::Api(AppError::Validation { .. })), "{empty:?}");
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_token_are_refused() {
+    let app = TestApp::new().await;
+    let server = serve(&app).await;
+
+    let client = Client::new(&server, Some("not-a-token".to_string())).unwrap();
+    let refused = client.list_todos(None).await;
+    assert!(matches!(refused, Err(ClientError::Api(AppError::Unauthorized))), "{refused:?}");
+
+    assert!(matches!(
+        Client::new("todos.example.com", None),
+        Err(ClientError::InvalidServer(_))
+    ));
+}
+
+#[tokio::test]
+async fn sign_in_and_sign_up_skip_the_csrf_check_only_without_a_session() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let session = app.session(&alice).await;
+    let server = serve(&app).await;
+    let http = reqwest::Client::new();
+
+    let signup = serde_json::json!({ "username": "bob", "email": "bob@example.com", "password": PASSWORD });
+    let created = http
+        .post(format!("{server}/api/v1/users"))
+        .json(&signup)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(created.status(), reqwest::StatusCode::CREATED);
+
+    // With the browser's session cookie it could be a forged request
+    let token_request = serde_json::json!({
+        "username": "alice",
+        "password": PASSWORD,
+        "token": { "name": "forged", "scope": "write" },
+    });
+    let cookie = format!("{}={session}", app.session_config().cookie_name);
+    let forged = http
+        .post(format!("{server}/api/v1/tokens"))
+        .header(reqwest::header::COOKIE, cookie)
+        .json(&token_request)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(forged.status(), reqwest::StatusCode::FORBIDDEN);
+    assert!(app.db.list_api_tokens(alice.id).await.unwrap().is_empty());
+}
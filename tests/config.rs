@@ -0,0 +1,177 @@
+#![cfg(feature = "ssr")]
+
+use clap::Parser;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use todo_leptos::cli::Command;
+use todo_leptos::config::{Args, Config, ConfigError, LogFormat};
+
+fn args(flags: &[&str]) -> Args {
+    Args::try_parse_from(std::iter::once("todo-leptos").chain(flags.iter().copied())).unwrap()
+}
+
+fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+    let vars: HashMap<String, String> = vars
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    move |name| vars.get(name).cloned()
+}
+
+fn config_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("todo-leptos-{}-{name}.toml", std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn defaults_apply_when_nothing_is_set() {
+    let config = Config::load(&args(&[]), env(&[]), true).unwrap();
+
+    assert_eq!(config.address, None);
+    assert_eq!(config.database.url, "sqlite:Todos.db");
+    assert_eq!(config.database.max_connections, 10);
+    assert_eq!(config.database.busy_timeout, std::time::Duration::from_secs(5));
+    assert_eq!(config.session.cookie_name, "session_id");
+    assert!(config.session.cookie_secure);
+    assert_eq!(config.session.idle_timeout, chrono::Duration::days(7));
+    assert_eq!(config.auth.bcrypt_cost, bcrypt::DEFAULT_COST);
+    assert_eq!(config.backup.directory, None);
+    assert_eq!(config.backup.interval, std::time::Duration::from_secs(24 * 60 * 60));
+    assert_eq!(config.backup.keep, 7);
+    assert_eq!(config.metrics.token, None);
+    assert_eq!(config.log.filter, "info");
+    assert_eq!(config.log.format, LogFormat::Text);
+}
+
+#[test]
+fn flags_override_environment_overrides_file() {
+    let path = config_file(
+        "layers",
+        r#"
+        [database]
+        url = "sqlite:from-file.db"
+        max_connections = 3
+
+        [session]
+        cookie_name = "file_session"
+        idle_timeout_hours = 2
+
+        [auth]
+        bcrypt_cost = 10
+        "#,
+    );
+    let path = path.to_str().unwrap();
+
+    let config = Config::load(
+        &args(&["--config", path, "--bcrypt-cost", "5", "--address", "0.0.0.0:8080"]),
+        env(&[
+            ("DATABASE_URL", "sqlite:from-env.db"),
+            ("BCRYPT_COST", "11"),
+            ("SESSION_COOKIE_SECURE", "false"),
+        ]),
+        true,
+    )
+    .unwrap();
+
+    assert_eq!(config.address, Some("0.0.0.0:8080".parse().unwrap()));
+    assert_eq!(config.database.url, "sqlite:from-env.db");
+    assert_eq!(config.database.max_connections, 3);
+    assert_eq!(config.session.cookie_name, "file_session");
+    assert_eq!(config.session.idle_timeout, chrono::Duration::hours(2));
+    assert!(!config.session.cookie_secure);
+    assert_eq!(config.auth.bcrypt_cost, 5);
+}
+
+#[test]
+fn config_file_can_come_from_the_environment() {
+    let path = config_file("from-env", "[database]\nmax_connections = 4\n");
+
+    let config = Config::load(&args(&[]), env(&[("CONFIG_FILE", path.to_str().unwrap())]), true).unwrap();
+
+    assert_eq!(config.database.max_connections, 4);
+}
+
+#[test]
+fn restore_takes_the_same_settings_as_the_server() {
+    let args = args(&["restore", "backups/todos.db", "--database-url", "sqlite:other.db"]);
+    let config = Config::load(&args, env(&[]), true).unwrap();
+
+    assert!(matches!(args.command, Some(Command::Restore { backup }) if backup.as_os_str() == "backups/todos.db"));
+    assert_eq!(config.database.url, "sqlite:other.db");
+}
+
+#[test]
+fn logging_reads_rust_log() {
+    let path = config_file("log", "[log]\nfilter = \"warn\"\nformat = \"json\"\n");
+    let path = path.to_str().unwrap();
+
+    let config = Config::load(&args(&["--config", path]), env(&[("RUST_LOG", "info,sqlx=debug")]), true).unwrap();
+    assert_eq!(config.log.filter, "info,sqlx=debug");
+    assert_eq!(config.log.format, LogFormat::Json);
+
+    let config = Config::load(&args(&["--config", path, "--log-format", "text"]), env(&[]), true).unwrap();
+    assert_eq!(config.log.filter, "warn");
+    assert_eq!(config.log.format, LogFormat::Text);
+}
+
+#[test]
+fn problems_are_reported_by_name() {
+    let missing = Config::load(&args(&["--config", "/nonexistent/todo-leptos.toml"]), env(&[]), true);
+    assert!(matches!(missing, Err(ConfigError::Read { .. })));
+
+    let path = config_file("unknown-key", "[session]\nidle_timeout = 5\n");
+    let unknown = Config::load(&args(&["--config", path.to_str().unwrap()]), env(&[]), true);
+    assert!(matches!(unknown, Err(ConfigError::Parse { .. })));
+
+    let error = Config::load(&args(&[]), env(&[("SESSION_IDLE_TIMEOUT_HOURS", "a week")]), true).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "environment variable SESSION_IDLE_TIMEOUT_HOURS: expected a whole number of hours, got \"a week\""
+    );
+
+    let error = Config::load(&args(&["--bcrypt-cost", "40"]), env(&[]), true).unwrap_err();
+    assert_eq!(error.to_string(), "invalid setting `auth.bcrypt_cost`: must be between 4 and 31");
+
+    let error = Config::load(
+        &args(&["--session-idle-timeout-hours", "48", "--session-absolute-timeout-hours", "24"]),
+        env(&[]),
+        true,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        error,
+        ConfigError::Invalid {
+            key: "session.absolute_timeout_hours",
+            ..
+        }
+    ));
+
+    let error = Config::load(&args(&["--database-url", "mysql://localhost/todos"]), env(&[]), true).unwrap_err();
+    assert!(matches!(error, ConfigError::Invalid { key: "database.url", .. }));
+
+    let error = Config::load(
+        &args(&["--backup-dir", "/var/backups"]),
+        env(&[("DATABASE_URL", "postgres://localhost/todos")]),
+        true,
+    )
+    .unwrap_err();
+    assert!(matches!(error, ConfigError::Invalid { key: "backup.directory", .. }));
+
+    let error = Config::load(&args(&[]), env(&[("BACKUP_KEEP", "0")]), true).unwrap_err();
+    assert_eq!(error.to_string(), "invalid setting `backup.keep`: must be at least 1");
+
+    let error = Config::load(&args(&["--metrics-token", "short"]), env(&[]), true).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid setting `metrics.token`: must be at least 16 characters"
+    );
+
+    let error = Config::load(&args(&[]), env(&[("RUST_LOG", "todo_leptos=loud")]), true).unwrap_err();
+    assert!(matches!(error, ConfigError::Invalid { key: "log.filter", .. }), "{error}");
+    let error = Config::load(&args(&[]), env(&[("LOG_FORMAT", "xml")]), true).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "environment variable LOG_FORMAT: expected text or json, got \"xml\""
+    );
+}
@@ -0,0 +1,47 @@
+//! The `/api/events` stream of live todo changes.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use axum::body::Body;
+use axum::http::Request;
+use axum::routing::get;
+use axum::{Extension, Router};
+use futures::StreamExt;
+use std::time::Duration;
+use todo_leptos::auth::CurrentUser;
+use todo_leptos::events;
+use todo_leptos::testing::TestApp;
+use tower::ServiceExt;
+
+#[tokio::test]
+async fn streams_only_the_users_changes_and_ends_on_shutdown() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+
+    let router = Router::new()
+        .route(events::EVENTS_PATH, get(events::event_stream).with_state(shutdown_rx))
+        .layer(Extension(CurrentUser::session(Some(alice.clone()))));
+    let request = Request::get(events::EVENTS_PATH).body(Body::empty()).unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    let mut body = response.into_body().into_data_stream();
+
+    app.todo(&bob, "Not for alice").await;
+    app.todo(&alice, "Buy milk").await;
+    let frame = tokio::time::timeout(Duration::from_secs(5), body.next())
+        .await
+        .expect("an event arrives")
+        .unwrap()
+        .unwrap();
+    let frame = String::from_utf8(frame.to_vec()).unwrap();
+    assert!(frame.starts_with("event: todo\n"), "{frame}");
+    assert!(frame.contains("Buy milk") && !frame.contains("Not for alice"), "{frame}");
+
+    shutdown_tx.send(true).unwrap();
+    let end = tokio::time::timeout(Duration::from_secs(5), body.next())
+        .await
+        .expect("the stream ends on shutdown");
+    assert!(end.is_none());
+}
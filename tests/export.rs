@@ -0,0 +1,108 @@
+//! The export writers, checked by reading their output back with the
+//! importer.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use todo_leptos::export::write;
+use todo_leptos::import::parse;
+use todo_leptos::models::{CsvColumns, ImportRequest, Todo, TodoFormat};
+
+fn todo(id: i64, title: &str, completed: bool) -> Todo {
+    Todo {
+        id,
+        title: title.to_string(),
+        completed,
+        created_at: "2024-05-01 09:30:00".to_string(),
+        updated_at: "2024-05-02 18:00:00".to_string(),
+        version: 1,
+        user_id: 1,
+    }
+}
+
+fn export(format: TodoFormat, todos: &[Todo]) -> String {
+    let mut file = write::header(format);
+    for (index, todo) in todos.iter().enumerate() {
+        file.push_str(&write::todo(format, index, todo));
+    }
+    file.push_str(&write::footer(format));
+    file
+}
+
+fn reimport(format: TodoFormat, content: String) -> Vec<(String, bool)> {
+    let request = ImportRequest {
+        format,
+        content,
+        columns: CsvColumns::default(),
+        dry_run: true,
+    };
+    let preview = parse::parse(&request).unwrap();
+    assert_eq!(preview.skipped, Vec::<String>::new(), "{format:?}");
+    preview.todos.into_iter().map(|todo| (todo.title, todo.completed)).collect()
+}
+
+#[test]
+fn every_format_reads_back_as_the_same_todos() {
+    let todos = [
+        todo(1, "Buy milk", true),
+        todo(2, "Call mum @phone +family due:2024-06-01", false),
+        todo(3, "Milk, \"good\" eggs; bread\\butter", true),
+        todo(4, "x marks the spot", false),
+        todo(5, "(A) 2024-01-01 looks like a prefix", false),
+        todo(6, "- [ ] nested", true),
+        todo(7, "Ünïcode ✓", false),
+    ];
+    let expected: Vec<_> = todos.iter().map(|todo| (todo.title.clone(), todo.completed)).collect();
+
+    for format in TodoFormat::ALL {
+        assert_eq!(reimport(format, export(format, &todos)), expected, "{format:?}");
+        assert_eq!(reimport(format, export(format, &[])), [], "{format:?} without todos");
+    }
+}
+
+#[test]
+fn line_breaks_survive_only_where_the_format_allows() {
+    let todos = [todo(1, "First line\nsecond  line", false)];
+
+    for format in [TodoFormat::Json, TodoFormat::Csv] {
+        assert_eq!(
+            reimport(format, export(format, &todos)),
+            [("First line\nsecond  line".to_string(), false)],
+            "{format:?}"
+        );
+    }
+    for format in [TodoFormat::Markdown, TodoFormat::TodoTxt] {
+        assert_eq!(
+            reimport(format, export(format, &todos)),
+            [("First line second line".to_string(), false)],
+            "{format:?}"
+        );
+    }
+}
+
+#[test]
+fn csv_fields_are_quoted_and_escaped() {
+    let todos = [
+        todo(1, "Plain", false),
+        todo(2, "Milk, \"good\" eggs", true),
+        todo(3, "Two\nlines", false),
+    ];
+
+    assert_eq!(
+        export(TodoFormat::Csv, &todos),
+        "id,title,completed,created_at,updated_at\r\n\
+         1,Plain,false,2024-05-01 09:30:00,2024-05-02 18:00:00\r\n\
+         2,\"Milk, \"\"good\"\" eggs\",true,2024-05-01 09:30:00,2024-05-02 18:00:00\r\n\
+         3,\"Two\nlines\",false,2024-05-01 09:30:00,2024-05-02 18:00:00\r\n"
+    );
+}
+
+#[test]
+fn todo_txt_lines_carry_their_dates() {
+    let todos = [todo(1, "Buy milk", true), todo(2, "Call mum", false)];
+
+    assert_eq!(
+        export(TodoFormat::TodoTxt, &todos),
+        "x 2024-05-02 2024-05-01 Buy milk\n2024-05-01 Call mum\n"
+    );
+}
@@ -0,0 +1,228 @@
+//! The import parsers, and the size and count limits of `import_todos`.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use todo_leptos::error::AppError;
+use todo_leptos::import::{import_todos, parse, MAX_IMPORT_BYTES, MAX_IMPORT_TODOS};
+use todo_leptos::models::{CsvColumns, ImportRequest, ImportedTodo, TodoFormat};
+use todo_leptos::testing::TestApp;
+
+fn titles(todos: &[ImportedTodo]) -> Vec<(&str, bool)> {
+    todos.iter().map(|todo| (todo.title.as_str(), todo.completed)).collect()
+}
+
+fn columns(title: Option<&str>, completed: Option<&str>) -> CsvColumns {
+    CsvColumns {
+        title: title.map(str::to_string),
+        completed: completed.map(str::to_string),
+        chosen: true,
+    }
+}
+
+fn request(format: TodoFormat, content: String, dry_run: bool) -> ImportRequest {
+    ImportRequest {
+        format,
+        content,
+        columns: CsvColumns::default(),
+        dry_run,
+    }
+}
+
+fn validation_field(error: AppError) -> String {
+    let AppError::Validation { fields } = error else {
+        panic!("expected a validation error, got {error:?}");
+    };
+    fields.into_keys().next().expect("a field")
+}
+
+#[test]
+fn todo_txt_reads_completion_priority_and_tags() {
+    let preview = parse::todo_txt(
+        "x 2024-05-02 2024-05-01 Call mum @phone +family pri:A\n\
+         \n\
+         (B) 2024-05-01 Pay rent due:2024-06-01 @home\n\
+         x 2024-05-02\n\
+         Plain task\n",
+    );
+
+    assert_eq!(
+        preview.todos,
+        [
+            ImportedTodo {
+                title: "Call mum @phone +family pri:A".to_string(),
+                completed: true,
+                priority: Some('A'),
+                due: None,
+                contexts: vec!["phone".to_string()],
+                projects: vec!["family".to_string()],
+            },
+            ImportedTodo {
+                title: "Pay rent due:2024-06-01 @home".to_string(),
+                completed: false,
+                priority: Some('B'),
+                due: Some("2024-06-01".to_string()),
+                contexts: vec!["home".to_string()],
+                projects: vec![],
+            },
+            ImportedTodo {
+                title: "Plain task".to_string(),
+                ..Default::default()
+            },
+        ]
+    );
+    // Blank lines aren't reported, a done marker with no description is
+    assert_eq!(preview.skipped, ["Line 4: no description"]);
+}
+
+#[test]
+fn markdown_reads_checked_and_unchecked_items() {
+    let preview = parse::markdown(
+        "# Groceries\n\
+         - [ ] Milk\n\
+         * [x] Eggs\n\
+         + [X] Bread\n\
+         1. [ ] Butter\n\
+         - [ ]   \n\
+         - a plain bullet\n\
+         -[ ] no space\n\
+         Some prose [x] here\n",
+    );
+
+    assert_eq!(
+        titles(&preview.todos),
+        [("Milk", false), ("Eggs", true), ("Bread", true), ("Butter", false)]
+    );
+    assert_eq!(preview.skipped, ["Line 6: empty item"]);
+}
+
+#[test]
+fn csv_guesses_columns_and_reads_quoted_fields() {
+    let preview = parse::csv(
+        "Id,Task,Done\n\
+         1,\"Milk, eggs and \"\"good\"\" bread\",yes\n\
+         2,\"Two\nlines\",no\n\
+         3,,x\n\
+         4,  Padded  ,DONE\n",
+        &CsvColumns::default(),
+    )
+    .unwrap();
+
+    assert_eq!(preview.columns, ["Id", "Task", "Done"]);
+    assert_eq!(preview.mapping, columns(Some("Task"), Some("Done")));
+    assert_eq!(
+        titles(&preview.todos),
+        [
+            ("Milk, eggs and \"good\" bread", true),
+            ("Two\nlines", false),
+            ("Padded", true),
+        ]
+    );
+    // The quoted title spans two lines, but it's still one row
+    assert_eq!(preview.skipped, ["Row 4: no title"]);
+}
+
+#[test]
+fn csv_falls_back_to_the_first_column() {
+    let preview = parse::csv("What,When\nMilk,today\n", &CsvColumns::default()).unwrap();
+
+    assert_eq!(preview.mapping, columns(Some("What"), None));
+    assert_eq!(titles(&preview.todos), [("Milk", false)]);
+}
+
+#[test]
+fn csv_uses_the_chosen_columns() {
+    let content = "title,note,state\nIgnored,Milk,completed\nAlso ignored,Eggs,open\n";
+
+    let preview = parse::csv(content, &columns(Some("Note"), Some("state"))).unwrap();
+    assert_eq!(preview.mapping, columns(Some("note"), Some("state")));
+    assert_eq!(titles(&preview.todos), [("Milk", true), ("Eggs", false)]);
+
+    // A chosen mapping without a completion column isn't filled in by guessing
+    let preview = parse::csv("task,done\nMilk,yes\n", &columns(Some("task"), None)).unwrap();
+    assert_eq!(preview.mapping, columns(Some("task"), None));
+    assert_eq!(titles(&preview.todos), [("Milk", false)]);
+
+    let missing = parse::csv(content, &columns(Some("summary"), None)).unwrap_err();
+    assert_eq!(validation_field(missing), "columns.title");
+    let missing = parse::csv(content, &columns(Some("title"), Some("done"))).unwrap_err();
+    assert_eq!(validation_field(missing), "columns.completed");
+}
+
+#[test]
+fn json_reads_arrays_and_export_objects() {
+    let array = parse::json(
+        r#"[{"title": "Milk", "completed": true}, {"title": "  "}, {"id": 3}, {"title": "Eggs"}]"#,
+    )
+    .unwrap();
+    assert_eq!(titles(&array.todos), [("Milk", true), ("Eggs", false)]);
+    assert_eq!(array.skipped, ["Item 2: no title", "Item 3: no title"]);
+
+    let object = parse::json(r#"{"todos": [{"title": "Milk", "completed": false}]}"#).unwrap();
+    assert_eq!(titles(&object.todos), [("Milk", false)]);
+
+    for invalid in [r#"{"items": []}"#, "42", "[{"] {
+        assert_eq!(validation_field(parse::json(invalid).unwrap_err()), "content");
+    }
+}
+
+#[tokio::test]
+async fn import_creates_todos_unless_previewing() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let mut client = app.signed_in(&alice).await;
+    let content = "- [ ] Milk\n- [x] Eggs\n".to_string();
+
+    let preview = app
+        .call(&mut client, import_todos(request(TodoFormat::Markdown, content.clone(), true)))
+        .await
+        .unwrap();
+    assert!(!preview.imported);
+    assert_eq!(preview.todos.len(), 2);
+    assert!(app.db.get_user_todos(alice.id).await.unwrap().is_empty());
+
+    let imported = app
+        .call(&mut client, import_todos(request(TodoFormat::Markdown, content, false)))
+        .await
+        .unwrap();
+    assert!(imported.imported);
+    let mut stored: Vec<_> = app
+        .db
+        .get_user_todos(alice.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|todo| (todo.title, todo.completed))
+        .collect();
+    stored.sort();
+    assert_eq!(stored, [("Eggs".to_string(), true), ("Milk".to_string(), false)]);
+}
+
+#[tokio::test]
+async fn import_refuses_files_over_the_limits() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let mut client = app.signed_in(&alice).await;
+
+    let too_large = "a".repeat(MAX_IMPORT_BYTES + 1);
+    let refused = app
+        .call(&mut client, import_todos(request(TodoFormat::TodoTxt, too_large, true)))
+        .await
+        .unwrap_err();
+    assert_eq!(validation_field(refused), "content");
+
+    let at_limit = "t\n".repeat(MAX_IMPORT_TODOS);
+    let preview = app
+        .call(&mut client, import_todos(request(TodoFormat::TodoTxt, at_limit, true)))
+        .await
+        .unwrap();
+    assert_eq!(preview.todos.len(), MAX_IMPORT_TODOS);
+
+    let too_many = "t\n".repeat(MAX_IMPORT_TODOS + 1);
+    let refused = app
+        .call(&mut client, import_todos(request(TodoFormat::TodoTxt, too_many, false)))
+        .await
+        .unwrap_err();
+    assert_eq!(validation_field(refused), "content");
+    assert!(app.db.get_user_todos(alice.id).await.unwrap().is_empty());
+}
@@ -0,0 +1,130 @@
+//! What `/metrics` reports about requests, server functions and the
+//! database.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use todo_leptos::metrics::{self, Metrics};
+use todo_leptos::telemetry::ServerFnCall;
+use todo_leptos::testing::TestApp;
+use tower::ServiceExt;
+
+async fn get_path(app: &Router, path: &str) -> StatusCode {
+    let request = Request::get(path).body(Body::empty()).unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+const TOKEN: &str = "scrape-token-0123456789";
+
+fn scrape(token: Option<&str>) -> Request<Body> {
+    let request = Request::get(metrics::METRICS_PATH);
+    let request = match token {
+        Some(token) => request.header(header::AUTHORIZATION, format!("Bearer {token}")),
+        None => request,
+    };
+    request.body(Body::empty()).unwrap()
+}
+
+/// The lines of `rendered` for `metric`, without the comments.
+fn samples<'a>(rendered: &'a str, metric: &str) -> Vec<&'a str> {
+    rendered.lines().filter(|line| line.starts_with(metric)).collect()
+}
+
+#[tokio::test]
+async fn requests_are_counted_by_route_and_server_fn_calls_by_name() {
+    let metrics = Metrics::new();
+    let call = |error| {
+        move || async move {
+            let mut response = "".into_response();
+            response.extensions_mut().insert(ServerFnCall { name: "login", error });
+            response
+        }
+    };
+    let app = metrics.track_requests(
+        Router::new()
+            .route("/todos/{id}", get(|| async { "todo" }))
+            .route("/api/login", get(call(None)))
+            .route("/api/login-failing", get(call(Some(401)))),
+    );
+
+    assert_eq!(get_path(&app, "/todos/1").await, StatusCode::OK);
+    assert_eq!(get_path(&app, "/todos/2").await, StatusCode::OK);
+    assert_eq!(get_path(&app, "/nowhere/abc").await, StatusCode::NOT_FOUND);
+    get_path(&app, "/api/login").await;
+    get_path(&app, "/api/login-failing").await;
+
+    let rendered = metrics.render();
+    assert_eq!(
+        samples(&rendered, "todo_leptos_http_requests_total"),
+        [
+            r#"todo_leptos_http_requests_total{method="GET",route="/api/login",status="200"} 1"#,
+            r#"todo_leptos_http_requests_total{method="GET",route="/api/login-failing",status="200"} 1"#,
+            r#"todo_leptos_http_requests_total{method="GET",route="/todos/{id}",status="200"} 2"#,
+            r#"todo_leptos_http_requests_total{method="GET",route="fallback",status="404"} 1"#,
+        ]
+    );
+    assert!(rendered.contains(r#"todo_leptos_http_request_duration_seconds_count{method="GET",route="/todos/{id}"} 2"#));
+    assert_eq!(
+        samples(&rendered, "todo_leptos_server_fn_"),
+        [
+            r#"todo_leptos_server_fn_calls_total{name="login"} 2"#,
+            r#"todo_leptos_server_fn_errors_total{name="login",status="401"} 1"#,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn the_endpoint_reports_database_and_business_gauges() {
+    let metrics = Metrics::new();
+    let app = TestApp::new().await;
+    let db = metrics.meter(app.db.clone());
+    let alice = app.user("alice").await;
+    app.session(&alice).await;
+    for title in ["Buy milk", "Call mum"] {
+        db.create_user_todo(
+            alice.id,
+            todo_leptos::models::CreateTodo {
+                title: title.to_string(),
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    let router: Router = metrics.router(db, TOKEN.to_string());
+    let response = router.oneshot(scrape(Some(TOKEN))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let rendered = String::from_utf8(body.to_vec()).unwrap();
+
+    assert!(rendered.contains("todo_leptos_active_sessions 1\n"), "{rendered}");
+    assert!(rendered.contains("todo_leptos_todos_created_today 2\n"), "{rendered}");
+    assert!(
+        rendered.contains(r#"todo_leptos_db_operation_duration_seconds_count{operation="create_user_todo"} 2"#),
+        "{rendered}"
+    );
+    assert!(
+        rendered.contains(r#"todo_leptos_db_connections{pool="writer",state="max"} 1"#),
+        "{rendered}"
+    );
+}
+
+#[tokio::test]
+async fn the_endpoint_needs_the_token() {
+    let metrics = Metrics::new();
+    let app = TestApp::new().await;
+    let router: Router = metrics.router(app.db.clone(), TOKEN.to_string());
+
+    for token in [None, Some(""), Some("scrape-token-wrong-token")] {
+        let response = router.clone().oneshot(scrape(token)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{token:?}");
+        assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+    }
+    let response = router.oneshot(scrape(Some(TOKEN))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
@@ -0,0 +1,83 @@
+//! Keeps the published OpenAPI document in step with the `/api/v1` handlers.
+//!
+//! Run with `cargo test --features ssr`. After an intentional API change,
+//! regenerate the snapshot with `UPDATE_OPENAPI=1 cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, StatusCode};
+use axum::{Extension, Router};
+use sqlx::SqlitePool;
+use todo_leptos::auth::CurrentUser;
+use todo_leptos::config::AuthConfig;
+use todo_leptos::database::Database;
+use todo_leptos::rest::{self, ApiDoc};
+use tower::ServiceExt;
+use utoipa::OpenApi;
+
+const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/openapi.json");
+
+fn spec() -> serde_json::Value {
+    serde_json::to_value(ApiDoc::openapi()).unwrap()
+}
+
+#[test]
+fn spec_matches_snapshot() {
+    let generated = serde_json::to_string_pretty(&spec()).unwrap() + "\n";
+    if std::env::var_os("UPDATE_OPENAPI").is_some() {
+        std::fs::write(SNAPSHOT, &generated).unwrap();
+        return;
+    }
+
+    let snapshot = std::fs::read_to_string(SNAPSHOT).unwrap_or_default();
+    assert!(
+        snapshot == generated,
+        "OpenAPI document changed; review it and rerun with UPDATE_OPENAPI=1 to update {SNAPSHOT}"
+    );
+}
+
+fn app() -> Router {
+    let pool = SqlitePool::connect_lazy("sqlite::memory:").unwrap();
+    rest::router(Database::sqlite(pool), AuthConfig { bcrypt_cost: 4 }).layer(Extension(CurrentUser::default()))
+}
+
+/// Sends `method path` and reports whether a handler answered it, as opposed
+/// to the router's own 404 / 405.
+async fn is_routed(app: &Router, method: &Method, path: &str) -> bool {
+    let request = Request::builder()
+        .method(method.clone())
+        .uri(path.replace("{id}", "1"))
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+
+    // Handlers answer unknown todos with a JSON error body; the router's own
+    // 404 is empty
+    status != StatusCode::METHOD_NOT_ALLOWED && !(status == StatusCode::NOT_FOUND && body.is_empty())
+}
+
+#[tokio::test]
+async fn documented_operations_match_routes() {
+    let app = app();
+    let spec = spec();
+    let paths = spec["paths"].as_object().expect("spec has paths");
+    assert!(!paths.is_empty());
+
+    for (path, operations) in paths {
+        let documented = operations.as_object().unwrap();
+        for method in [Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE] {
+            let is_documented = documented.contains_key(&method.as_str().to_lowercase());
+            let routed = is_routed(&app, &method, path).await;
+            assert_eq!(
+                is_documented, routed,
+                "{method} {path}: documented = {is_documented}, routed = {routed}"
+            );
+        }
+    }
+}
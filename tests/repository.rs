@@ -0,0 +1,550 @@
+//! The storage layer, with every case run against both backends.
+//!
+//! SQLite runs in memory. PostgreSQL needs a server: point
+//! `TEST_POSTGRES_URL` at a role that may create databases, e.g.
+//! `postgres://postgres@127.0.0.1:5432/postgres` (PostgreSQL 13 or newer);
+//! each test then gets a database of its own. The PostgreSQL variants are
+//! ignored by default and fail when run without it.
+//!
+//! Run with `cargo test --features ssr`, adding `-- --include-ignored` with
+//! `TEST_POSTGRES_URL` set to cover PostgreSQL too.
+#![cfg(feature = "ssr")]
+
+use chrono::{Duration, Utc};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, Connection, PgConnection, SqliteConnection};
+use std::str::FromStr;
+use todo_leptos::config::DatabaseConfig;
+use todo_leptos::database::Database;
+use todo_leptos::models::{CreateTodo, ImportedTodo, LoginUser, RegisterUser, TokenScope, UpdateTodo, User, WebhookEvent};
+
+/// A freshly migrated database on the `TEST_POSTGRES_URL` server, dropped
+/// again by [`TestPostgres::finish`]. A failing test leaves it behind for
+/// inspection.
+struct TestPostgres {
+    server: PgConnectOptions,
+    name: String,
+    database: Database,
+}
+
+impl TestPostgres {
+    async fn create() -> Self {
+        let url = std::env::var("TEST_POSTGRES_URL").expect("TEST_POSTGRES_URL is set");
+        let server = PgConnectOptions::from_str(&url).unwrap();
+        let name = format!("todo_leptos_test_{}", uuid::Uuid::new_v4().simple());
+
+        let mut admin = server.connect().await.unwrap();
+        sqlx::query(&format!("CREATE DATABASE {name}"))
+            .execute(&mut admin)
+            .await
+            .unwrap();
+        admin.close().await.unwrap();
+
+        let pool = PgPoolOptions::new()
+            .max_connections(2)
+            .connect_with(server.clone().database(&name))
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations/postgres").run(&pool).await.unwrap();
+
+        Self {
+            server,
+            name,
+            database: Database::postgres(pool),
+        }
+    }
+
+    async fn finish(self) {
+        self.database.close().await;
+        let mut admin: PgConnection = self.server.connect().await.unwrap();
+        // Closed connections may not have left the server yet
+        sqlx::query(&format!("DROP DATABASE {} WITH (FORCE)", self.name))
+            .execute(&mut admin)
+            .await
+            .unwrap();
+    }
+}
+
+/// Runs each listed case once per backend, as `sqlite::<case>` and
+/// `postgres::<case>`.
+macro_rules! backend_tests {
+    ($($case:ident),* $(,)?) => {
+        mod sqlite {
+            $(
+                #[tokio::test]
+                async fn $case() {
+                    super::$case(super::Database::memory().await.unwrap()).await;
+                }
+            )*
+        }
+
+        mod postgres {
+            $(
+                #[tokio::test]
+                #[ignore = "needs a PostgreSQL server in TEST_POSTGRES_URL"]
+                async fn $case() {
+                    let test = super::TestPostgres::create().await;
+                    super::$case(test.database.clone()).await;
+                    test.finish().await;
+                }
+            )*
+        }
+    };
+}
+
+backend_tests!(
+    users,
+    disabled_users,
+    sessions,
+    todos,
+    stale_updates_are_refused,
+    imports_and_streams_todos,
+    api_tokens,
+    calendar_feeds,
+    webhook_deliveries,
+    optimize,
+    counts_for_metrics,
+);
+
+async fn user(db: &Database, name: &str) -> User {
+    db.create_user(
+        RegisterUser {
+            username: name.to_string(),
+            email: format!("{name}@example.com"),
+            password: "secret1".to_string(),
+        },
+        // The cheapest cost bcrypt allows, to keep the tests quick
+        4,
+    )
+    .await
+    .unwrap()
+}
+
+async fn todo(db: &Database, user: &User, title: &str) -> i64 {
+    db.create_user_todo(
+        user.id,
+        CreateTodo {
+            title: title.to_string(),
+        },
+    )
+    .await
+    .unwrap()
+    .id
+}
+
+fn is_timestamp(value: &str) -> bool {
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").is_ok()
+}
+
+async fn users(db: Database) {
+    let alice = user(&db, "alice").await;
+    let bob = user(&db, "bob").await;
+
+    assert!(!alice.is_admin, "administrators are made explicitly");
+    assert!(db.set_user_admin(alice.id, true).await.unwrap());
+    assert!(db.get_user_by_id(alice.id).await.unwrap().unwrap().is_admin);
+    assert!(!bob.is_admin);
+    assert!(!db.set_user_admin(bob.id + 100, true).await.unwrap());
+    assert!(is_timestamp(&alice.created_at), "{}", alice.created_at);
+
+    assert!(db.user_exists("alice", "nobody@example.com").await.unwrap());
+    assert!(db.user_exists("nobody", "bob@example.com").await.unwrap());
+    assert!(!db.user_exists("carol", "carol@example.com").await.unwrap());
+
+    let login = |password: &str| LoginUser {
+        username: "alice".to_string(),
+        password: password.to_string(),
+    };
+    let signed_in = db.authenticate_user(login("secret1")).await.unwrap().unwrap();
+    assert_eq!(signed_in.id, alice.id);
+    assert!(db.authenticate_user(login("wrong")).await.unwrap().is_none());
+
+    let found = db.get_user_by_id(bob.id).await.unwrap().unwrap();
+    assert_eq!(found.username, "bob");
+    assert_eq!(found.email, "bob@example.com");
+    assert!(db.get_user_by_id(bob.id + 100).await.unwrap().is_none());
+    assert_eq!(db.get_user_by_username("bob").await.unwrap().map(|user| user.id), Some(bob.id));
+    assert!(db.get_user_by_username("carol").await.unwrap().is_none());
+
+    let all: Vec<_> = db.list_users().await.unwrap().into_iter().map(|user| user.username).collect();
+    assert_eq!(all, ["alice", "bob"]);
+
+    assert!(db.set_user_password(alice.id, "secret2", 4).await.unwrap());
+    assert!(db.authenticate_user(login("secret1")).await.unwrap().is_none());
+    assert!(db.authenticate_user(login("secret2")).await.unwrap().is_some());
+}
+
+async fn disabled_users(db: Database) {
+    let alice = user(&db, "alice").await;
+    let (session, _) = db
+        .create_session(alice.id, Duration::hours(1), Duration::days(1))
+        .await
+        .unwrap();
+    let (api_token, _) = db
+        .create_api_token(alice.id, "cli", TokenScope::Read, None)
+        .await
+        .unwrap();
+    let feed = db.reset_calendar_feed(alice.id).await.unwrap();
+    let login = LoginUser {
+        username: "alice".to_string(),
+        password: "secret1".to_string(),
+    };
+
+    assert!(db.set_user_disabled(alice.id, true).await.unwrap());
+    let disabled_at = db.get_user_by_id(alice.id).await.unwrap().unwrap().disabled_at;
+    assert!(disabled_at.as_deref().is_some_and(is_timestamp), "{disabled_at:?}");
+    assert!(db.authenticate_user(login.clone()).await.unwrap().is_none());
+    assert!(db.get_session(&session).await.unwrap().is_none());
+    assert!(db.authenticate_api_token(&api_token).await.unwrap().is_none());
+    assert!(db.calendar_feed_user(&feed).await.unwrap().is_none());
+
+    // Disabling again keeps the original time
+    db.set_user_disabled(alice.id, true).await.unwrap();
+    assert_eq!(db.get_user_by_id(alice.id).await.unwrap().unwrap().disabled_at, disabled_at);
+
+    assert!(db.set_user_disabled(alice.id, false).await.unwrap());
+    assert_eq!(db.get_user_by_id(alice.id).await.unwrap().unwrap().disabled_at, None);
+    assert!(db.authenticate_user(login).await.unwrap().is_some());
+    assert!(db.get_session(&session).await.unwrap().is_some());
+    assert!(db.authenticate_api_token(&api_token).await.unwrap().is_some());
+    assert_eq!(db.calendar_feed_user(&feed).await.unwrap(), Some(alice.id));
+
+    assert!(!db.set_user_disabled(alice.id + 100, true).await.unwrap());
+}
+
+async fn sessions(db: Database) {
+    let alice = user(&db, "alice").await;
+
+    let (token, session) = db
+        .create_session(alice.id, Duration::hours(1), Duration::days(1))
+        .await
+        .unwrap();
+    assert_ne!(session.id, token, "only the hash is stored");
+    assert!(is_timestamp(&session.expires_at), "{}", session.expires_at);
+
+    let (found, owner) = db.get_session(&token).await.unwrap().unwrap();
+    assert_eq!(found.id, session.id);
+    assert_eq!(owner.id, alice.id);
+
+    // Refreshing right away is skipped, a longer idle timeout isn't
+    assert!(!db.refresh_session(&session.id, Duration::hours(1)).await.unwrap());
+    assert!(db.refresh_session(&session.id, Duration::hours(2)).await.unwrap());
+
+    let (expired, _) = db
+        .create_session(alice.id, Duration::hours(-1), Duration::days(1))
+        .await
+        .unwrap();
+    assert!(db.get_session(&expired).await.unwrap().is_none());
+    assert_eq!(db.purge_expired_sessions().await.unwrap(), 1);
+
+    assert!(db.delete_session(&token).await.unwrap());
+    assert!(!db.delete_session(&token).await.unwrap());
+    assert!(db.get_session(&token).await.unwrap().is_none());
+
+    for _ in 0..2 {
+        db.create_session(alice.id, Duration::hours(1), Duration::days(1))
+            .await
+            .unwrap();
+    }
+    assert_eq!(db.delete_user_sessions(alice.id).await.unwrap(), 2);
+}
+
+async fn todos(db: Database) {
+    let alice = user(&db, "alice").await;
+    let bob = user(&db, "bob").await;
+
+    let first = todo(&db, &alice, "Water the plants").await;
+    let second = todo(&db, &alice, "File taxes").await;
+    todo(&db, &bob, "Someone else's").await;
+
+    let titles: Vec<_> = db
+        .get_user_todos(alice.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|todo| todo.title)
+        .collect();
+    assert_eq!(titles, ["File taxes", "Water the plants"], "newest first");
+
+    let update = UpdateTodo {
+        completed: Some(true),
+        ..Default::default()
+    };
+    let updated = db
+        .update_user_todo(alice.id, first, update.clone(), None)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(updated.completed);
+    assert_eq!(updated.title, "Water the plants");
+    assert!(is_timestamp(&updated.updated_at), "{}", updated.updated_at);
+
+    // Other users' todos are out of reach
+    assert!(db.get_user_todo_by_id(bob.id, first).await.unwrap().is_none());
+    assert!(db
+        .update_user_todo(bob.id, first, update, None)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(!db.delete_user_todo(bob.id, second, None).await.unwrap());
+
+    assert!(db.delete_user_todo(alice.id, second, None).await.unwrap());
+    assert!(db.get_user_todo_by_id(alice.id, second).await.unwrap().is_none());
+    assert_eq!(db.get_user_todos(alice.id).await.unwrap().len(), 1);
+}
+
+async fn stale_updates_are_refused(db: Database) {
+    let alice = user(&db, "alice").await;
+    let id = todo(&db, &alice, "Draft").await;
+    let seen = db.get_user_todo_by_id(alice.id, id).await.unwrap().unwrap();
+
+    let rename = |title: &str| UpdateTodo {
+        title: Some(title.to_string()),
+        ..Default::default()
+    };
+    let renamed = db
+        .update_user_todo(alice.id, id, rename("Final"), Some(seen.version))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(renamed.title, "Final");
+    assert_eq!(renamed.version, seen.version + 1);
+
+    // Within the same second as the first update, so only the version tells
+    // them apart
+    let refused = db
+        .update_user_todo(alice.id, id, rename("Overwritten"), Some(seen.version))
+        .await
+        .unwrap();
+    assert!(refused.is_none());
+    assert_eq!(db.get_user_todo_by_id(alice.id, id).await.unwrap().unwrap().title, "Final");
+
+    assert!(!db.delete_user_todo(alice.id, id, Some(seen.version)).await.unwrap());
+    assert!(db.delete_user_todo(alice.id, id, Some(renamed.version)).await.unwrap());
+}
+
+async fn imports_and_streams_todos(db: Database) {
+    let alice = user(&db, "alice").await;
+    let imported = |title: &str, completed| ImportedTodo {
+        title: title.to_string(),
+        completed,
+        ..Default::default()
+    };
+
+    let created = db
+        .import_user_todos(
+            alice.id,
+            &[imported("one", false), imported("two", true), imported("three", false)],
+        )
+        .await
+        .unwrap();
+    assert_eq!(created.len(), 3);
+    assert!(created[1].completed);
+
+    let collect = |completed| {
+        let db = db.clone();
+        async move {
+            let (sender, mut receiver) = tokio::sync::mpsc::channel(1);
+            tokio::spawn(async move { db.send_user_todos(alice.id, completed, sender).await });
+            let mut titles = Vec::new();
+            while let Some(todo) = receiver.recv().await {
+                titles.push(todo.unwrap().title);
+            }
+            titles
+        }
+    };
+    assert_eq!(collect(None).await, ["one", "two", "three"], "oldest first");
+    assert_eq!(collect(Some(false)).await, ["one", "three"]);
+    assert_eq!(collect(Some(true)).await, ["two"]);
+
+    // Enough to span several pages, all created in the same second
+    let more: Vec<_> = (0..450).map(|n| imported(&format!("item {n}"), n % 2 == 0)).collect();
+    db.import_user_todos(alice.id, &more).await.unwrap();
+    let titles = collect(None).await;
+    assert_eq!(titles.len(), 453);
+    assert_eq!(titles[3..], more.iter().map(|todo| todo.title.clone()).collect::<Vec<_>>()[..]);
+    assert_eq!(collect(Some(true)).await.len(), 226);
+}
+
+async fn api_tokens(db: Database) {
+    let alice = user(&db, "alice").await;
+
+    let (secret, token) = db
+        .create_api_token(alice.id, "phone", TokenScope::Read, None)
+        .await
+        .unwrap();
+    let (expired, _) = db
+        .create_api_token(alice.id, "old", TokenScope::Write, Some(Duration::hours(-1)))
+        .await
+        .unwrap();
+    assert!(secret.starts_with("tdl_"));
+    assert_eq!(token.scope, TokenScope::Read);
+    assert!(token.last_used_at.is_none());
+
+    let (scope, owner) = db.authenticate_api_token(&secret).await.unwrap().unwrap();
+    assert_eq!(scope, TokenScope::Read);
+    assert_eq!(owner.id, alice.id);
+    assert!(db.authenticate_api_token(&expired).await.unwrap().is_none());
+    assert!(db.authenticate_api_token("tdl_nope").await.unwrap().is_none());
+
+    let listed = db.list_api_tokens(alice.id).await.unwrap();
+    assert_eq!(listed.len(), 2);
+    let phone = listed.iter().find(|listed| listed.id == token.id).unwrap();
+    assert!(phone.last_used_at.is_some(), "using a token records it");
+
+    assert!(db.delete_api_token(alice.id, token.id).await.unwrap());
+    assert!(db.authenticate_api_token(&secret).await.unwrap().is_none());
+}
+
+async fn calendar_feeds(db: Database) {
+    let alice = user(&db, "alice").await;
+    assert!(db.get_calendar_feed(alice.id).await.unwrap().is_none());
+
+    let first = db.reset_calendar_feed(alice.id).await.unwrap();
+    let second = db.reset_calendar_feed(alice.id).await.unwrap();
+    assert_ne!(first, second);
+    assert!(db.calendar_feed_user(&first).await.unwrap().is_none(), "resetting revokes");
+    assert_eq!(db.calendar_feed_user(&second).await.unwrap(), Some(alice.id));
+    assert!(db.get_calendar_feed(alice.id).await.unwrap().is_some());
+
+    assert!(db.delete_calendar_feed(alice.id).await.unwrap());
+    assert!(db.calendar_feed_user(&second).await.unwrap().is_none());
+
+    let created = db
+        .create_dav_todo(alice.id, "phone-task.ics", Some("phone-task"), "From my phone", true)
+        .await
+        .unwrap();
+    assert!(created.completed);
+    let resources = db.list_dav_resources(alice.id).await.unwrap();
+    assert_eq!(resources.len(), 1);
+    assert_eq!(resources[0].todo_id, created.id);
+    assert_eq!(resources[0].name, "phone-task.ics");
+    assert_eq!(resources[0].uid, "phone-task");
+
+    // A name that's taken leaves no todo behind
+    let taken = db
+        .create_dav_todo(alice.id, "phone-task.ics", None, "Twice", false)
+        .await;
+    assert!(taken.is_err());
+    assert_eq!(db.get_user_todos(alice.id).await.unwrap().len(), 1);
+
+    let generated = db.create_dav_todo(alice.id, "other.ics", None, "No UID", false).await.unwrap();
+    let resources = db.list_dav_resources(alice.id).await.unwrap();
+    let resource = resources.iter().find(|resource| resource.todo_id == generated.id).unwrap();
+    assert_eq!(resource.uid, format!("todo-{}@todo-leptos", generated.id));
+}
+
+async fn webhook_deliveries(db: Database) {
+    let alice = user(&db, "alice").await;
+    let (secret, webhook) = db
+        .create_webhook(alice.id, "http://127.0.0.1:9/hook", &[WebhookEvent::TodoCreated])
+        .await
+        .unwrap();
+    assert!(!secret.is_empty());
+    assert_eq!(webhook.events, [WebhookEvent::TodoCreated]);
+    assert_eq!(db.list_webhooks(alice.id).await.unwrap().len(), 1);
+
+    let id = todo(&db, &alice, "Announce me").await;
+    db.delete_user_todo(alice.id, id, None).await.unwrap();
+
+    // Only the subscribed event is queued
+    let due = db.due_webhook_deliveries(10).await.unwrap();
+    assert_eq!(due.len(), 1);
+    assert_eq!(due[0].event, "todo.created");
+    assert_eq!(due[0].secret, secret);
+    assert!(due[0].payload.contains("Announce me"));
+
+    let retry_at = Utc::now() + Duration::minutes(5);
+    db.mark_webhook_attempt_failed(due[0].id, Some(500), "server error", Some(retry_at))
+        .await
+        .unwrap();
+    assert!(db.due_webhook_deliveries(10).await.unwrap().is_empty());
+
+    let log = db.list_webhook_deliveries(alice.id, 10).await.unwrap();
+    assert_eq!(log[0].status, "pending");
+    assert_eq!(log[0].attempts, 1);
+    assert_eq!(log[0].last_status_code, Some(500));
+
+    db.mark_webhook_delivered(due[0].id, 204).await.unwrap();
+    let log = db.list_webhook_deliveries(alice.id, 10).await.unwrap();
+    assert_eq!(log[0].status, "delivered");
+    assert_eq!(log[0].attempts, 2);
+    assert!(log[0].delivered_at.is_some());
+
+    // Finished deliveries are purged once old enough, pending ones never
+    todo(&db, &alice, "Give up on me").await;
+    todo(&db, &alice, "Still pending").await;
+    let due = db.due_webhook_deliveries(10).await.unwrap();
+    db.mark_webhook_attempt_failed(due[0].id, None, "refused", None)
+        .await
+        .unwrap();
+    assert_eq!(db.purge_webhook_deliveries(Utc::now() - Duration::days(1)).await.unwrap(), 0);
+    assert_eq!(db.purge_webhook_deliveries(Utc::now() + Duration::minutes(1)).await.unwrap(), 2);
+    let log = db.list_webhook_deliveries(alice.id, 10).await.unwrap();
+    assert_eq!(log.len(), 1);
+    assert_eq!(log[0].status, "pending");
+
+    assert!(db.delete_webhook(alice.id, webhook.id).await.unwrap());
+    assert!(db.list_webhooks(alice.id).await.unwrap().is_empty());
+}
+
+async fn optimize(db: Database) {
+    db.optimize().await.unwrap();
+}
+
+async fn counts_for_metrics(db: Database) {
+    let alice = user(&db, "alice").await;
+    let bob = user(&db, "bob").await;
+    let before = Utc::now() - Duration::seconds(1);
+    todo(&db, &alice, "Water the plants").await;
+    todo(&db, &bob, "Call mum").await;
+    db.create_session(alice.id, Duration::hours(1), Duration::days(1))
+        .await
+        .unwrap();
+    db.create_session(bob.id, Duration::hours(-1), Duration::days(1))
+        .await
+        .unwrap();
+
+    assert_eq!(db.count_todos_created_since(before).await.unwrap(), 2);
+    assert_eq!(db.count_todos_created_since(Utc::now() + Duration::hours(1)).await.unwrap(), 0);
+    assert_eq!(db.count_active_sessions().await.unwrap(), 1);
+
+    let pools = db.pool_stats();
+    assert!(!pools.is_empty());
+    assert!(pools.iter().all(|pool| pool.idle <= pool.size as usize && pool.size <= pool.max_connections));
+}
+
+#[tokio::test]
+async fn sqlite_files_are_created_in_wal_mode_with_foreign_keys() {
+    let dir = std::env::temp_dir().join(format!("todo-leptos-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::create_dir(&dir).unwrap();
+    let url = format!("sqlite:{}", dir.join("todos.db").display());
+    let db = Database::connect(&DatabaseConfig {
+        url: url.clone(),
+        ..DatabaseConfig::default()
+    })
+    .await
+    .unwrap();
+
+    let alice = user(&db, "alice").await;
+    let id = todo(&db, &alice, "Written by the writer").await;
+    // Reads go through the other pool and still see the write
+    assert!(db.get_user_todo_by_id(alice.id, id).await.unwrap().is_some());
+
+    let mut connection = SqliteConnection::connect(&url).await.unwrap();
+    let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+        .fetch_one(&mut connection)
+        .await
+        .unwrap();
+    assert_eq!(journal_mode, "wal");
+
+    // Deleting the todo only takes its CalDAV name along when the
+    // connection enforces foreign keys
+    let id = db.create_dav_todo(alice.id, "task.ics", None, "Task", false).await.unwrap().id;
+    db.delete_user_todo(alice.id, id, None).await.unwrap();
+    assert!(db.list_dav_resources(alice.id).await.unwrap().is_empty());
+
+    connection.close().await.unwrap();
+    db.close().await;
+    std::fs::remove_dir_all(dir).unwrap();
+}
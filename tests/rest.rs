@@ -0,0 +1,55 @@
+//! Conditional requests against the `/api/v1` todo handlers.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use axum::body::Body;
+use axum::http::{header, Method, Request, Response, StatusCode};
+use axum::{Extension, Router};
+use todo_leptos::auth::CurrentUser;
+use todo_leptos::models::User;
+use todo_leptos::rest;
+use todo_leptos::testing::TestApp;
+use tower::ServiceExt;
+
+fn router(app: &TestApp, user: &User) -> Router {
+    rest::router(app.db.clone(), app.config.auth.clone())
+        .layer(Extension(CurrentUser::session(Some(user.clone()))))
+}
+
+async fn send(router: &Router, method: Method, path: &str, if_match: Option<&str>, body: &str) -> Response<Body> {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(path)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(etag) = if_match {
+        request = request.header(header::IF_MATCH, etag);
+    }
+    let request = request.body(Body::from(body.to_string())).unwrap();
+    router.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn writes_with_a_stale_etag_are_refused() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let todo = app.todo(&alice, "Draft").await;
+    let router = router(&app, &alice);
+    let path = format!("/todos/{}", todo.id);
+
+    let read = send(&router, Method::GET, &path, None, "").await;
+    let etag = read.headers()[header::ETAG].to_str().unwrap().to_string();
+
+    // Both within the same second, so only the version tells them apart
+    let first = send(&router, Method::PATCH, &path, Some(&etag), r#"{"title":"Final"}"#).await;
+    assert_eq!(first.status(), StatusCode::OK);
+    assert_ne!(first.headers()[header::ETAG], etag.as_str());
+    let second = send(&router, Method::PATCH, &path, Some(&etag), r#"{"completed":true}"#).await;
+    assert_eq!(second.status(), StatusCode::PRECONDITION_FAILED);
+
+    let deleted = send(&router, Method::DELETE, &path, Some(&etag), "").await;
+    assert_eq!(deleted.status(), StatusCode::PRECONDITION_FAILED);
+
+    let stored = app.db.get_user_todo_by_id(alice.id, todo.id).await.unwrap().unwrap();
+    assert_eq!((stored.title.as_str(), stored.completed), ("Final", false));
+}
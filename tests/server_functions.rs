@@ -0,0 +1,196 @@
+//! Server functions called directly through the test harness, without
+//! starting a server.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use todo_leptos::api_tokens::generate_api_token;
+use todo_leptos::auth::{get_current_user, login, logout, register};
+use todo_leptos::error::AppError;
+use todo_leptos::models::{CreateApiToken, CreateTodo, LoginUser, RegisterUser, TokenScope};
+use todo_leptos::server_functions::{add_todo, delete_todo, get_todo_by_id, get_todos, toggle_todo};
+use todo_leptos::testing::{TestApp, PASSWORD};
+
+fn new_todo(title: &str) -> CreateTodo {
+    CreateTodo {
+        title: title.to_string(),
+    }
+}
+
+fn credentials(username: &str, password: &str) -> LoginUser {
+    LoginUser {
+        username: username.to_string(),
+        password: password.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn login_starts_a_session_and_logout_ends_it() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let mut client = app.client();
+    let cookie_name = app.session_config().cookie_name.clone();
+
+    assert!(app.call(&mut client, get_current_user()).await.unwrap().is_none());
+    assert_eq!(
+        app.call(&mut client, add_todo(new_todo("Milk"))).await.unwrap_err(),
+        AppError::Unauthorized
+    );
+
+    let wrong = app.call(&mut client, login(credentials("alice", "hunter2"))).await;
+    assert_eq!(wrong.unwrap_err(), AppError::Unauthorized);
+    assert!(client.cookie(&cookie_name).is_none());
+
+    let user = app.call(&mut client, login(credentials("alice", PASSWORD))).await.unwrap();
+    assert_eq!(user.id, alice.id);
+    let session = client.cookie(&cookie_name).expect("session cookie").to_string();
+
+    let todo = app.call(&mut client, add_todo(new_todo("  Milk  "))).await.unwrap();
+    assert_eq!(todo.title, "Milk");
+    let current = app.call(&mut client, get_current_user()).await.unwrap();
+    assert_eq!(current.map(|user| user.id), Some(alice.id));
+
+    // Logging in again replaces the session
+    app.call(&mut client, login(credentials("alice", PASSWORD))).await.unwrap();
+    assert_ne!(client.cookie(&cookie_name), Some(session.as_str()));
+    assert!(app.db.get_session(&session).await.unwrap().is_none());
+
+    app.call(&mut client, logout()).await.unwrap();
+    assert!(client.cookie(&cookie_name).is_none());
+    assert_eq!(
+        app.call(&mut client, get_todos()).await.unwrap_err(),
+        AppError::Unauthorized
+    );
+}
+
+#[tokio::test]
+async fn register_validates_and_rejects_taken_names() {
+    let app = TestApp::new().await;
+    let mut client = app.client();
+    let registration = |username: &str, email: &str, password: &str| RegisterUser {
+        username: username.to_string(),
+        email: email.to_string(),
+        password: password.to_string(),
+    };
+
+    let invalid = app
+        .call(&mut client, register(registration(" ", "not-an-email", "short")))
+        .await;
+    let Err(AppError::Validation { fields }) = invalid else {
+        panic!("expected validation errors, got {invalid:?}");
+    };
+    assert_eq!(fields.keys().collect::<Vec<_>>(), ["email", "password", "username"]);
+
+    let alice = app
+        .call(&mut client, register(registration("alice", "alice@example.com", PASSWORD)))
+        .await
+        .unwrap();
+    assert!(!alice.is_admin, "signing up never makes an administrator");
+
+    let taken = app
+        .call(&mut client, register(registration("bob", "alice@example.com", PASSWORD)))
+        .await;
+    assert!(matches!(taken, Err(AppError::Conflict(_))));
+}
+
+#[tokio::test]
+async fn todos_belong_to_their_owner() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let bob = app.user("bob").await;
+    let theirs = app.todo(&bob, "Bob's").await;
+    let mut client = app.signed_in(&alice).await;
+
+    let mine = app.call(&mut client, add_todo(new_todo("Alice's"))).await.unwrap();
+    let todos = app.call(&mut client, get_todos()).await.unwrap();
+    assert_eq!(todos.iter().map(|todo| todo.id).collect::<Vec<_>>(), [mine.id]);
+
+    assert_eq!(
+        app.call(&mut client, get_todo_by_id(theirs.id)).await.unwrap_err(),
+        AppError::NotFound
+    );
+    assert_eq!(
+        app.call(&mut client, delete_todo(theirs.id, None)).await.unwrap_err(),
+        AppError::NotFound
+    );
+    assert_eq!(
+        app.call(&mut client, add_todo(new_todo("   "))).await.unwrap_err(),
+        AppError::validation("title", "Title cannot be empty.")
+    );
+}
+
+#[tokio::test]
+async fn stale_changes_are_refused() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let todo = app.todo(&alice, "Water the plants").await;
+    let mut client = app.signed_in(&alice).await;
+
+    let stale = Some("2000-01-01 00:00:00".to_string());
+    assert_eq!(
+        app.call(&mut client, toggle_todo(todo.id, true, stale.clone())).await.unwrap_err(),
+        AppError::PreconditionFailed
+    );
+    assert_eq!(
+        app.call(&mut client, delete_todo(todo.id, stale)).await.unwrap_err(),
+        AppError::PreconditionFailed
+    );
+    // A timestamp that can't be read can't be the current one
+    assert_eq!(
+        app.call(&mut client, toggle_todo(todo.id, true, Some("yesterday".to_string())))
+            .await
+            .unwrap_err(),
+        AppError::PreconditionFailed
+    );
+
+    // The same moment written the ISO 8601 way still matches
+    let iso = todo.updated_at.replacen(' ', "T", 1);
+    let done = app
+        .call(&mut client, toggle_todo(todo.id, true, Some(iso)))
+        .await
+        .unwrap();
+    assert!(done.completed);
+    app.call(&mut client, delete_todo(todo.id, Some(done.updated_at)))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn api_tokens_are_limited_to_their_scope() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    app.todo(&alice, "Read me").await;
+    let mut session = app.signed_in(&alice).await;
+
+    let issued = app
+        .call(
+            &mut session,
+            generate_api_token(CreateApiToken {
+                name: "phone".to_string(),
+                scope: TokenScope::Read,
+                expires_in_days: None,
+            }),
+        )
+        .await
+        .unwrap();
+
+    let mut phone = app.client().bearer(&issued.token);
+    assert_eq!(app.call(&mut phone, get_todos()).await.unwrap().len(), 1);
+    assert_eq!(
+        app.call(&mut phone, add_todo(new_todo("Nope"))).await.unwrap_err(),
+        AppError::Forbidden
+    );
+
+    // Tokens can't mint more tokens
+    let minted = app
+        .call(
+            &mut phone,
+            generate_api_token(CreateApiToken {
+                name: "more".to_string(),
+                scope: TokenScope::Write,
+                expires_in_days: None,
+            }),
+        )
+        .await;
+    assert_eq!(minted.unwrap_err(), AppError::Forbidden);
+}
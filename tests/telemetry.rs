@@ -0,0 +1,103 @@
+//! Request ids and the `request` span the server logs under.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use todo_leptos::auth::middleware::resolve_session;
+use todo_leptos::rest;
+use todo_leptos::telemetry::{self, REQUEST_ID_HEADER};
+use todo_leptos::testing::TestApp;
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Collects what the subscriber writes.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Captured {
+    fn text(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn requests_get_an_id_unless_they_bring_one() {
+    let app = telemetry::trace_requests(Router::new().route("/", get(|| async { "ok" })));
+
+    let response = app.clone().oneshot(Request::get("/").body(Body::empty()).unwrap()).await.unwrap();
+    let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap();
+    assert!(uuid::Uuid::parse_str(generated).is_ok(), "{generated}");
+
+    let request = Request::get("/")
+        .header(REQUEST_ID_HEADER, "from-the-proxy")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.headers()[REQUEST_ID_HEADER], "from-the-proxy");
+}
+
+#[tokio::test]
+async fn the_request_span_names_the_route_and_the_user() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let token = app
+        .db
+        .create_api_token(alice.id, "logs", todo_leptos::models::TokenScope::Read, None)
+        .await
+        .unwrap()
+        .0;
+    let router = telemetry::trace_requests(
+        Router::new()
+            .nest("/api/v1", rest::router(app.db.clone(), app.config.auth.clone()))
+            .route_layer(axum::middleware::from_fn_with_state(
+                (app.db.clone(), app.session_config().clone()),
+                resolve_session,
+            )),
+    );
+
+    let captured = Captured::default();
+    let subscriber = tracing_subscriber::registry().with(
+        tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer({
+                let captured = captured.clone();
+                move || captured.clone()
+            }),
+    );
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let request = Request::get("/api/v1/todos/12345")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .header(REQUEST_ID_HEADER, "req-1")
+        .body(Body::empty())
+        .unwrap();
+    let response = router.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let logs = captured.text();
+    let line = logs
+        .lines()
+        .find(|line| line.contains("finished processing request"))
+        .unwrap_or_else(|| panic!("no response logged in:\n{logs}"));
+    assert!(line.contains(r#"request_id="req-1""#), "{line}");
+    assert!(line.contains(r#"route="/api/v1/todos/{id}""#), "{line}");
+    assert!(line.contains(&format!("user_id={}", alice.id)), "{line}");
+    assert!(line.contains("status=404"), "{line}");
+}
@@ -0,0 +1,259 @@
+//! Webhook delivery end to end against the local test receiver.
+//!
+//! Run with `cargo test --features ssr`.
+#![cfg(feature = "ssr")]
+
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use todo_leptos::database::Database;
+use todo_leptos::error::AppError;
+use todo_leptos::models::{CreateTodo, CreateWebhook, RegisterUser, UpdateTodo, User, WebhookEvent};
+use todo_leptos::testing::TestApp;
+use todo_leptos::webhooks::add_webhook;
+use todo_leptos::webhooks::delivery::{self, Destinations};
+use todo_leptos::webhooks::receiver::TestReceiver;
+
+async fn setup() -> (Database, SqlitePool, User) {
+    // A single connection, so every query sees the same in-memory database
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations/sqlite").run(&pool).await.unwrap();
+
+    let db = Database::sqlite(pool.clone());
+
+    let user = db
+        .create_user(
+            RegisterUser {
+                username: "alice".to_string(),
+                email: "alice@example.com".to_string(),
+                password: "secret1".to_string(),
+            },
+            // The cheapest cost bcrypt allows, to keep the tests quick
+            4,
+        )
+        .await
+        .unwrap();
+
+    (db, pool, user)
+}
+
+async fn create_todo(db: &Database, user: &User, title: &str) -> i64 {
+    db.create_user_todo(
+        user.id,
+        CreateTodo {
+            title: title.to_string(),
+        },
+    )
+    .await
+    .unwrap()
+    .id
+}
+
+#[tokio::test]
+async fn delivers_signed_events_in_order() {
+    let (db, _, user) = setup().await;
+    let mut receiver = TestReceiver::start().await.unwrap();
+    let (secret, _) = db.create_webhook(user.id, &receiver.url(), &WebhookEvent::ALL)
+        .await
+        .unwrap();
+
+    let id = create_todo(&db, &user, "write tests").await;
+    let complete = UpdateTodo {
+        completed: Some(true),
+        ..Default::default()
+    };
+    db.update_user_todo(user.id, id, complete.clone(), None)
+        .await
+        .unwrap();
+    // Already complete, so no second `todo.completed`
+    db.update_user_todo(user.id, id, complete, None)
+        .await
+        .unwrap();
+    db.delete_user_todo(user.id, id, None).await.unwrap();
+
+    delivery::deliver_due(&db, Destinations::Any).await.unwrap();
+
+    for expected in ["todo.created", "todo.completed", "todo.deleted"] {
+        let hook = receiver.recv().await.expect("webhook delivered");
+        assert_eq!(hook.event.as_deref(), Some(expected));
+        assert!(hook.verify(&secret), "bad signature on {expected}");
+        assert!(!hook.verify("whsec_wrong"));
+
+        let body = hook.json();
+        assert_eq!(body["event"], expected);
+        assert_eq!(body["todo"]["id"], id);
+        assert_eq!(body["todo"]["title"], "write tests");
+    }
+    assert!(receiver.try_recv().is_none());
+
+    let log = db.list_webhook_deliveries(user.id, 10)
+        .await
+        .unwrap();
+    assert_eq!(log.len(), 3);
+    assert!(log.iter().all(|d| d.status == "delivered" && d.attempts == 1));
+}
+
+#[tokio::test]
+async fn only_subscribed_events_are_queued() {
+    let (db, _, user) = setup().await;
+    let mut receiver = TestReceiver::start().await.unwrap();
+    db.create_webhook(user.id, &receiver.url(), &[WebhookEvent::TodoDeleted])
+        .await
+        .unwrap();
+
+    let id = create_todo(&db, &user, "ignored").await;
+    db.delete_user_todo(user.id, id, None).await.unwrap();
+    delivery::deliver_due(&db, Destinations::Any).await.unwrap();
+
+    let hook = receiver.recv().await.expect("webhook delivered");
+    assert_eq!(hook.event.as_deref(), Some("todo.deleted"));
+    assert!(receiver.try_recv().is_none());
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_with_backoff() {
+    let (db, pool, user) = setup().await;
+    let mut receiver = TestReceiver::start().await.unwrap();
+    db.create_webhook(user.id, &receiver.url(), &WebhookEvent::ALL)
+        .await
+        .unwrap();
+    receiver.fail_next(1);
+
+    create_todo(&db, &user, "flaky").await;
+    delivery::deliver_due(&db, Destinations::Any).await.unwrap();
+
+    let hook = receiver.recv().await.expect("first attempt made");
+    assert_eq!(hook.responded_with.as_u16(), 500);
+
+    let log = db.list_webhook_deliveries(user.id, 10)
+        .await
+        .unwrap();
+    assert_eq!(log[0].status, "pending");
+    assert_eq!(log[0].attempts, 1);
+    assert_eq!(log[0].last_status_code, Some(500));
+
+    // Not due again until the backoff has passed
+    delivery::deliver_due(&db, Destinations::Any).await.unwrap();
+    assert!(receiver.try_recv().is_none());
+
+    sqlx::query("UPDATE webhook_deliveries SET next_attempt_at = datetime('now')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    delivery::deliver_due(&db, Destinations::Any).await.unwrap();
+
+    let hook = receiver.recv().await.expect("retried");
+    assert_eq!(hook.responded_with.as_u16(), 204);
+    let log = db.list_webhook_deliveries(user.id, 10)
+        .await
+        .unwrap();
+    assert_eq!(log[0].status, "delivered");
+    assert_eq!(log[0].attempts, 2);
+}
+
+#[test]
+fn backoff_doubles_up_to_a_cap() {
+    assert_eq!(delivery::backoff(1).num_seconds(), 30);
+    assert_eq!(delivery::backoff(2).num_seconds(), 60);
+    assert_eq!(delivery::backoff(5).num_seconds(), 480);
+    assert_eq!(delivery::backoff(30).num_seconds(), 6 * 60 * 60);
+}
+
+#[tokio::test]
+async fn only_public_addresses_can_be_registered() {
+    let app = TestApp::new().await;
+    let alice = app.user("alice").await;
+    let mut client = app.signed_in(&alice).await;
+    let add = |url: &str| {
+        add_webhook(CreateWebhook {
+            url: url.to_string(),
+            events: WebhookEvent::ALL.to_vec(),
+        })
+    };
+
+    for url in [
+        "http://localhost:8080/hook",
+        "http://127.0.0.1/hook",
+        "http://[::1]/hook",
+        "http://10.0.0.5/hook",
+        "http://192.168.1.1/hook",
+        "http://169.254.169.254/latest/meta-data/",
+        "http://0.0.0.0/hook",
+    ] {
+        let refused = app.call(&mut client, add(url)).await;
+        let Err(AppError::Validation { fields }) = refused else {
+            panic!("{url} was accepted: {refused:?}");
+        };
+        assert!(fields.contains_key("url"), "{url}: {fields:?}");
+    }
+    assert!(app.db.list_webhooks(alice.id).await.unwrap().is_empty());
+
+    // Literal public addresses need no DNS
+    let (host, addresses) = delivery::resolve_public("https://93.184.216.34/hook").await.unwrap();
+    assert_eq!(host, "93.184.216.34");
+    assert_eq!(addresses, ["93.184.216.34:443".parse().unwrap()]);
+}
+
+#[tokio::test]
+async fn reserved_and_embedded_private_addresses_are_refused() {
+    for address in [
+        // IETF protocol assignments, benchmarking and reserved
+        "192.0.0.8",
+        "198.18.0.1",
+        "198.19.255.254",
+        "240.0.0.1",
+        "255.255.255.255",
+        // 10.0.0.5 and 127.0.0.1 through IPv4-mapped, NAT64 and 6to4
+        "[::ffff:10.0.0.5]",
+        "[64:ff9b::a00:5]",
+        "[2002:7f00:1::1]",
+        // Teredo with client 192.168.1.1, stored as its complement
+        "[2001:0:4136:e378:8000:63bf:3f57:fefe]",
+    ] {
+        let refused = delivery::resolve_public(&format!("http://{address}/hook")).await;
+        assert!(
+            refused.as_ref().is_err_and(|e| e.contains("isn't a public address")),
+            "{address}: {refused:?}"
+        );
+    }
+
+    // The same embeddings of 93.184.216.34 are fine
+    for address in [
+        "[::ffff:93.184.216.34]",
+        "[64:ff9b::5db8:d822]",
+        "[2002:5db8:d822::1]",
+        "[2001:0:4136:e378:8000:63bf:a247:27dd]",
+        "198.20.0.1",
+    ] {
+        let accepted = delivery::resolve_public(&format!("http://{address}/hook")).await;
+        assert!(accepted.is_ok(), "{address}: {accepted:?}");
+    }
+}
+
+#[tokio::test]
+async fn deliveries_to_private_addresses_are_refused() {
+    let (db, _, user) = setup().await;
+    let mut receiver = TestReceiver::start().await.unwrap();
+    db.create_webhook(user.id, &receiver.url(), &WebhookEvent::ALL)
+        .await
+        .unwrap();
+
+    // Stored directly, as if the host had resolved elsewhere when the webhook
+    // was added
+    create_todo(&db, &user, "rebound").await;
+    delivery::deliver_due(&db, Destinations::Public).await.unwrap();
+
+    assert!(receiver.try_recv().is_none());
+    let log = db.list_webhook_deliveries(user.id, 10)
+        .await
+        .unwrap();
+    assert_eq!(log[0].status, "pending");
+    assert!(
+        log[0].last_error.as_deref().is_some_and(|e| e.contains("isn't a public address")),
+        "{:?}",
+        log[0].last_error
+    );
+}